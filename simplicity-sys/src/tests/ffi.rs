@@ -512,14 +512,15 @@ extern "C" {
     pub static schnorr6_imr: [u32; 8];
     pub static schnorr6_cost: ubounded;
 
-    /*
-    // FIXME enable this test; is not 1->1, requires extra frame setup
+    // Not a 1->1 program; running it requires extra frame setup
     pub static sizeof_hashBlock: c_size_t;
     pub static hashBlock: [u8; 3259];
     pub static hashBlock_amr: [u32; 8];
     pub static hashBlock_cmr: [u32; 8];
     pub static hashBlock_imr: [u32; 8];
+    pub static hashBlock_cost: ubounded;
 
+    /*
     // FIXME enable this test; requires a little but of extra work to set up an Elements env
     pub static elementsCheckSigHashAllTx1: [u8; 1151];
     pub static elementsCheckSigHashAllTx1_amr: [u32; 8];
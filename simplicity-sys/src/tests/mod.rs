@@ -315,6 +315,18 @@ mod test_data {
         }
     }
 
+    pub fn hash_block_test_data() -> TestData {
+        unsafe {
+            TestData {
+                cmr: parse_root(&ffi::hashBlock_cmr),
+                amr: parse_root(&ffi::hashBlock_amr),
+                imr: parse_root(&ffi::hashBlock_imr),
+                prog: slice::from_raw_parts(ffi::hashBlock.as_ptr(), ffi::sizeof_hashBlock).into(),
+                cost: ffi::hashBlock_cost,
+            }
+        }
+    }
+
     pub fn ctx8_pruned_test_data() -> TestData {
         unsafe {
             TestData {
@@ -162,22 +162,25 @@ impl<I: Iterator<Item = u8>> BitIter<I> {
             + (self.cached_byte >> (8 - self.read_bits)))
     }
 
-    /// Reads a 256-bit CMR from the iterator.
-    pub fn read_cmr(&mut self) -> Result<Cmr, EarlyEndOfStreamError> {
-        let mut ret = [0; 32];
+    /// Reads `N` bytes from the iterator.
+    ///
+    /// The iterator does not need to be at a byte boundary.
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], EarlyEndOfStreamError> {
+        let mut ret = [0; N];
         for byte in &mut ret {
             *byte = self.read_u8()?;
         }
-        Ok(Cmr::from_byte_array(ret))
+        Ok(ret)
+    }
+
+    /// Reads a 256-bit CMR from the iterator.
+    pub fn read_cmr(&mut self) -> Result<Cmr, EarlyEndOfStreamError> {
+        self.read_array().map(Cmr::from_byte_array)
     }
 
     /// Reads a 512-bit fail-combinator entropy from the iterator.
     pub fn read_fail_entropy(&mut self) -> Result<FailEntropy, EarlyEndOfStreamError> {
-        let mut ret = [0; 64];
-        for byte in &mut ret {
-            *byte = self.read_u8()?;
-        }
-        Ok(FailEntropy::from_byte_array(ret))
+        self.read_array().map(FailEntropy::from_byte_array)
     }
 
     /// Decode a value from bits, based on the given type.
@@ -231,10 +234,14 @@ impl<I: Iterator<Item = u8>> BitIter<I> {
 
     /// Decode a natural number from bits.
     ///
-    /// If a bound is specified, then the decoding terminates before trying to
-    /// decode a larger number.
+    /// The number must fit into 32 bits. If a bound is specified, then
+    /// numbers larger than the bound are rejected as bad indices.
     pub fn read_natural(&mut self, bound: Option<usize>) -> Result<usize, decode::Error> {
-        decode::decode_natural(self, bound)
+        let n = decode::decode_natural(self, 32)?;
+        match bound {
+            Some(bound) if n > bound => Err(decode::Error::BadIndex),
+            _ => Ok(n),
+        }
     }
 
     /// Accessor for the number of bits which have been read,
@@ -259,6 +266,34 @@ mod tests {
         assert_eq!(iter.n_total_read(), 0);
     }
 
+    #[test]
+    fn read_array() {
+        let data: Vec<u8> = (0..=32).collect();
+
+        let mut aligned = BitIter::from(&data[..]);
+        let array: [u8; 32] = aligned.read_array().unwrap();
+        assert_eq!(&array[..], &data[..32]);
+        assert_eq!(aligned.n_total_read(), 256);
+
+        let mut unaligned = BitIter::from(&data[..]);
+        for _ in 0..4 {
+            unaligned.read_bit().unwrap();
+        }
+        let array: [u8; 32] = unaligned.read_array().unwrap();
+        let expected: Vec<u8> = data.windows(2).map(|w| (w[0] << 4) | (w[1] >> 4)).collect();
+        assert_eq!(&array[..], &expected[..]);
+        assert_eq!(unaligned.n_total_read(), 260);
+
+        // Fewer than 32 bytes remain
+        let mut short = BitIter::from(&data[..32]);
+        short.read_bit().unwrap();
+        assert_eq!(short.read_array::<32>(), Err(EarlyEndOfStreamError));
+        assert_eq!(
+            BitIter::from(&data[..31]).read_array::<32>(),
+            Err(EarlyEndOfStreamError)
+        );
+    }
+
     #[test]
     fn one_bit_iter() {
         let mut iter = BitIter::from([0x80].iter().cloned());
@@ -5,6 +5,17 @@ use std::io;
 /// Bitwise writer formed by wrapping a bytewise [`io::Write`].
 /// Bits are written in big-endian order.
 /// Bytes are filled with zeroes for padding.
+///
+/// At most one byte is buffered: a complete byte is passed on to the underlying
+/// writer when the next bit is written, or by [`BitWriter::flush_all`].
+/// This makes it possible to stream large programs to a network peer.
+///
+/// If the underlying writer returns an error, such as [`io::ErrorKind::WouldBlock`],
+/// from [`BitWriter::write_bit`] or [`BitWriter::flush_all`], then the state of the
+/// writer is left as it was before the call, and the call can be retried. This does
+/// not hold for methods that write several bits, such as [`BitWriter::write_bits_be`],
+/// or for encoders such as [`crate::encode::encode_program`]. They abort on the first
+/// error and cannot be resumed, leaving a prefix of their output in the writer.
 pub struct BitWriter<W: io::Write> {
     /// Byte writer
     w: W,
@@ -131,4 +142,97 @@ mod tests {
         let vec = write_to_vec(|_| Ok(0));
         assert!(vec.is_empty());
     }
+
+    /// Writer that accepts at most one byte per call,
+    /// and that refuses every other call.
+    struct TrickleWriter {
+        bytes: Vec<u8>,
+        block: bool,
+    }
+
+    impl io::Write for TrickleWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.block = !self.block;
+            if self.block {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            match buf.first() {
+                Some(byte) => {
+                    self.bytes.push(*byte);
+                    Ok(1)
+                }
+                None => Ok(0),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Retry the given write until the writer no longer blocks.
+    fn retry<F: FnMut() -> io::Result<()>>(mut f: F) {
+        loop {
+            match f() {
+                Ok(()) => return,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn would_block() {
+        let bits = [
+            true, false, true, true, false, false, true, false, true, true,
+        ];
+        let mut w = BitWriter::new(TrickleWriter {
+            bytes: vec![],
+            block: false,
+        });
+        for bit in bits {
+            retry(|| w.write_bit(bit));
+        }
+        retry(|| w.flush_all());
+
+        assert_eq!(w.n_total_written(), bits.len());
+        assert_eq!(w.w.bytes, [0b1011_0010, 0b1100_0000]);
+    }
+
+    #[test]
+    fn one_byte_at_a_time() {
+        use crate::jet::Core;
+        use crate::node::SimpleFinalizer;
+        use crate::{BitIter, CommitNode, RedeemNode, Value};
+
+        /// Writer that accepts at most one byte per call.
+        struct OneByteWriter(Vec<u8>);
+
+        impl io::Write for OneByteWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.extend(buf.first());
+                Ok(buf.len().min(1))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // main = comp (pair wit1 wit2) jet_eq_32 followed by jet_verify
+        let eqwits = [0xcd, 0xdc, 0x51, 0xb6, 0xe2, 0x08, 0xc0, 0x40];
+        let program = CommitNode::<Core>::decode(&mut BitIter::from(&eqwits[..]))
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(
+                vec![Value::u32(7), Value::u32(8)].into_iter(),
+            ))
+            .unwrap();
+
+        let mut w = BitWriter::new(OneByteWriter(vec![]));
+        program.encode(&mut w).unwrap();
+        assert_eq!(w.w.0, program.encode_to_vec());
+
+        let decoded = RedeemNode::<Core>::decode(&mut BitIter::from(&w.w.0[..])).unwrap();
+        assert_eq!(decoded, program);
+    }
 }
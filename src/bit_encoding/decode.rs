@@ -5,30 +5,44 @@
 //! Functionality to decode Simplicity programs.
 //! Refer to [`crate::encode`] for information on the encoding.
 
-use crate::dag::{Dag, DagLike, InternalSharing};
+use crate::dag::{Dag, DagLike, InternalSharing, MaxSharing};
 use crate::jet::Jet;
 use crate::merkle::cmr::Cmr;
 use crate::node::{
-    ConstructNode, CoreConstructible, DisconnectConstructible, JetConstructible, NoWitness,
-    WitnessConstructible,
+    Commit, CommitNode, ConstructNode, CoreConstructible, DisconnectConstructible,
+    JetConstructible, NoWitness, RedeemNode, WitnessConstructible,
 };
 use crate::{BitIter, FailEntropy, Value};
+use hex::FromHex;
 use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::str::FromStr;
 use std::sync::Arc;
-use std::{error, fmt};
+use std::{cmp, error, fmt, iter};
 
 use super::bititer::u2;
 
 type ArcNode<J> = Arc<ConstructNode<J>>;
 
+/// Maximum number of nodes of a decoded program.
+// FIXME: check maximum length of DAG that is allowed by consensus
+const MAX_NODES: usize = 1_000_000;
+
 /// Decoding error
 #[non_exhaustive]
 #[derive(Debug)]
 pub enum Error {
+    /// Line of an assembly listing is not a valid node
+    AsmSyntax(usize),
     /// Node made a back-reference past the beginning of the program
     BadIndex,
+    /// Container does not start with the magic bytes
+    BadContainerMagic,
     /// Both children of a node are hidden
     BothChildrenHidden,
+    /// Decoded program does not have the expected CMR
+    CmrMismatch { expected: Cmr, found: Cmr },
     /// Program must not be empty
     EmptyProgram,
     /// Bitstream ended early
@@ -37,8 +51,12 @@ pub enum Error {
     HiddenNode,
     /// Tried to parse a jet but the name wasn't recognized
     InvalidJet,
-    /// Number exceeded 32 bits
+    /// Program has witness nodes, but the encoding has no witness data
+    MissingWitnessBlock,
+    /// Number exceeded the allowed number of bits
     NaturalOverflow,
+    /// Container has a version that this library does not know
+    UnknownContainerVersion(u8),
     /// Program is not encoded in canonical order
     NotInCanonicalOrder,
     /// Program does not have maximal sharing
@@ -47,6 +65,8 @@ pub enum Error {
     TooManyNodes(usize),
     /// Type-checking error
     Type(crate::types::Error),
+    /// Encoding of a program without witness data is followed by witness data
+    UnexpectedWitnessBlock,
 }
 
 impl From<super::bititer::EarlyEndOfStreamError> for Error {
@@ -64,21 +84,39 @@ impl From<crate::types::Error> for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Error::AsmSyntax(line) => {
+                write!(f, "line {} of assembly listing is not a valid node", line)
+            }
             Error::BadIndex => {
                 f.write_str("node made a back-reference past the beginning of the program")
             }
+            Error::BadContainerMagic => f.write_str("container does not start with magic bytes"),
             Error::BothChildrenHidden => f.write_str("both children of a case node are hidden"),
+            Error::CmrMismatch { expected, found } => {
+                write!(f, "expected program with CMR {}, found {}", expected, found)
+            }
             Error::EmptyProgram => f.write_str("empty program"),
             Error::EndOfStream => f.write_str("bitstream ended early"),
             Error::HiddenNode => write!(f, "hidden node occurred outside of a case combinator"),
             Error::InvalidJet => write!(f, "unrecognized jet"),
-            Error::NaturalOverflow => f.write_str("encoded number exceeded 32 bits"),
+            Error::MissingWitnessBlock => {
+                f.write_str("program has witness nodes but the encoding has no witness data")
+            }
+            Error::NaturalOverflow => {
+                f.write_str("encoded number exceeded the allowed number of bits")
+            }
             Error::NotInCanonicalOrder => f.write_str("program not in canonical order"),
+            Error::UnknownContainerVersion(version) => {
+                write!(f, "unknown container version {}", version)
+            }
             Error::SharingNotMaximal => f.write_str("Decoded programs must have maximal sharing"),
             Error::TooManyNodes(k) => {
                 write!(f, "program has too many nodes ({})", k)
             }
             Error::Type(ref e) => fmt::Display::fmt(e, f),
+            Error::UnexpectedWitnessBlock => {
+                f.write_str("program without witness data is followed by witness data")
+            }
         }
     }
 }
@@ -86,17 +124,23 @@ impl fmt::Display for Error {
 impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
+            Error::AsmSyntax(..) => None,
             Error::BadIndex => None,
+            Error::BadContainerMagic => None,
             Error::BothChildrenHidden => None,
+            Error::CmrMismatch { .. } => None,
             Error::EmptyProgram => None,
             Error::EndOfStream => None,
             Error::HiddenNode => None,
             Error::InvalidJet => None,
+            Error::MissingWitnessBlock => None,
             Error::NaturalOverflow => None,
             Error::NotInCanonicalOrder => None,
+            Error::UnknownContainerVersion(..) => None,
             Error::SharingNotMaximal => None,
             Error::TooManyNodes(..) => None,
             Error::Type(ref e) => Some(e),
+            Error::UnexpectedWitnessBlock => None,
         }
     }
 }
@@ -154,41 +198,206 @@ impl<'d, J: Jet> DagLike for (usize, &'d [DecodeNode<J>]) {
 pub fn decode_expression<I: Iterator<Item = u8>, J: Jet>(
     bits: &mut BitIter<I>,
 ) -> Result<ArcNode<J>, Error> {
-    enum Converted<J: Jet> {
-        Node(ArcNode<J>),
-        Hidden(Cmr),
+    decode_expression_with_depth(bits).map(|(node, _)| node)
+}
+
+/// Decode an expression and check that it has the expected CMR.
+///
+/// Returns [`Error::CmrMismatch`] if the CMR of the decoded expression differs,
+/// for instance from the CMR that an address commits to.
+pub fn decode_expecting<I: Iterator<Item = u8>, J: Jet>(
+    bits: &mut BitIter<I>,
+    expected: Cmr,
+) -> Result<ArcNode<J>, Error> {
+    let node = decode_expression(bits)?;
+    let found = node.cmr();
+    if found == expected {
+        Ok(node)
+    } else {
+        Err(Error::CmrMismatch { expected, found })
     }
-    use Converted::{Hidden, Node};
-    impl<J: Jet> Converted<J> {
-        fn get(&self) -> Result<&ArcNode<J>, Error> {
-            match self {
-                Node(arc) => Ok(arc),
-                Hidden(_) => Err(Error::HiddenNode),
-            }
+}
+
+/// Program that was decoded by [`decode_program`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DecodedProgram<J: Jet> {
+    /// Program without witness data
+    Commit(Arc<CommitNode<J>>),
+    /// Program with witness data
+    Redeem(Arc<RedeemNode<J>>),
+}
+
+/// Decode a program, where `has_witness` says whether its encoding includes witness data.
+///
+/// Without witness data, the program is decoded as in [`CommitNode::decode`] and the
+/// remaining bits must be zero padding. Otherwise, [`Error::UnexpectedWitnessBlock`]
+/// is returned.
+///
+/// With witness data, the program is decoded as in [`RedeemNode::decode`]. If the
+/// program has witness nodes but the encoding has no witness data, then
+/// [`Error::MissingWitnessBlock`] is returned.
+pub fn decode_program<I: Iterator<Item = u8>, J: Jet>(
+    bits: &mut BitIter<I>,
+    has_witness: bool,
+) -> Result<DecodedProgram<J>, crate::Error> {
+    if has_witness {
+        RedeemNode::decode(bits).map(DecodedProgram::Redeem)
+    } else {
+        let program = CommitNode::decode(bits)?;
+        if bits.any(|bit| bit) {
+            return Err(Error::UnexpectedWitnessBlock.into());
         }
+        Ok(DecodedProgram::Commit(program))
     }
+}
 
+/// Check that the program satisfies all invariants that the decoder checks for
+/// decoded programs, without encoding it first.
+///
+/// The program must have no more nodes than the decoder accepts, it must be
+/// maximally shared, and it must type-check as a program, i.e. with unit source and
+/// target types.
+///
+/// The decoder additionally rejects nodes that are not in canonical order and
+/// hidden nodes that are not children of `case`. These are properties of the bit
+/// encoding, which an in-memory program does not have, so they are not checked here:
+/// the encoder writes every program in canonical order, and the constructors accept
+/// hidden children only as the CMRs of `assertl` and `assertr`.
+pub fn validate_program<J: Jet>(program: &CommitNode<J>) -> Result<(), crate::Error> {
+    validate_program_with_limit(program, MAX_NODES)
+}
+
+fn validate_program_with_limit<J: Jet>(
+    program: &CommitNode<J>,
+    max_nodes: usize,
+) -> Result<(), crate::Error> {
+    let len = program.post_order_iter::<MaxSharing<Commit<J>>>().count();
+    if len > max_nodes {
+        return Err(Error::TooManyNodes(len).into());
+    }
+    if !program.is_shared_as::<MaxSharing<Commit<J>>>() {
+        return Err(Error::SharingNotMaximal.into());
+    }
+    // Type inference from scratch, as after decoding
+    program.unfinalize_types()?.finalize_types()?;
+    Ok(())
+}
+
+/// Decode an expression and return it along with the maximum depth that the
+/// decoder reached while traversing it.
+///
+/// The depth is the length of the longest path from the root to a leaf, counting
+/// both ends, and bounds the size of the stack that is used during decoding.
+/// Shared nodes are counted once on each path that passes through them.
+pub fn decode_expression_with_depth<I: Iterator<Item = u8>, J: Jet>(
+    bits: &mut BitIter<I>,
+) -> Result<(ArcNode<J>, usize), Error> {
+    decode_expression_internal(bits, |_| {})
+}
+
+/// Decode an expression and return it along with the range of bits that each
+/// node occupied in the input.
+///
+/// The ranges are given in positions of [`BitIter::n_total_read`] and are listed in
+/// the order of the encoding, which is the post order of the expression with
+/// [`InternalSharing`]. The range of the first node begins after the length
+/// prefix of the expression, and the range of each other node begins where the
+/// range of the previous node ends.
+pub fn decode_expression_with_offsets<I: Iterator<Item = u8>, J: Jet>(
+    bits: &mut BitIter<I>,
+) -> Result<(ArcNode<J>, Vec<Range<usize>>), Error> {
+    let mut offsets = vec![];
+    let (node, _) = decode_expression_internal(bits, |range| offsets.push(range))?;
+    Ok((node, offsets))
+}
+
+/// Decode an expression, calling `record_offsets` with the range of bits of each node.
+fn decode_expression_internal<I, J, F>(
+    bits: &mut BitIter<I>,
+    mut record_offsets: F,
+) -> Result<(ArcNode<J>, usize), Error>
+where
+    I: Iterator<Item = u8>,
+    J: Jet,
+    F: FnMut(Range<usize>),
+{
     let len = bits.read_natural(None)?;
 
     if len == 0 {
         return Err(Error::EmptyProgram);
     }
-    // FIXME: check maximum length of DAG that is allowed by consensus
-    if len > 1_000_000 {
+    if len > MAX_NODES {
         return Err(Error::TooManyNodes(len));
     }
 
     let mut nodes = Vec::with_capacity(len);
     for _ in 0..len {
+        let start = bits.n_total_read();
         let new_node = decode_node(bits, nodes.len())?;
+        record_offsets(start..bits.n_total_read());
         nodes.push(new_node);
     }
 
+    convert_nodes(&nodes)
+}
+
+/// Decode an expression from its assembly listing, as written by
+/// [`crate::encode::encode_asm`].
+///
+/// Empty lines are ignored. The nodes are checked like those of the bit encoding,
+/// so every child must come before its parent ([`Error::BadIndex`]) and the nodes
+/// must be listed in canonical order ([`Error::NotInCanonicalOrder`]), starting
+/// at index zero. Lines that are not nodes result in [`Error::AsmSyntax`] with
+/// their line number, counting from one.
+pub fn decode_asm<J: Jet>(s: &str) -> Result<ArcNode<J>, Error> {
+    let mut nodes = vec![];
+    for (n, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let syntax = || Error::AsmSyntax(n + 1);
+        let (index, line) = line.split_once(':').ok_or_else(syntax)?;
+        if index.trim().parse::<usize>().map_err(|_| syntax())? != nodes.len() {
+            return Err(Error::NotInCanonicalOrder);
+        }
+        nodes.push(decode_asm_node(line, n + 1, nodes.len())?);
+    }
+
+    if nodes.is_empty() {
+        return Err(Error::EmptyProgram);
+    }
+    if nodes.len() > MAX_NODES {
+        return Err(Error::TooManyNodes(nodes.len()));
+    }
+    convert_nodes(&nodes).map(|(node, _)| node)
+}
+
+/// Convert decoded nodes into an expression and return it along with its depth.
+///
+/// The nodes must be in canonical order, the last one being the root.
+fn convert_nodes<J: Jet>(nodes: &[DecodeNode<J>]) -> Result<(ArcNode<J>, usize), Error> {
+    enum Converted<J: Jet> {
+        Node(ArcNode<J>),
+        Hidden(Cmr),
+    }
+    use Converted::{Hidden, Node};
+    impl<J: Jet> Converted<J> {
+        fn get(&self) -> Result<&ArcNode<J>, Error> {
+            match self {
+                Node(arc) => Ok(arc),
+                Hidden(_) => Err(Error::HiddenNode),
+            }
+        }
+    }
+
+    let len = nodes.len();
     // It is a sharing violation for any hidden node to be repeated. Track them in this set.
     let mut hidden_set = HashSet::<Cmr>::new();
     // Convert the DecodeNode structure into a CommitNode structure
     let mut converted = Vec::<Converted<J>>::with_capacity(len);
-    for data in (nodes.len() - 1, &nodes[..]).post_order_iter::<InternalSharing>() {
+    // Depth of the subexpression rooted at each node
+    let mut depths = Vec::<usize>::with_capacity(len);
+    for data in (len - 1, nodes).post_order_iter::<InternalSharing>() {
         // Check canonical order as we go
         if data.index != data.node.0 {
             return Err(Error::NotInCanonicalOrder);
@@ -234,9 +443,15 @@ pub fn decode_expression<I: Iterator<Item = u8>, J: Jet>(
             DecodeNode::Word(ref w) => Node(ArcNode::const_word(Arc::clone(w))),
         };
         converted.push(new);
+
+        let left_depth = data.left_index.map(|i| depths[i]).unwrap_or(0);
+        let right_depth = data.right_index.map(|i| depths[i]).unwrap_or(0);
+        depths.push(1 + cmp::max(left_depth, right_depth));
     }
 
-    converted[len - 1].get().map(Arc::clone)
+    converted[len - 1]
+        .get()
+        .map(|root| (Arc::clone(root), depths[len - 1]))
 }
 
 /// Decode a single Simplicity node from bits and
@@ -306,6 +521,75 @@ fn decode_node<I: Iterator<Item = u8>, J: Jet>(
     }
 }
 
+/// Decode a single Simplicity node from a line of an assembly listing,
+/// without its index.
+fn decode_asm_node<J: Jet>(
+    line: &str,
+    line_number: usize,
+    index: usize,
+) -> Result<DecodeNode<J>, Error> {
+    let syntax = || Error::AsmSyntax(line_number);
+    let child = |arg: &str| match arg.parse::<usize>() {
+        Ok(i) if i < index => Ok(i),
+        Ok(_) => Err(Error::BadIndex),
+        Err(_) => Err(syntax()),
+    };
+
+    let mut words = line.split_whitespace();
+    let name = words.next().ok_or_else(syntax)?;
+    let args: Vec<&str> = words.collect();
+    let node = match (name, &args[..]) {
+        ("iden", []) => DecodeNode::Iden,
+        ("unit", []) => DecodeNode::Unit,
+        ("injl", [i]) => DecodeNode::InjL(child(i)?),
+        ("injr", [i]) => DecodeNode::InjR(child(i)?),
+        ("take", [i]) => DecodeNode::Take(child(i)?),
+        ("drop", [i]) => DecodeNode::Drop(child(i)?),
+        ("comp", [i, j]) => DecodeNode::Comp(child(i)?, child(j)?),
+        ("case", [i, j]) => DecodeNode::Case(child(i)?, child(j)?),
+        ("pair", [i, j]) => DecodeNode::Pair(child(i)?, child(j)?),
+        ("disconnect", [i]) => DecodeNode::Disconnect1(child(i)?),
+        ("disconnect", [i, j]) => DecodeNode::Disconnect(child(i)?, child(j)?),
+        ("witness", []) => DecodeNode::Witness,
+        ("fail", [entropy]) => {
+            let entropy = <[u8; 64]>::from_hex(entropy).map_err(|_| syntax())?;
+            DecodeNode::Fail(FailEntropy::from_byte_array(entropy))
+        }
+        ("hidden", [cmr]) => DecodeNode::Hidden(Cmr::from_str(cmr).map_err(|_| syntax())?),
+        ("jet", [jet]) => DecodeNode::Jet(J::from_str(jet).map_err(|_| Error::InvalidJet)?),
+        ("word", [word]) => DecodeNode::Word(decode_asm_word(word).ok_or_else(syntax)?),
+        _ => return Err(syntax()),
+    };
+    Ok(node)
+}
+
+/// Decode a word that is written in hex with prefix `0x`, or in binary with prefix `0b`.
+///
+/// Returns `None` if the number of bits is not a power of two.
+fn decode_asm_word(s: &str) -> Option<Arc<Value>> {
+    let bits: Vec<bool> = if let Some(hex) = s.strip_prefix("0x") {
+        Vec::<u8>::from_hex(hex)
+            .ok()?
+            .into_iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| byte & (1 << i) != 0))
+            .collect()
+    } else {
+        s.strip_prefix("0b")?
+            .chars()
+            .map(|ch| match ch {
+                '0' => Some(false),
+                '1' => Some(true),
+                _ => None,
+            })
+            .collect::<Option<_>>()?
+    };
+    let len = bits.len();
+    if !len.is_power_of_two() {
+        return None;
+    }
+    decode_power_of_2(&mut bits.into_iter(), len).ok()
+}
+
 /// Decode a value from bits, of the form 2^exp
 ///
 /// # Panics
@@ -343,16 +627,115 @@ pub fn decode_power_of_2<I: Iterator<Item = bool>>(
     Ok(stack.pop().unwrap().value)
 }
 
+/// Iterator over a stream of length-prefixed programs, including witness data.
+///
+/// The stream is expected to consist of concatenated frames as written by
+/// [`crate::encode::encode_framed`]. Iteration ends when the stream is exhausted
+/// at a frame boundary. A decoding error is yielded as the final item.
+pub struct ProgramIter<I: Iterator<Item = u8>, J: Jet> {
+    bits: BitIter<I>,
+    done: bool,
+    phantom: PhantomData<J>,
+}
+
+impl<I: Iterator<Item = u8>, J: Jet> ProgramIter<I, J> {
+    /// Create an iterator over the framed programs of the given bit stream.
+    ///
+    /// The bit stream must be at a byte boundary.
+    pub fn new(bits: BitIter<I>) -> Self {
+        ProgramIter {
+            bits,
+            done: false,
+            phantom: PhantomData,
+        }
+    }
+
+    fn next_program(&mut self, first_bit: bool) -> Result<Arc<RedeemNode<J>>, crate::Error> {
+        let len = decode_natural(&mut iter::once(first_bit).chain(&mut self.bits), 32)?;
+        while self.bits.n_total_read() % 8 != 0 {
+            self.bits.read_bit().map_err(Error::from)?;
+        }
+        let bytes = (0..len)
+            .map(|_| self.bits.read_u8())
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(Error::from)?;
+        RedeemNode::decode(&mut BitIter::from(bytes))
+    }
+}
+
+impl<I: Iterator<Item = u8>, J: Jet> Iterator for ProgramIter<I, J> {
+    type Item = Result<Arc<RedeemNode<J>>, crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        // The stream may only end at a frame boundary
+        let first_bit = match self.bits.read_bit() {
+            Ok(bit) => bit,
+            Err(_) => {
+                self.done = true;
+                return None;
+            }
+        };
+        let result = self.next_program(first_bit);
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+/// Decode a Simplicity program, including its witness data, from a container
+/// as produced by [`crate::encode::encode_container`].
+///
+/// The bit stream must be at a byte boundary.
+pub fn decode_container<I: Iterator<Item = u8>, J: Jet>(
+    bits: &mut BitIter<I>,
+) -> Result<Arc<RedeemNode<J>>, crate::Error> {
+    let header: [u8; 5] = bits.read_array().map_err(Error::from)?;
+    if header[..4] != crate::encode::CONTAINER_MAGIC {
+        return Err(Error::BadContainerMagic.into());
+    }
+    if header[4] != crate::encode::CONTAINER_VERSION {
+        return Err(Error::UnknownContainerVersion(header[4]).into());
+    }
+    RedeemNode::decode(bits)
+}
+
 /// Decode a natural number from bits.
-/// If a bound is specified, then the decoding terminates before trying to decode a larger number.
+///
+/// The decoded number must fit into `max_bits` bits, i.e. be strictly less
+/// than `2^max_bits`. Otherwise, decoding terminates with
+/// [`Error::NaturalOverflow`] before reading the number itself.
+/// Every natural is positive, so `max_bits = 0` rejects all input.
+/// Decoded numbers must fit into a `usize`, so larger limits are clamped
+/// to the bit width of `usize`.
 pub fn decode_natural<I: Iterator<Item = bool>>(
     iter: &mut I,
-    bound: Option<usize>,
+    max_bits: usize,
 ) -> Result<usize, Error> {
+    let max_bits = cmp::min(max_bits, usize::BITS as usize);
+    if max_bits == 0 {
+        return Err(Error::NaturalOverflow);
+    }
+
+    // The encoding is recursive: the number of leading ones is the recursion depth,
+    // and every level encodes the bit length of the next level. We decode the levels
+    // iteratively, so adversarial inputs cannot exhaust the stack. We also stop reading
+    // leading ones once the depth alone implies an overflow, so adversarial inputs
+    // cannot make us read an unbounded number of bits.
     let mut recurse_depth = 0;
+    // Smallest number that the current depth can encode
+    let mut min_n = 1usize;
     loop {
         match iter.next() {
-            Some(true) => recurse_depth += 1,
+            Some(true) => {
+                if min_n >= max_bits {
+                    return Err(Error::NaturalOverflow);
+                }
+                recurse_depth += 1;
+                // Does not overflow because `min_n < max_bits <= usize::BITS`
+                min_n = 1 << min_n;
+            }
             Some(false) => break,
             None => return Err(Error::EndOfStream),
         }
@@ -371,15 +754,12 @@ pub fn decode_natural<I: Iterator<Item = bool>>(
         }
 
         if recurse_depth == 0 {
-            if let Some(bound) = bound {
-                if n > bound {
-                    return Err(Error::BadIndex);
-                }
-            }
             return Ok(n);
         } else {
+            // The final number has `n + 1` bits, and it is only going
+            // to get bigger as we recurse.
             len = n;
-            if len > 31 {
+            if len >= max_bits {
                 return Err(Error::NaturalOverflow);
             }
             recurse_depth -= 1;
@@ -392,7 +772,7 @@ mod tests {
     use super::*;
     use crate::encode;
     use crate::jet::Core;
-    use crate::node::{CommitNode, RedeemNode};
+    use crate::node::SimpleFinalizer;
     use crate::BitWriter;
 
     #[test]
@@ -488,7 +868,7 @@ mod tests {
         for (natural, bitvec) in tries {
             let truncated = bitvec[0..bitvec.len() - 1].to_vec();
             assert!(matches!(
-                decode_natural(&mut truncated.into_iter(), None),
+                decode_natural(&mut truncated.into_iter(), 32),
                 Err(Error::EndOfStream)
             ));
 
@@ -499,9 +879,370 @@ mod tests {
             w.flush_all().expect("flushing");
             assert_eq!(w.n_total_written(), bitvec.len());
 
-            let decoded_natural = decode_natural(&mut BitIter::from(sink.into_iter()), None)
+            let decoded_natural = decode_natural(&mut BitIter::from(sink.into_iter()), 32)
                 .expect("decoding from vector");
             assert_eq!(natural, decoded_natural);
         }
     }
+
+    #[test]
+    fn decode_natural_pathological() {
+        // An endless stream of ones encodes an ever deeper recursion
+        let mut ones = iter::repeat(true);
+        assert!(matches!(
+            decode_natural(&mut ones, 32),
+            Err(Error::NaturalOverflow)
+        ));
+        // The depth is bounded before reading the number itself
+        let bits: Vec<bool> = iter::repeat(true).take(5).chain([false; 100]).collect();
+        let mut iter = bits.into_iter();
+        assert!(matches!(
+            decode_natural(&mut iter, 64),
+            Err(Error::NaturalOverflow)
+        ));
+        assert_eq!(iter.count(), 100);
+
+        // All valid naturals up to 32 bits still decode
+        for n in (1..=u32::MAX as usize)
+            .step_by(65_521)
+            .chain([u32::MAX as usize])
+        {
+            let bytes = crate::write_to_vec(|w| encode::encode_natural(n, w));
+            let mut bits = BitIter::from(bytes.into_iter());
+            assert_eq!(decode_natural(&mut bits, 32).unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn decode_natural_max_bits() {
+        fn encode(n: usize) -> Vec<u8> {
+            let mut sink = Vec::<u8>::new();
+            let mut w = BitWriter::from(&mut sink);
+            encode::encode_natural(n, &mut w).expect("encoding to vector");
+            w.flush_all().expect("flushing");
+            sink
+        }
+
+        // The limit of 32 bits does not leave room above it on 32-bit targets
+        let widths: &[usize] = if cfg!(target_pointer_width = "64") {
+            &[1, 2, 8, 16, 31, 32]
+        } else {
+            &[1, 2, 8, 16, 31]
+        };
+        for &max_bits in widths {
+            let limit = (1usize << max_bits) - 1;
+            // Just below the limit and at the limit
+            for n in [limit - 1, limit] {
+                if n == 0 {
+                    continue;
+                }
+                let decoded = decode_natural(&mut BitIter::from(encode(n).into_iter()), max_bits)
+                    .expect("decoding from vector");
+                assert_eq!(n, decoded);
+            }
+            // Just above the limit
+            assert!(matches!(
+                decode_natural(&mut BitIter::from(encode(limit + 1).into_iter()), max_bits),
+                Err(Error::NaturalOverflow)
+            ));
+        }
+
+        // Not even the smallest natural fits into zero bits
+        assert!(matches!(
+            decode_natural(&mut BitIter::from(encode(1).into_iter()), 0),
+            Err(Error::NaturalOverflow)
+        ));
+
+        // The default limit used while decoding programs is 32 bits
+        #[cfg(target_pointer_width = "64")]
+        {
+            let mut iter = BitIter::from(encode(1 << 32).into_iter());
+            assert!(matches!(
+                iter.read_natural(None),
+                Err(Error::NaturalOverflow)
+            ));
+        }
+        // ...but callers can ask for more
+        let mut iter = BitIter::from(encode(usize::MAX).into_iter());
+        assert_eq!(
+            decode_natural(&mut iter, usize::BITS as usize).unwrap(),
+            usize::MAX
+        );
+        // ...and limits beyond the width of usize are clamped to it
+        let mut iter = BitIter::from(encode(usize::MAX).into_iter());
+        assert_eq!(decode_natural(&mut iter, usize::MAX).unwrap(), usize::MAX);
+        assert!(matches!(
+            decode_natural(&mut iter::repeat(true), usize::MAX),
+            Err(Error::NaturalOverflow)
+        ));
+    }
+
+    #[test]
+    fn decode_framed_programs() {
+        // main = unit
+        let unit = Arc::<ConstructNode<Core>>::unit()
+            .finalize_types()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+            .unwrap();
+        // main = comp (pair wit1 wit2) jet_eq_32 followed by jet_verify,
+        // with different witnesses each time
+        let eqwits = [0xcd, 0xdc, 0x51, 0xb6, 0xe2, 0x08, 0xc0, 0x40];
+        let eqwits_prog = CommitNode::<Core>::decode(&mut BitIter::from(&eqwits[..])).unwrap();
+        let eqwits_same = eqwits_prog
+            .finalize(&mut SimpleFinalizer::new(std::iter::repeat(Value::u32(7))))
+            .unwrap();
+        let eqwits_diff = eqwits_prog
+            .finalize(&mut SimpleFinalizer::new(
+                vec![Value::u32(7), Value::u32(8)].into_iter(),
+            ))
+            .unwrap();
+        let programs = vec![unit, eqwits_same, eqwits_diff];
+
+        let mut sink = Vec::<u8>::new();
+        let mut w = BitWriter::from(&mut sink);
+        for program in &programs {
+            encode::encode_framed(program, &mut w).expect("encoding to vector");
+        }
+        w.flush_all().expect("flushing");
+
+        let decoded = ProgramIter::<_, Core>::new(BitIter::from(&sink[..]))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("decoding from vector");
+        assert_eq!(decoded, programs);
+
+        // Truncating the stream yields an error, which ends iteration
+        let mut iter = ProgramIter::<_, Core>::new(BitIter::from(&sink[..sink.len() - 1]));
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn decode_depth() {
+        let unit = Arc::<ConstructNode<Core>>::unit();
+        let flat = unit.finalize_types().unwrap().encode_to_vec();
+
+        let mut nested = Arc::clone(&unit);
+        for _ in 0..10 {
+            nested = Arc::<ConstructNode<Core>>::comp(&unit, &nested).unwrap();
+        }
+        let nested = nested.finalize_types().unwrap().encode_to_vec();
+
+        let mut iter = BitIter::from(&flat[..]);
+        let (_, flat_depth) = decode_expression_with_depth::<_, Core>(&mut iter).unwrap();
+        let mut iter = BitIter::from(&nested[..]);
+        let (_, nested_depth) = decode_expression_with_depth::<_, Core>(&mut iter).unwrap();
+        assert_eq!(flat_depth, 1);
+        assert_eq!(nested_depth, 11);
+    }
+
+    #[test]
+    fn decode_expecting_cmr() {
+        let unit = Arc::<ConstructNode<Core>>::unit();
+        let iden = Arc::<ConstructNode<Core>>::iden();
+        let bytes = unit.finalize_types().unwrap().encode_to_vec();
+
+        let mut iter = BitIter::from(&bytes[..]);
+        let decoded = decode_expecting::<_, Core>(&mut iter, unit.cmr()).unwrap();
+        assert_eq!(decoded.cmr(), unit.cmr());
+
+        let mut iter = BitIter::from(&bytes[..]);
+        match decode_expecting::<_, Core>(&mut iter, iden.cmr()) {
+            Err(Error::CmrMismatch { expected, found }) => {
+                assert_eq!(expected, iden.cmr());
+                assert_eq!(found, unit.cmr());
+            }
+            _ => panic!("expected CMR mismatch"),
+        }
+    }
+
+    #[test]
+    fn decode_program_with_and_without_witness() {
+        // comp witness jet_verify :: 1 → 1
+        let witness = Arc::<ConstructNode<Core>>::witness(NoWitness);
+        let verify = Arc::<ConstructNode<Core>>::jet(Core::Verify);
+        let commit = Arc::<ConstructNode<Core>>::comp(&witness, &verify)
+            .unwrap()
+            .finalize_types()
+            .unwrap();
+        let redeem = commit
+            .finalize(&mut SimpleFinalizer::new(iter::once(Value::u1(1))))
+            .unwrap();
+        let commit_bytes = commit.encode_to_vec();
+        let redeem_bytes = redeem.encode_to_vec();
+
+        let mut iter = BitIter::from(&commit_bytes[..]);
+        match decode_program::<_, Core>(&mut iter, false) {
+            Ok(DecodedProgram::Commit(decoded)) => assert_eq!(decoded, commit),
+            x => panic!("expected commitment program, got {:?}", x),
+        }
+        let mut iter = BitIter::from(&redeem_bytes[..]);
+        match decode_program::<_, Core>(&mut iter, true) {
+            Ok(DecodedProgram::Redeem(decoded)) => assert_eq!(decoded, redeem),
+            x => panic!("expected redeem program, got {:?}", x),
+        }
+
+        let mut iter = BitIter::from(&commit_bytes[..]);
+        assert!(matches!(
+            decode_program::<_, Core>(&mut iter, true),
+            Err(crate::Error::Decode(Error::MissingWitnessBlock))
+        ));
+        let mut iter = BitIter::from(&redeem_bytes[..]);
+        assert!(matches!(
+            decode_program::<_, Core>(&mut iter, false),
+            Err(crate::Error::Decode(Error::UnexpectedWitnessBlock))
+        ));
+    }
+
+    #[test]
+    fn validate_program_invariants() {
+        type Node = Arc<ConstructNode<Core>>;
+        let unit = Node::unit();
+        let shared = Node::comp(&Node::pair(&unit, &unit).unwrap(), &Node::drop_(&unit))
+            .unwrap()
+            .finalize_types()
+            .unwrap();
+        validate_program(shared.as_ref()).unwrap();
+        // The same program, decoded
+        let bytes = shared.encode_to_vec();
+        let decoded = CommitNode::<Core>::decode(&mut BitIter::from(&bytes[..])).unwrap();
+        validate_program(decoded.as_ref()).unwrap();
+
+        // Too many nodes
+        assert!(matches!(
+            validate_program_with_limit(shared.as_ref(), 2),
+            Err(crate::Error::Decode(Error::TooManyNodes(4)))
+        ));
+
+        // The `unit` nodes are not shared
+        let unshared = Node::comp(
+            &Node::pair(&Node::unit(), &Node::unit()).unwrap(),
+            &Node::drop_(&Node::unit()),
+        )
+        .unwrap()
+        .finalize_types()
+        .unwrap();
+        assert!(matches!(
+            validate_program(unshared.as_ref()),
+            Err(crate::Error::Decode(Error::SharingNotMaximal))
+        ));
+
+        // An expression of type 2^64 → 2 is not a program
+        let eq = Node::jet(Core::Eq32).finalize_types_non_program().unwrap();
+        assert!(matches!(
+            validate_program(eq.as_ref()),
+            Err(crate::Error::Type(..))
+        ));
+    }
+
+    #[test]
+    fn validate_program_encoding_invariants() {
+        type Node = Arc<ConstructNode<Core>>;
+        // The right child is constructed before the left one, which the encoding
+        // lists first, and the assertion has a hidden child
+        let unit = Node::unit();
+        let assertion = Node::assertl(&Node::drop_(&unit), Cmr::iden()).unwrap();
+        let tag = Node::pair(&Node::injl(&unit), &unit).unwrap();
+        let program = Node::comp(&tag, &assertion)
+            .unwrap()
+            .finalize_types()
+            .unwrap();
+        validate_program(program.as_ref()).unwrap();
+
+        // The encoder puts the nodes in canonical order and the hidden node below `case`,
+        // so the decoder accepts the encoding
+        let bytes = program.encode_to_vec();
+        let decoded = CommitNode::<Core>::decode(&mut BitIter::from(&bytes[..])).unwrap();
+        assert_eq!(decoded.cmr(), program.cmr());
+        validate_program(decoded.as_ref()).unwrap();
+    }
+
+    #[test]
+    fn decode_invalid_hidden_nodes() {
+        fn hidden<W: std::io::Write>(cmr: Cmr, w: &mut BitWriter<W>) -> std::io::Result<usize> {
+            Ok(w.write_bits_be(0b0110, 4)? + encode::encode_hash(cmr.as_ref(), w)?)
+        }
+
+        // case hidden(unit) hidden(iden)
+        let bytes = crate::write_to_vec(|w| {
+            let mut len = encode::encode_natural(3, w)?;
+            len += hidden(Cmr::unit(), w)?;
+            len += hidden(Cmr::iden(), w)?;
+            len += w.write_bits_be(0b00001, 5)?;
+            len += encode::encode_natural(2, w)?;
+            len += encode::encode_natural(1, w)?;
+            Ok(len)
+        });
+        assert!(matches!(
+            decode_expression::<_, Core>(&mut BitIter::from(&bytes[..])),
+            Err(Error::BothChildrenHidden)
+        ));
+
+        // injl hidden(unit)
+        let bytes = crate::write_to_vec(|w| {
+            let mut len = encode::encode_natural(2, w)?;
+            len += hidden(Cmr::unit(), w)?;
+            len += w.write_bits_be(0b00100, 5)?;
+            len += encode::encode_natural(1, w)?;
+            Ok(len)
+        });
+        assert!(matches!(
+            decode_expression::<_, Core>(&mut BitIter::from(&bytes[..])),
+            Err(Error::HiddenNode)
+        ));
+
+        // The constructors take hidden children only as the CMRs of assertions,
+        // which are encoded as `case` nodes with one hidden child
+        type Node = Arc<ConstructNode<Core>>;
+        let unit = Node::unit();
+        let left = Node::assertl(&Node::drop_(&unit), Cmr::iden()).unwrap();
+        let right = Node::assertr(Cmr::iden(), &Node::drop_(&unit)).unwrap();
+        for (tag, assertion) in [(Node::injl(&unit), left), (Node::injr(&unit), right)] {
+            let program = Node::comp(&Node::pair(&tag, &unit).unwrap(), &assertion)
+                .unwrap()
+                .finalize_types()
+                .unwrap();
+            let bytes = program.encode_to_vec();
+            let decoded = CommitNode::<Core>::decode(&mut BitIter::from(&bytes[..])).unwrap();
+            assert_eq!(decoded.cmr(), program.cmr());
+        }
+    }
+
+    #[test]
+    fn container_roundtrip() {
+        let unit = Arc::<ConstructNode<Core>>::unit();
+        let program = unit
+            .finalize_types()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(iter::empty()))
+            .unwrap();
+
+        let bytes = crate::write_to_vec(|w| encode::encode_container(&program, w));
+        assert_eq!(&bytes[..4], b"SMPL");
+        let decoded = decode_container::<_, Core>(&mut BitIter::from(&bytes[..])).unwrap();
+        assert_eq!(decoded, program);
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 0xff;
+        assert!(matches!(
+            decode_container::<_, Core>(&mut BitIter::from(&bad_version[..])),
+            Err(crate::Error::Decode(Error::UnknownContainerVersion(0xff)))
+        ));
+    }
+
+    #[test]
+    fn container_bad_magic() {
+        // A raw program encoding is not a container
+        let unit = Arc::<ConstructNode<Core>>::unit();
+        let bytes = unit.finalize_types().unwrap().encode_to_vec();
+        let mut padded = bytes.clone();
+        padded.resize(8, 0);
+
+        assert!(matches!(
+            decode_container::<_, Core>(&mut BitIter::from(&padded[..])),
+            Err(crate::Error::Decode(Error::BadContainerMagic))
+        ));
+    }
 }
@@ -7,14 +7,15 @@
 //! so given a hex dump of a program it is not generally possible
 //! to read it visually the way you can with Bitcoin Script.
 
-use crate::dag::{Dag, DagLike, PostOrderIterItem, SharingTracker};
+use crate::dag::{Dag, DagLike, MaxSharing, PostOrderIterItem, SharingTracker};
+use crate::human_encoding::DisplayWord;
 use crate::jet::Jet;
-use crate::node::{self, Disconnectable};
-use crate::{BitWriter, Cmr, Value};
+use crate::node::{self, Disconnectable, Hide, RedeemNode};
+use crate::{BitWriter, Cmr, Imr, Value};
 
 use std::collections::{hash_map::Entry, HashMap};
 use std::sync::Arc;
-use std::{hash, io, mem};
+use std::{fmt, hash, io, mem};
 
 #[derive(Copy, Clone)]
 enum EncodeNode<'n, N: node::Marker> {
@@ -151,6 +152,94 @@ impl<'n, N: node::Marker> SharingTracker<EncodeNode<'n, N>> for EncodeSharing<N>
     }
 }
 
+/// View of a program in which some branches of its `case` nodes are hidden.
+#[derive(Copy, Clone)]
+struct PrunedNode<'n, N: node::Marker> {
+    node: EncodeNode<'n, N>,
+    hide: &'n HashMap<N::SharingId, Hide>,
+}
+
+impl<'n, N: node::Marker> DagLike for PrunedNode<'n, N> {
+    type Node = Self;
+    fn data(&self) -> &Self {
+        self
+    }
+
+    fn as_dag_node(&self) -> Dag<Self> {
+        let hide = self.hide;
+        let wrap = |node| PrunedNode { node, hide };
+        if let EncodeNode::Node(node) = self.node {
+            if let node::Inner::Case(left, right) = node.inner() {
+                match node.sharing_id().and_then(|id| hide.get(&id)) {
+                    Some(Hide::Left) => {
+                        let left = EncodeNode::Hidden(left.cmr());
+                        return Dag::Binary(wrap(left), wrap(EncodeNode::Node(right)));
+                    }
+                    Some(Hide::Right) => {
+                        let right = EncodeNode::Hidden(right.cmr());
+                        return Dag::Binary(wrap(EncodeNode::Node(left)), wrap(right));
+                    }
+                    Some(Hide::Neither) | None => {}
+                }
+            }
+        }
+        self.node.as_dag_node().map(wrap)
+    }
+}
+
+/// Shares the nodes of a [`PrunedNode`] view like [`EncodeSharing`]
+#[derive(Clone)]
+struct PrunedSharing<N: node::Marker>(EncodeSharing<N>);
+
+impl<N: node::Marker> Default for PrunedSharing<N> {
+    fn default() -> Self {
+        PrunedSharing(EncodeSharing::default())
+    }
+}
+
+impl<'n, N: node::Marker> SharingTracker<PrunedNode<'n, N>> for PrunedSharing<N> {
+    fn record(&mut self, d: &PrunedNode<'n, N>, index: usize) -> Option<usize> {
+        self.0.record(&d.node, index)
+    }
+
+    fn seen_before(&self, d: &PrunedNode<'n, N>) -> Option<usize> {
+        self.0.seen_before(&d.node)
+    }
+}
+
+/// Return the number of bits of the encoding of a program, including witness data,
+/// after hiding the given branches of its `case` nodes.
+///
+/// This is the length of the pruned program that hides these branches,
+/// computed without constructing it.
+pub(crate) fn pruned_bit_len<J: Jet>(program: &RedeemNode<J>, hide: &HashMap<Imr, Hide>) -> usize {
+    let mut w = BitWriter::new(io::sink());
+    let iter = PrunedNode {
+        node: EncodeNode::Node(program),
+        hide,
+    }
+    .post_order_iter::<PrunedSharing<_>>();
+
+    let mut witness = vec![];
+    encode_natural(iter.clone().count(), &mut w).expect("writing to a sink never fails");
+    for data in iter {
+        if let EncodeNode::Node(node) = data.node.node {
+            if let node::Inner::Witness(value) = node.inner() {
+                witness.push(value.as_ref());
+            }
+        }
+        let data = PostOrderIterItem {
+            node: data.node.node,
+            index: data.index,
+            left_index: data.left_index,
+            right_index: data.right_index,
+        };
+        encode_node(data, &mut w).expect("writing to a sink never fails");
+    }
+    encode_witness(witness.into_iter(), &mut w).expect("writing to a sink never fails");
+    w.n_total_written()
+}
+
 /// Encode a Simplicity program to bits, without witness data.
 ///
 /// Returns the number of written bits.
@@ -171,6 +260,152 @@ pub fn encode_program<W: io::Write, N: node::Marker>(
     Ok(w.n_total_written() - n_start)
 }
 
+/// Write the assembly listing of a Simplicity program, without witness data.
+///
+/// The listing has one line per node of the encoding, in the same order. Each
+/// line starts with the index of the node, followed by its combinator, the indices
+/// of its children and its payload, if any:
+///
+/// ```text
+/// 0: unit
+/// 1: witness
+/// 2: hidden 0000000000000000000000000000000000000000000000000000000000000000
+/// 3: case 0 2
+/// 4: jet add_32
+/// 5: word 0xdeadbeef
+/// ```
+///
+/// Words are written in hex, or in binary if they are shorter than a byte.
+/// The listing can be parsed back with [`crate::decode::decode_asm`].
+pub fn encode_asm<W: fmt::Write, N: node::Marker>(
+    program: &node::Node<N>,
+    w: &mut W,
+) -> fmt::Result {
+    for data in EncodeNode::Node(program).post_order_iter::<EncodeSharing<N>>() {
+        write!(w, "{}: ", data.index)?;
+        let node = match data.node {
+            EncodeNode::Node(node) => node,
+            EncodeNode::Hidden(cmr) => {
+                writeln!(w, "hidden {}", cmr)?;
+                continue;
+            }
+        };
+        let name = match node.inner() {
+            node::Inner::Iden => "iden",
+            node::Inner::Unit => "unit",
+            node::Inner::InjL(..) => "injl",
+            node::Inner::InjR(..) => "injr",
+            node::Inner::Take(..) => "take",
+            node::Inner::Drop(..) => "drop",
+            node::Inner::Comp(..) => "comp",
+            node::Inner::Case(..) | node::Inner::AssertL(..) | node::Inner::AssertR(..) => "case",
+            node::Inner::Pair(..) => "pair",
+            node::Inner::Disconnect(..) => "disconnect",
+            node::Inner::Witness(..) => "witness",
+            node::Inner::Fail(..) => "fail",
+            node::Inner::Jet(..) => "jet",
+            node::Inner::Word(..) => "word",
+        };
+        w.write_str(name)?;
+        for child in data.left_index.iter().chain(data.right_index.iter()) {
+            write!(w, " {}", child)?;
+        }
+        match node.inner() {
+            node::Inner::Fail(entropy) => write!(w, " {}", entropy)?,
+            node::Inner::Jet(jet) => write!(w, " {}", jet)?,
+            node::Inner::Word(value) => write!(w, " {}", DisplayWord(value))?,
+            _ => {}
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Return the positions of the nodes of a Simplicity program in its encoding,
+/// grouped by CMR.
+///
+/// Nodes are numbered in the order in which [`encode_program`] writes them,
+/// which is also the order in which they are decoded. Hidden children of
+/// `assertl` and `assertr` nodes take up a position of their own.
+///
+/// Distinct nodes may have the same CMR, e.g. witness nodes with different
+/// values, so each CMR may be assigned several positions.
+pub fn node_indices<N: node::Marker>(program: &node::Node<N>) -> HashMap<Cmr, Vec<usize>> {
+    let mut indices = HashMap::<Cmr, Vec<usize>>::new();
+    for data in EncodeNode::Node(program).post_order_iter::<EncodeSharing<N>>() {
+        let cmr = match data.node {
+            EncodeNode::Node(node) => node.cmr(),
+            EncodeNode::Hidden(cmr) => cmr,
+        };
+        indices.entry(cmr).or_default().push(data.index);
+    }
+    indices
+}
+
+/// Return the positions of the `assertl`, `assertr` and `fail` nodes of a Simplicity
+/// program in its encoding, in ascending order.
+///
+/// These are the nodes where execution can abort, apart from jets. Nodes are numbered
+/// like in [`node_indices`], so the hidden child of an assertion takes up a position
+/// of its own, but it is not reported.
+pub fn assertion_indices<N: node::Marker>(program: &node::Node<N>) -> Vec<usize> {
+    EncodeNode::Node(program)
+        .post_order_iter::<EncodeSharing<N>>()
+        .filter(|data| match data.node {
+            EncodeNode::Node(node) => matches!(
+                node.inner(),
+                node::Inner::AssertL(..) | node::Inner::AssertR(..) | node::Inner::Fail(..)
+            ),
+            EncodeNode::Hidden(..) => false,
+        })
+        .map(|data| data.index)
+        .collect()
+}
+
+/// Return the positions of the `comp` nodes of a Simplicity program in its encoding
+/// that have `iden` as a child, in ascending order.
+///
+/// Such compositions are no-ops and can be replaced by their other child,
+/// so programs that contain them are not minimal. Nodes are numbered like
+/// in [`node_indices`].
+pub fn redundant_comp_indices<N: node::Marker>(program: &node::Node<N>) -> Vec<usize> {
+    EncodeNode::Node(program)
+        .post_order_iter::<EncodeSharing<N>>()
+        .filter(|data| match data.node {
+            EncodeNode::Node(node) => match node.inner() {
+                node::Inner::Comp(left, right) => {
+                    matches!(left.inner(), node::Inner::Iden)
+                        || matches!(right.inner(), node::Inner::Iden)
+                }
+                _ => false,
+            },
+            EncodeNode::Hidden(..) => false,
+        })
+        .map(|data| data.index)
+        .collect()
+}
+
+/// Return the position of the given node of a Simplicity program in its encoding.
+///
+/// Nodes are numbered like in [`node_indices`] and shared nodes
+/// are identified by their sharing ID.
+pub(crate) fn node_index<N: node::Marker>(
+    program: &node::Node<N>,
+    target: &node::Node<N>,
+) -> Option<usize> {
+    let target_id = target.sharing_id();
+    EncodeNode::Node(program)
+        .post_order_iter::<EncodeSharing<N>>()
+        .find(|data| match data.node {
+            EncodeNode::Node(node) => match target_id {
+                Some(ref id) => node.sharing_id().as_ref() == Some(id),
+                None => std::ptr::eq(node, target),
+            },
+            EncodeNode::Hidden(..) => false,
+        })
+        .map(|data| data.index)
+}
+
 /// Encode a node to bits.
 fn encode_node<W: io::Write, N: node::Marker>(
     data: PostOrderIterItem<EncodeNode<N>>,
@@ -322,6 +557,68 @@ pub fn encode_value<W: io::Write>(value: &Value, w: &mut BitWriter<W>) -> io::Re
     Ok(w.n_total_written() - n_start)
 }
 
+/// Encode a Simplicity program, including witness data, as a length-prefixed frame.
+///
+/// The frame consists of the byte length of the encoded program as a natural
+/// number, zero padding up to the next byte boundary, and then the encoded
+/// program itself. Frames can be concatenated and read back using
+/// [`crate::decode::ProgramIter`].
+///
+/// The program is streamed to the writer without being buffered in memory;
+/// its length is obtained from a first encoding pass that discards its output.
+///
+/// The writer must be at a byte boundary. Returns the number of written bits.
+pub fn encode_framed<W: io::Write, J: Jet>(
+    program: &node::RedeemNode<J>,
+    w: &mut BitWriter<W>,
+) -> io::Result<usize> {
+    let n_start = w.n_total_written();
+    let program_bits = program.encode(&mut BitWriter::new(io::sink()))?;
+    encode_natural((program_bits + 7) / 8, w)?;
+    while w.n_total_written() % 8 != 0 {
+        w.write_bit(false)?;
+    }
+    encode_program(program, w)?;
+    encode_witness(
+        program
+            .post_order_iter::<MaxSharing<node::Redeem<J>>>()
+            .into_witnesses()
+            .map(Arc::as_ref),
+        w,
+    )?;
+    while w.n_total_written() % 8 != 0 {
+        w.write_bit(false)?;
+    }
+
+    Ok(w.n_total_written() - n_start)
+}
+
+/// Magic bytes at the start of a program container.
+pub const CONTAINER_MAGIC: [u8; 4] = *b"SMPL";
+
+/// Version of the encoding of programs inside containers.
+pub const CONTAINER_VERSION: u8 = 1;
+
+/// Encode a Simplicity program, including its witness data, into a container.
+///
+/// The container consists of [`CONTAINER_MAGIC`] and the byte [`CONTAINER_VERSION`],
+/// followed by the encoding of the program as produced by [`node::RedeemNode::encode`].
+/// Containers can be decoded using [`crate::decode::decode_container`].
+///
+/// The writer must be at a byte boundary. Returns the number of written bits.
+pub fn encode_container<W: io::Write, J: Jet>(
+    program: &node::RedeemNode<J>,
+    w: &mut BitWriter<W>,
+) -> io::Result<usize> {
+    let n_start = w.n_total_written();
+    for byte in CONTAINER_MAGIC.iter().chain(&[CONTAINER_VERSION]) {
+        w.write_bits_be(u64::from(*byte), 8)?;
+    }
+    program.encode(w)?;
+
+    Ok(w.n_total_written() - n_start)
+}
+
 /// Encode a hash to bits.
 pub fn encode_hash<W: io::Write>(h: &[u8], w: &mut BitWriter<W>) -> io::Result<usize> {
     for byte in h {
@@ -368,9 +665,100 @@ pub fn encode_natural<W: io::Write>(mut n: usize, w: &mut BitWriter<W>) -> io::R
 mod test {
     use super::*;
 
+    use crate::dag::InternalSharing;
     use crate::decode;
+    use crate::jet::Core;
+    use crate::node::{CoreConstructible, RedeemNode};
     use crate::BitIter;
 
+    #[test]
+    fn node_indices_decode_order() {
+        // asst = assertl unit deadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeef
+        // input0 = pair (injl unit) unit
+        // main = comp input0 asst
+        let assertl = [
+            0xcd, 0x24, 0x08, 0x4b, 0x6f, 0x56, 0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77, 0xef, 0x56,
+            0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77,
+            0xef, 0x56, 0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77, 0x86, 0x01, 0x80,
+        ];
+        // wit1 = witness :: 1 -> 2^32
+        // wit2 = witness :: 1 -> 2^32
+        // wits_are_equal = comp (pair wit1 wit2) jet_eq_32 :: 1 -> 2
+        // main = comp wits_are_equal jet_verify            :: 1 -> 1
+        let eqwits = [
+            0xc9, 0xc4, 0x6d, 0xb8, 0x82, 0x30, 0x11, 0xe2, 0x0d, 0xea, 0xdb, 0xee, 0xf0,
+        ];
+
+        for bytes in [&assertl[..], &eqwits[..]] {
+            let n_nodes = BitIter::from(bytes).read_natural(None).unwrap();
+            let program = RedeemNode::<Core>::decode(&mut BitIter::from(bytes)).unwrap();
+            let indices = program.node_indices();
+
+            // Indices are contiguous
+            let mut all_indices: Vec<usize> = indices.values().flatten().copied().collect();
+            all_indices.sort_unstable();
+            assert_eq!(all_indices, (0..n_nodes).collect::<Vec<_>>());
+
+            // Indices match decode order
+            let mut expected_index = 0;
+            for data in program.post_order_iter::<InternalSharing>() {
+                if let node::Inner::AssertL(_, cmr) = data.node.inner() {
+                    // The hidden right child comes after the left child
+                    assert!(indices[cmr].contains(&expected_index));
+                    expected_index += 1;
+                }
+                assert!(indices[&data.node.cmr()].contains(&expected_index));
+                expected_index += 1;
+            }
+            assert_eq!(expected_index, n_nodes);
+        }
+    }
+
+    #[test]
+    fn assertion_indices_assertl() {
+        // asst = assertl unit deadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeef
+        // input0 = pair (injl unit) unit
+        // main = comp input0 asst
+        let assertl = [
+            0xcd, 0x24, 0x08, 0x4b, 0x6f, 0x56, 0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77, 0xef, 0x56,
+            0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77,
+            0xef, 0x56, 0xdf, 0x77, 0xef, 0x56, 0xdf, 0x77, 0x86, 0x01, 0x80,
+        ];
+        let program = RedeemNode::<Core>::decode(&mut BitIter::from(&assertl[..])).unwrap();
+        let asst = match program.inner() {
+            node::Inner::Comp(_, right) => right,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            program.assertion_indices(),
+            program.node_indices()[&asst.cmr()],
+        );
+    }
+
+    #[test]
+    fn redundant_comp_indices_iden() {
+        let iden = Arc::<node::ConstructNode<Core>>::iden();
+        let unit = Arc::<node::ConstructNode<Core>>::unit();
+        let comp_iden_unit = Arc::<node::ConstructNode<Core>>::comp(&iden, &unit).unwrap();
+        // Use a fresh unit node, so its type is not tied to the first one
+        let unit = Arc::<node::ConstructNode<Core>>::unit();
+        let program = Arc::<node::ConstructNode<Core>>::comp(&comp_iden_unit, &unit)
+            .unwrap()
+            .finalize_types()
+            .unwrap();
+
+        // Only `comp iden unit` is flagged, not the root `comp`
+        let indices = program.node_indices();
+        assert_eq!(
+            program.redundant_comp_indices(),
+            indices[&comp_iden_unit.cmr()],
+        );
+
+        let minimal = unit.finalize_types().unwrap();
+        assert!(minimal.redundant_comp_indices().is_empty());
+    }
+
     #[test]
     fn encode_decode_natural() {
         for n in 1..1000 {
@@ -378,7 +766,7 @@ mod test {
             let mut w = BitWriter::from(&mut sink);
             encode_natural(n, &mut w).expect("encoding to vector");
             w.flush_all().expect("flushing");
-            let m = decode::decode_natural(&mut BitIter::from(sink.into_iter()), None)
+            let m = decode::decode_natural(&mut BitIter::from(sink.into_iter()), 32)
                 .expect("decoding from vector");
             assert_eq!(n, m);
         }
@@ -15,3 +15,55 @@ pub mod encode;
 
 pub use bititer::{u2, BitIter, EarlyEndOfStreamError};
 pub use bitwriter::{write_to_vec, BitWriter};
+
+/// Return the index of the first bit where the two byte strings differ.
+///
+/// Bits are indexed from the most significant bit of the first byte, in the order
+/// in which [`BitIter`] reads them. If one string is a prefix of the other, then
+/// the first bit after the end of the shorter string differs.
+///
+/// Returns `None` if the strings are equal.
+pub fn first_diff_bit(a: &[u8], b: &[u8]) -> Option<usize> {
+    if let Some((i, (x, y))) = a.iter().zip(b).enumerate().find(|(_, (x, y))| x != y) {
+        return Some(8 * i + (x ^ y).leading_zeros() as usize);
+    }
+    if a.len() == b.len() {
+        None
+    } else {
+        Some(8 * a.len().min(b.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jet::Core;
+    use crate::node::CoreConstructible;
+    use crate::ConstructNode;
+    use std::sync::Arc;
+
+    #[test]
+    fn first_diff_bit_of_encodings() {
+        let unit = Arc::<ConstructNode<Core>>::unit()
+            .finalize_types_non_program()
+            .unwrap()
+            .encode_to_vec();
+        let iden = Arc::<ConstructNode<Core>>::iden()
+            .finalize_types_non_program()
+            .unwrap()
+            .encode_to_vec();
+        let diff = first_diff_bit(&unit, &iden).unwrap();
+        // Both programs consist of one node, so the first difference is in its code
+        let mut unit_bits = BitIter::from(&unit[..]);
+        let mut iden_bits = BitIter::from(&iden[..]);
+        for _ in 0..diff {
+            assert_eq!(unit_bits.read_bit(), iden_bits.read_bit());
+        }
+        assert_ne!(unit_bits.read_bit(), iden_bits.read_bit());
+
+        assert_eq!(first_diff_bit(&unit, &unit), None);
+        assert_eq!(first_diff_bit(&[0xff, 0x00], &[0xff, 0x10]), Some(11));
+        assert_eq!(first_diff_bit(&[0xff], &[0xff, 0x00]), Some(8));
+        assert_eq!(first_diff_bit(&[], &[0x00]), Some(0));
+    }
+}
@@ -38,6 +38,11 @@ impl Frame {
         self.start
     }
 
+    /// Return the current position of the cursor inside the referenced data.
+    pub(super) fn cursor(&self) -> usize {
+        self.cursor
+    }
+
     /// Return the bit width of the frame.
     pub fn bit_width(&self) -> usize {
         self.len
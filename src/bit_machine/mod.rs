@@ -7,24 +7,31 @@
 //!
 
 mod frame;
+mod transcript;
 
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 use std::{cmp, error};
 
 use crate::analysis;
-use crate::dag::{DagLike, NoSharing};
+use crate::dag::{DagLike, InternalSharing, NoSharing};
+use crate::ffi::ffi::UWORD;
 use crate::jet::{Jet, JetFailed};
 use crate::node::{self, RedeemNode};
-use crate::types::Final;
+use crate::types::{CompleteBound, Final};
 use crate::{Cmr, FailEntropy, Value};
 use frame::Frame;
+pub use transcript::Transcript;
 
 /// An execution context for a Simplicity program
 pub struct BitMachine {
     /// Space for bytes that read and write frames point to.
     /// (De)allocation happens LIFO from left to right
     data: Vec<u8>,
+    /// Bitmap of the cells in `data` that have been written since their frame was allocated,
+    /// maintained only while uninitialized reads are checked
+    defined: Vec<u8>,
     /// Top of data stack; index of first unused bit
     next_frame_start: usize,
     /// Read frame stack
@@ -33,6 +40,16 @@ pub struct BitMachine {
     write: Vec<Frame>,
     /// Acceptable source type
     source_ty: Arc<Final>,
+    /// Whether compositions in tail position reuse the cells of the active read frame
+    reuse_frames: bool,
+    /// Whether the frames are checked against the type of every executed node
+    check_types: bool,
+    /// Whether reads of unwritten cells are detected
+    check_uninit: bool,
+    /// Largest number of cells that were in use at the same time
+    peak_cells: usize,
+    /// Largest number of frames that were on the read and write stacks at the same time
+    peak_frames: usize,
 }
 
 impl BitMachine {
@@ -40,15 +57,86 @@ impl BitMachine {
     pub fn for_program<J: Jet>(program: &RedeemNode<J>) -> Self {
         let io_width = program.arrow().source.bit_width() + program.arrow().target.bit_width();
 
+        let n_bytes = (io_width + program.bounds().extra_cells + 7) / 8;
         Self {
-            data: vec![0; (io_width + program.bounds().extra_cells + 7) / 8],
+            data: vec![0; n_bytes],
+            defined: vec![],
             next_frame_start: 0,
             read: Vec::with_capacity(program.bounds().extra_frames + analysis::IO_EXTRA_FRAMES),
             write: Vec::with_capacity(program.bounds().extra_frames + analysis::IO_EXTRA_FRAMES),
             source_ty: program.arrow().source.clone(),
+            reuse_frames: false,
+            check_types: false,
+            check_uninit: false,
+            peak_cells: 0,
+            peak_frames: 0,
         }
     }
 
+    /// Enable or disable the reuse of frame cells by compositions in tail position.
+    ///
+    /// When the right child of a composition is itself a composition, the input
+    /// frame of the outer composition is no longer needed once the left child of
+    /// the inner composition has been executed. With reuse enabled, the output of
+    /// that left child is moved into the cells of the dead input frame, so long
+    /// chains of compositions use a bounded number of cells instead of a number
+    /// that grows with the length of the chain.
+    ///
+    /// The results of execution are the same either way. Reuse is disabled by default.
+    pub fn set_frame_reuse(&mut self, enabled: bool) {
+        self.reuse_frames = enabled;
+    }
+
+    /// Enable or disable checking the frames against the type of every executed node.
+    ///
+    /// Before executing a node, the Bit Machine checks that the source and target types
+    /// of the node have exactly the widths of the cells that its parent hands to it,
+    /// and that the active read and write frames have room for these cells after
+    /// their cursors. Correctly typed programs always pass these checks, so a failure
+    /// indicates a bug in type inference or in the construction of the program.
+    /// Execution then stops with [`ExecutionError::TypeAnnotationMismatch`].
+    ///
+    /// This is meant for debugging and makes execution slower. Checks are disabled by default.
+    pub fn set_type_checks(&mut self, enabled: bool) {
+        self.check_types = enabled;
+    }
+
+    /// Enable or disable detecting reads of cells that were never written.
+    ///
+    /// The Bit Machine keeps track of which cells have been written since their frame
+    /// was allocated. Before a `case` or an assertion reads its choice bit and before
+    /// a jet reads its input, the Bit Machine checks that the cells are written.
+    /// Execution then stops with [`ExecutionError::UninitializedRead`]. Programs built
+    /// with correct types never read unwritten cells, so this is meant for hand-built
+    /// programs and witnesses.
+    ///
+    /// Tracking the cells makes every write slower. Checks are disabled by default.
+    /// Cells that are in use while the checks are enabled count as written.
+    pub fn set_uninit_checks(&mut self, enabled: bool) {
+        if enabled && !self.check_uninit {
+            self.defined = vec![0; self.data.len()];
+            for index in 0..self.next_frame_start {
+                self.set_defined(index, true);
+            }
+        }
+        self.check_uninit = enabled;
+    }
+
+    /// Return the largest number of cells that were in use at the same time
+    /// during the executions on this Bit Machine.
+    pub fn peak_cells(&self) -> usize {
+        self.peak_cells
+    }
+
+    /// Return the largest number of frames that were on the read and write
+    /// frame stacks at the same time during the executions on this Bit Machine.
+    ///
+    /// Together with [`Self::peak_cells`], this is the observed counterpart of the
+    /// static [`RedeemNode::bounds`].
+    pub fn peak_frames(&self) -> usize {
+        self.peak_frames
+    }
+
     #[cfg(test)]
     pub fn test_exec<J: Jet>(
         program: Arc<crate::node::ConstructNode<J>>,
@@ -65,6 +153,18 @@ impl BitMachine {
         mac.exec(&prog, env)
     }
 
+    /// Drop all frames and clear all cells, like in a newly constructed Bit Machine.
+    ///
+    /// The settings and the peak usage are kept.
+    fn reset(&mut self) {
+        for byte in self.data.iter_mut().chain(self.defined.iter_mut()) {
+            *byte = 0;
+        }
+        self.next_frame_start = 0;
+        self.read.clear();
+        self.write.clear();
+    }
+
     /// Push a new frame of given size onto the write frame stack
     fn new_frame(&mut self, len: usize) {
         debug_assert!(
@@ -76,8 +176,61 @@ impl BitMachine {
             "Stacks out of bounds: number of frames"
         );
 
+        if self.check_uninit {
+            for index in self.next_frame_start..self.next_frame_start + len {
+                self.set_defined(index, false);
+            }
+        }
         self.write.push(Frame::new(self.next_frame_start, len));
         self.next_frame_start += len;
+        self.peak_cells = cmp::max(self.peak_cells, self.next_frame_start);
+        self.peak_frames = cmp::max(self.peak_frames, self.read.len() + self.write.len());
+    }
+
+    /// Mark the given cell as written or unwritten.
+    fn set_defined(&mut self, index: usize, defined: bool) {
+        let mask = 1 << (7 - index % 8);
+        if defined {
+            self.defined[index / 8] |= mask;
+        } else {
+            self.defined[index / 8] &= !mask;
+        }
+    }
+
+    /// Check whether the given cell has been written.
+    fn is_defined(&self, index: usize) -> bool {
+        self.defined[index / 8] & (1 << (7 - index % 8)) != 0
+    }
+
+    /// Check whether the value of the given type, which starts at the cursor
+    /// of the active read frame, has been written.
+    ///
+    /// Padding cells of sum values are not part of the value, so they are not checked.
+    fn is_value_defined(&self, ty: &Final) -> bool {
+        let mut index = match self.read.last() {
+            Some(frame) => frame.cursor(),
+            None => return true,
+        };
+        let mut stack = vec![ty];
+        while let Some(ty) = stack.pop() {
+            match ty.bound() {
+                CompleteBound::Unit => {}
+                CompleteBound::Sum(left, right) => {
+                    if !self.is_defined(index) {
+                        return false;
+                    }
+                    let bit = self.data[index / 8] & (1 << (7 - index % 8)) != 0;
+                    let branch = if bit { right } else { left };
+                    index += ty.bit_width() - branch.bit_width();
+                    stack.push(branch);
+                }
+                CompleteBound::Product(left, right) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        true
     }
 
     /// Move the active write frame to the read frame stack
@@ -94,12 +247,40 @@ impl BitMachine {
         assert_eq!(self.next_frame_start, active_read_frame.start());
     }
 
+    /// Drop the read frame below the active one and move the active read frame
+    /// into its cells.
+    fn drop_frame_below(&mut self) {
+        let active_read_frame = self.read.pop().unwrap();
+        let below = self.read.pop().unwrap();
+        assert_eq!(below.start() + below.bit_width(), active_read_frame.start());
+
+        // The target cells lie below the source cells, so copying upwards is safe
+        let moved = Frame::new(below.start(), active_read_frame.bit_width());
+        for i in 0..active_read_frame.bit_width() {
+            let (from, to) = (active_read_frame.start() + i, moved.start() + i);
+            let mask = 1 << (7 - to % 8);
+            if self.data[from / 8] & (1 << (7 - from % 8)) != 0 {
+                self.data[to / 8] |= mask;
+            } else {
+                self.data[to / 8] &= !mask;
+            }
+            if self.check_uninit {
+                let defined = self.is_defined(from);
+                self.set_defined(to, defined);
+            }
+        }
+        self.next_frame_start = moved.start() + moved.bit_width();
+        self.read.push(moved);
+    }
+
     /// Write a single bit to the active write frame
     fn write_bit(&mut self, bit: bool) {
-        self.write
-            .last_mut()
-            .expect("Empty write frame stack")
-            .write_bit(bit, &mut self.data);
+        let frame = self.write.last_mut().expect("Empty write frame stack");
+        let index = frame.cursor();
+        frame.write_bit(bit, &mut self.data);
+        if self.check_uninit {
+            self.set_defined(index, true);
+        }
     }
 
     /// Move the cursor of the active write frame forward by
@@ -122,7 +303,15 @@ impl BitMachine {
         }
         let widx = self.write.len() - 1;
         let ridx = self.read.len() - 1;
+        let (write_start, read_start) = (self.write[widx].cursor(), self.read[ridx].cursor());
         self.write[widx].copy_from(&self.read[ridx], n, &mut self.data);
+        // Unwritten cells stay unwritten when they are copied
+        if self.check_uninit {
+            for i in 0..n {
+                let defined = self.is_defined(read_start + i);
+                self.set_defined(write_start + i, defined);
+            }
+        }
     }
 
     /// Move the cursor of the active read frame forward
@@ -149,10 +338,14 @@ impl BitMachine {
 
     /// Write a big-endian u8 value to the active write frame
     fn write_u8(&mut self, value: u8) {
-        self.write
-            .last_mut()
-            .expect("Empty write frame stack")
-            .write_u8(value, &mut self.data);
+        let frame = self.write.last_mut().expect("Empty write frame stack");
+        let start = frame.cursor();
+        frame.write_u8(value, &mut self.data);
+        if self.check_uninit {
+            for index in start..start + 8 {
+                self.set_defined(index, true);
+            }
+        }
     }
 
     /// Read a bit from the active read frame
@@ -195,6 +388,20 @@ impl BitMachine {
             .unwrap_or(0)
     }
 
+    /// Check whether the types of the given node match the cells of the given window
+    /// and whether the active frames have room for these cells.
+    fn frames_fit<J: Jet>(&self, node: &RedeemNode<J>, window: Window) -> bool {
+        let room = |frame: Option<&Frame>| {
+            frame.map_or(0, |frame| {
+                frame.start() + frame.bit_width() - frame.cursor()
+            })
+        };
+        node.arrow().source.bit_width() == window.read
+            && node.arrow().target.bit_width() == window.write
+            && window.read <= room(self.read.last())
+            && window.write <= room(self.write.last())
+    }
+
     /// Add a read frame with some given value in it, as input to the
     /// program
     pub fn input(&mut self, input: &Value) -> Result<(), ExecutionError> {
@@ -218,9 +425,162 @@ impl BitMachine {
         program: &RedeemNode<J>,
         env: &J::Environment,
     ) -> Result<Arc<Value>, ExecutionError> {
+        self.exec_with_limits(program, env, ExecLimits::NONE)
+    }
+
+    /// Execute the given program on the Bit Machine, using the given environment,
+    /// and stop as soon as the execution exceeds one of the given limits.
+    ///
+    /// Every executed combinator counts as one step, including jets, regardless of
+    /// how much work they do internally. Cells are counted like in [`Self::peak_cells`].
+    /// If a limit is exceeded, then execution stops before the offending node is
+    /// executed and [`ExecutionError::StepLimitExceeded`] or
+    /// [`ExecutionError::CellLimitExceeded`] is returned. The output frame is never read
+    /// in this case, so there is no partial output. The Bit Machine is then reset to
+    /// its state before [`Self::input`], so it can execute the program again.
+    ///
+    /// After any other error, the Bit Machine is left in the middle of execution
+    /// and must not be reused.
+    ///
+    /// Make sure the Bit Machine has enough space by constructing it via [`Self::for_program()`].
+    pub fn exec_with_limits<J: Jet + std::fmt::Debug>(
+        &mut self,
+        program: &RedeemNode<J>,
+        env: &J::Environment,
+        limits: ExecLimits,
+    ) -> Result<Arc<Value>, ExecutionError> {
+        self.exec_internal(
+            program,
+            &mut JetSource::Env(env),
+            &mut |_, value| Ok(Arc::clone(value)),
+            &mut NoTracker,
+            limits,
+        )
+    }
+
+    /// Execute the given program on the Bit Machine, using the given environment,
+    /// and report every step of the execution to the given tracker.
+    ///
+    /// Make sure the Bit Machine has enough space by constructing it via [`Self::for_program()`].
+    pub fn exec_with_tracker<J: Jet + std::fmt::Debug, T: ExecTracker<J>>(
+        &mut self,
+        program: &RedeemNode<J>,
+        env: &J::Environment,
+        tracker: &mut T,
+    ) -> Result<Arc<Value>, ExecutionError> {
+        self.exec_internal(
+            program,
+            &mut JetSource::Env(env),
+            &mut |_, value| Ok(Arc::clone(value)),
+            tracker,
+            ExecLimits::NONE,
+        )
+    }
+
+    /// Execute the given program on the Bit Machine, using the given environment,
+    /// and ask the given provider for the values of witness nodes.
+    ///
+    /// The provider is only asked for the witness nodes that are executed,
+    /// and the witness values inside the program are ignored. This means that
+    /// the program may be finalized with placeholder values, such as zeroes.
+    ///
+    /// Make sure the Bit Machine has enough space by constructing it via [`Self::for_program()`].
+    pub fn exec_with_provider<J: Jet + std::fmt::Debug, P: WitnessProvider>(
+        &mut self,
+        program: &RedeemNode<J>,
+        env: &J::Environment,
+        provider: &mut P,
+    ) -> Result<Arc<Value>, ExecutionError> {
+        let indices: HashMap<*const RedeemNode<J>, usize> = program
+            .post_order_iter::<InternalSharing>()
+            .map(|data| data.node as *const RedeemNode<J>)
+            .enumerate()
+            .map(|(index, ptr)| (ptr, index))
+            .collect();
+
+        self.exec_internal(
+            program,
+            &mut JetSource::Env(env),
+            &mut |node, _| {
+                let node_index = indices[&(node as *const RedeemNode<J>)];
+                let ty = &node.arrow().target;
+                match provider.provide(node_index, ty) {
+                    Some(value) if value.is_of_type(ty) => Ok(value),
+                    _ => Err(ExecutionError::WitnessUnavailable { node_index }),
+                }
+            },
+            &mut NoTracker,
+            ExecLimits::NONE,
+        )
+    }
+
+    /// Execute the given program on the Bit Machine, using the given environment,
+    /// and record a transcript of the execution.
+    ///
+    /// The transcript can be replayed without the environment using [`Self::replay`].
+    ///
+    /// Make sure the Bit Machine has enough space by constructing it via [`Self::for_program()`].
+    pub fn exec_with_transcript<J: Jet + std::fmt::Debug>(
+        &mut self,
+        program: &RedeemNode<J>,
+        env: &J::Environment,
+    ) -> (Result<Arc<Value>, ExecutionError>, Transcript) {
+        let mut recorder = transcript::Recorder::new(program);
+        let result = self.exec_with_tracker(program, env, &mut recorder);
+        (result, recorder.into_transcript())
+    }
+
+    /// Replay the transcript of an execution of the given program.
+    ///
+    /// Instead of calling jets, the Bit Machine takes their outputs from the transcript,
+    /// so no environment is needed and the outcome of the recorded execution is
+    /// reproduced. Returns an error if the transcript does not belong to the program.
+    ///
+    /// Make sure the Bit Machine has enough space by constructing it via [`Self::for_program()`].
+    pub fn replay<J: Jet + std::fmt::Debug>(
+        &mut self,
+        program: &RedeemNode<J>,
+        transcript: &Transcript,
+    ) -> Result<Arc<Value>, ExecutionError> {
+        let mut recorder = transcript::Recorder::new(program);
+        let mut jets = JetSource::Replay(transcript.jet_outputs().iter());
+        let result = self.exec_internal(
+            program,
+            &mut jets,
+            &mut |_, value| Ok(Arc::clone(value)),
+            &mut recorder,
+            ExecLimits::NONE,
+        );
+
+        let replayed = recorder.into_transcript();
+        let exhausted = match jets {
+            JetSource::Replay(mut outputs) => outputs.next().is_none(),
+            JetSource::Env(..) => unreachable!(),
+        };
+        if replayed.nodes() == transcript.nodes() && exhausted {
+            result
+        } else {
+            Err(ExecutionError::TranscriptMismatch)
+        }
+    }
+
+    fn exec_internal<J, W, T>(
+        &mut self,
+        program: &RedeemNode<J>,
+        jets: &mut JetSource<J>,
+        witnesses: &mut W,
+        tracker: &mut T,
+        limits: ExecLimits,
+    ) -> Result<Arc<Value>, ExecutionError>
+    where
+        J: Jet + std::fmt::Debug,
+        W: FnMut(&RedeemNode<J>, &Arc<Value>) -> Result<Arc<Value>, ExecutionError>,
+        T: ExecTracker<J>,
+    {
         enum CallStack<'a, J: Jet> {
-            Goto(&'a RedeemNode<J>),
+            Goto(&'a RedeemNode<J>, Window),
             MoveFrame,
+            MoveFrameDropBelow,
             DropFrame,
             CopyFwd(usize),
             Back(usize),
@@ -230,8 +590,9 @@ impl BitMachine {
         impl<'a, J: Jet> fmt::Debug for CallStack<'a, J> {
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                 match self {
-                    CallStack::Goto(ins) => write!(f, "goto {}", ins.inner()),
+                    CallStack::Goto(ins, _) => write!(f, "goto {}", ins.inner()),
                     CallStack::MoveFrame => f.write_str("move frame"),
+                    CallStack::MoveFrameDropBelow => f.write_str("move frame, drop below"),
                     CallStack::DropFrame => f.write_str("drop frame"),
                     CallStack::CopyFwd(n) => write!(f, "copy/fwd {}", n),
                     CallStack::Back(n) => write!(f, "back {}", n),
@@ -244,6 +605,7 @@ impl BitMachine {
         }
 
         let mut ip = program;
+        let mut window = Window::of(program);
         let mut call_stack = vec![];
         let mut iterations = 0u64;
 
@@ -257,6 +619,19 @@ impl BitMachine {
             if iterations % 1_000_000_000 == 0 {
                 println!("({:5} M) exec {:?}", iterations / 1_000_000, ip);
             }
+            if iterations > limits.max_steps {
+                self.reset();
+                return Err(ExecutionError::StepLimitExceeded(limits.max_steps));
+            }
+            // Includes the frames that the previous node allocated for this one
+            if self.next_frame_start > limits.max_cells {
+                self.reset();
+                return Err(ExecutionError::CellLimitExceeded(limits.max_cells));
+            }
+            tracker.visit_node(ip);
+            if self.check_types && !self.frames_fit(ip, window) {
+                return Err(ExecutionError::type_annotation_mismatch(program, ip));
+            }
 
             match ip.inner() {
                 node::Inner::Unit => {}
@@ -269,33 +644,67 @@ impl BitMachine {
                     let padl_b_c = ip.arrow().target.bit_width() - b.bit_width() - 1;
                     self.write_bit(false);
                     self.skip(padl_b_c);
-                    call_stack.push(CallStack::Goto(left));
+                    let window = window.with_write(b.bit_width());
+                    call_stack.push(CallStack::Goto(left, window));
                 }
                 node::Inner::InjR(left) => {
                     let (_b, c) = ip.arrow().target.as_sum().unwrap();
                     let padr_b_c = ip.arrow().target.bit_width() - c.bit_width() - 1;
                     self.write_bit(true);
                     self.skip(padr_b_c);
-                    call_stack.push(CallStack::Goto(left));
+                    let window = window.with_write(c.bit_width());
+                    call_stack.push(CallStack::Goto(left, window));
                 }
                 node::Inner::Pair(left, right) => {
-                    call_stack.push(CallStack::Goto(right));
-                    call_stack.push(CallStack::Goto(left));
+                    let (b, c) = ip.arrow().target.as_product().unwrap();
+                    call_stack.push(CallStack::Goto(right, window.with_write(c.bit_width())));
+                    call_stack.push(CallStack::Goto(left, window.with_write(b.bit_width())));
                 }
                 node::Inner::Comp(left, right) => {
                     let size_b = left.arrow().target.bit_width();
+                    // In tail position, the active read frame is dropped right after
+                    // this node. If it is the topmost frame, then it can already be
+                    // dropped after `left`, whose output takes its place.
+                    let tail = self.reuse_frames
+                        && matches!(call_stack.last(), Some(CallStack::DropFrame))
+                        && self.read.last().map_or(false, |frame| {
+                            frame.start() + frame.bit_width() == self.next_frame_start
+                        });
+
+                    let left_window = window.with_write(size_b);
+                    let right_window = Window {
+                        read: size_b,
+                        write: window.write,
+                    };
 
                     self.new_frame(size_b);
-                    call_stack.push(CallStack::DropFrame);
-                    call_stack.push(CallStack::Goto(right));
-                    call_stack.push(CallStack::MoveFrame);
-                    call_stack.push(CallStack::Goto(left));
+                    if tail {
+                        // Replace the pending drop of the active read frame
+                        call_stack.pop();
+                        call_stack.push(CallStack::DropFrame);
+                        call_stack.push(CallStack::Goto(right, right_window));
+                        call_stack.push(CallStack::MoveFrameDropBelow);
+                    } else {
+                        call_stack.push(CallStack::DropFrame);
+                        call_stack.push(CallStack::Goto(right, right_window));
+                        call_stack.push(CallStack::MoveFrame);
+                    }
+                    call_stack.push(CallStack::Goto(left, left_window));
                 }
                 node::Inner::Disconnect(left, right) => {
                     let size_prod_256_a = left.arrow().source.bit_width();
                     let size_a = size_prod_256_a - 256;
                     let size_prod_b_c = left.arrow().target.bit_width();
                     let size_b = size_prod_b_c - right.arrow().source.bit_width();
+                    let (b, d) = ip.arrow().target.as_product().unwrap();
+                    let left_window = Window {
+                        read: 256 + window.read,
+                        write: size_prod_b_c,
+                    };
+                    let right_window = Window {
+                        read: size_prod_b_c - b.bit_width(),
+                        write: d.bit_width(),
+                    };
 
                     self.new_frame(size_prod_256_a);
                     self.write_bytes(right.cmr().as_ref());
@@ -306,22 +715,30 @@ impl BitMachine {
                     // Remember that call stack pushes are executed in reverse order
                     call_stack.push(CallStack::DropFrame);
                     call_stack.push(CallStack::DropFrame);
-                    call_stack.push(CallStack::Goto(right));
+                    call_stack.push(CallStack::Goto(right, right_window));
                     call_stack.push(CallStack::CopyFwd(size_b));
                     call_stack.push(CallStack::MoveFrame);
-                    call_stack.push(CallStack::Goto(left));
+                    call_stack.push(CallStack::Goto(left, left_window));
                 }
-                node::Inner::Take(left) => call_stack.push(CallStack::Goto(left)),
-                node::Inner::Drop(left) => {
+                node::Inner::Take(left) => {
                     let size_a = ip.arrow().source.as_product().unwrap().0.bit_width();
+                    call_stack.push(CallStack::Goto(left, window.with_read(size_a)));
+                }
+                node::Inner::Drop(left) => {
+                    let (a, b) = ip.arrow().source.as_product().unwrap();
+                    let size_a = a.bit_width();
                     self.fwd(size_a);
                     call_stack.push(CallStack::Back(size_a));
-                    call_stack.push(CallStack::Goto(left));
+                    call_stack.push(CallStack::Goto(left, window.with_read(b.bit_width())));
                 }
                 node::Inner::Case(..) | node::Inner::AssertL(..) | node::Inner::AssertR(..) => {
+                    let choice_index = self.read[self.read.len() - 1].cursor();
+                    if self.check_uninit && !self.is_defined(choice_index) {
+                        return Err(ExecutionError::uninitialized_read(program, ip));
+                    }
                     let choice_bit = self.read[self.read.len() - 1].peek_bit(&self.data);
 
-                    let (sum_a_b, _c) = ip.arrow().source.as_product().unwrap();
+                    let (sum_a_b, c) = ip.arrow().source.as_product().unwrap();
                     let (a, b) = sum_a_b.as_sum().unwrap();
                     let size_a = a.bit_width();
                     let size_b = b.bit_width();
@@ -331,15 +748,19 @@ impl BitMachine {
                         | (node::Inner::AssertR(_, right), true) => {
                             let padr_a_b = cmp::max(size_a, size_b) - size_b;
                             self.fwd(1 + padr_a_b);
+                            tracker.track_case(ip, true);
                             call_stack.push(CallStack::Back(1 + padr_a_b));
-                            call_stack.push(CallStack::Goto(right));
+                            let window = window.with_read(size_b + c.bit_width());
+                            call_stack.push(CallStack::Goto(right, window));
                         }
                         (node::Inner::Case(left, _), false)
                         | (node::Inner::AssertL(left, _), false) => {
                             let padl_a_b = cmp::max(size_a, size_b) - size_a;
                             self.fwd(1 + padl_a_b);
+                            tracker.track_case(ip, false);
                             call_stack.push(CallStack::Back(1 + padl_a_b));
-                            call_stack.push(CallStack::Goto(left));
+                            let window = window.with_read(size_a + c.bit_width());
+                            call_stack.push(CallStack::Goto(left, window));
                         }
                         (node::Inner::AssertL(_, r_cmr), true) => {
                             return Err(ExecutionError::ReachedPrunedBranch(*r_cmr))
@@ -350,18 +771,41 @@ impl BitMachine {
                         _ => unreachable!(),
                     }
                 }
-                node::Inner::Witness(value) => self.write_value(value),
-                node::Inner::Jet(jet) => self.exec_jet(*jet, env)?,
+                node::Inner::Witness(value) => {
+                    let value = witnesses(ip, value)?;
+                    self.write_value(&value)
+                }
+                node::Inner::Jet(jet) => {
+                    if self.check_uninit && !self.is_value_defined(&ip.arrow().source) {
+                        return Err(ExecutionError::uninitialized_read(program, ip));
+                    }
+                    match jets {
+                        JetSource::Env(env) => self.exec_jet(*jet, env, tracker)?,
+                        JetSource::Replay(outputs) => match outputs.next() {
+                            Some(Some(bits)) if bits.len() == jet.target_ty().to_bit_width() => {
+                                for &bit in bits {
+                                    self.write_bit(bit);
+                                }
+                            }
+                            Some(None) => return Err(ExecutionError::JetFailed(JetFailed)),
+                            _ => return Err(ExecutionError::TranscriptMismatch),
+                        },
+                    }
+                }
                 node::Inner::Word(value) => self.write_value(value),
                 node::Inner::Fail(entropy) => {
                     return Err(ExecutionError::ReachedFailNode(*entropy))
                 }
             }
 
-            ip = loop {
+            let (next, next_window) = loop {
                 match call_stack.pop() {
-                    Some(CallStack::Goto(next)) => break next,
+                    Some(CallStack::Goto(next, next_window)) => break (next, next_window),
                     Some(CallStack::MoveFrame) => self.move_frame(),
+                    Some(CallStack::MoveFrameDropBelow) => {
+                        self.move_frame();
+                        self.drop_frame_below();
+                    }
                     Some(CallStack::DropFrame) => self.drop_frame(),
                     Some(CallStack::CopyFwd(n)) => {
                         self.copy(n);
@@ -371,6 +815,8 @@ impl BitMachine {
                     None => break 'main_loop,
                 };
             };
+            ip = next;
+            window = next_window;
         }
 
         if output_width > 0 {
@@ -387,10 +833,14 @@ impl BitMachine {
         }
     }
 
-    fn exec_jet<J: Jet>(&mut self, jet: J, env: &J::Environment) -> Result<(), JetFailed> {
+    fn exec_jet<J: Jet, T: ExecTracker<J>>(
+        &mut self,
+        jet: J,
+        env: &J::Environment,
+        tracker: &mut T,
+    ) -> Result<(), JetFailed> {
         use crate::ffi::c_jets::frame_ffi::{c_readBit, c_writeBit, CFrameItem};
         use crate::ffi::c_jets::uword_width;
-        use crate::ffi::ffi::UWORD;
 
         /// Create new C read frame that contains `bit_width` many bits from active read frame.
         ///
@@ -473,12 +923,18 @@ impl BitMachine {
         let output_width = jet.target_ty().to_bit_width();
         // Input buffer is implicitly referenced by input read frame!
         // Same goes for output buffer
-        let (input_read_frame, _input_buffer) = unsafe { get_input_frame(self, input_width) };
+        let (input_read_frame, input_buffer) = unsafe { get_input_frame(self, input_width) };
         let (mut output_write_frame, output_buffer) = unsafe { get_output_frame(output_width) };
 
         let jet_fn = jet.c_jet_ptr();
         let c_env = jet.c_jet_env(env);
         let success = jet_fn(&mut output_write_frame, input_read_frame, c_env);
+        tracker.track_jet_call(&JetCall {
+            jet,
+            input_buffer: &input_buffer,
+            output_buffer: &output_buffer,
+            success,
+        });
 
         if !success {
             Err(JetFailed)
@@ -489,6 +945,196 @@ impl BitMachine {
     }
 }
 
+/// Limits on the resources of a single execution on the Bit Machine.
+///
+/// See [`BitMachine::exec_with_limits`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ExecLimits {
+    /// Maximum number of executed combinators
+    pub max_steps: u64,
+    /// Maximum number of cells that are in use at the same time
+    pub max_cells: usize,
+}
+
+impl ExecLimits {
+    /// No limits.
+    pub const NONE: Self = ExecLimits {
+        max_steps: u64::MAX,
+        max_cells: usize::MAX,
+    };
+}
+
+impl Default for ExecLimits {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Widths of the cells that a node reads and writes, starting at the cursors
+/// of the active frames.
+#[derive(Clone, Copy, Debug)]
+struct Window {
+    read: usize,
+    write: usize,
+}
+
+impl Window {
+    /// Return the window of the given program, which covers the input and output frames.
+    fn of<J: Jet>(program: &RedeemNode<J>) -> Self {
+        Window {
+            read: program.arrow().source.bit_width(),
+            write: program.arrow().target.bit_width(),
+        }
+    }
+
+    fn with_read(self, read: usize) -> Self {
+        Window { read, ..self }
+    }
+
+    fn with_write(self, write: usize) -> Self {
+        Window { write, ..self }
+    }
+}
+
+/// Source of the outputs of jets during execution.
+enum JetSource<'a, J: Jet> {
+    /// Call the jets on the environment.
+    Env(&'a J::Environment),
+    /// Take the outputs from a transcript, in order of the jet calls.
+    Replay(std::slice::Iter<'a, Option<Vec<bool>>>),
+}
+
+/// A provider of witness values on demand.
+///
+/// See [`BitMachine::exec_with_provider`].
+pub trait WitnessProvider {
+    /// Return the value of the witness node at the given position,
+    /// which must be of the given type.
+    ///
+    /// Nodes are numbered like in [`DagLike::post_order_iter`] with [`InternalSharing`].
+    /// Returns `None` if no value is available.
+    fn provide(&mut self, node_index: usize, ty: &Final) -> Option<Arc<Value>>;
+}
+
+/// A tracker of the execution of a program on the Bit Machine.
+///
+/// Every method has a default implementation that does nothing.
+/// Users are expected to override the methods for the events they are interested in.
+pub trait ExecTracker<J: Jet> {
+    /// Called on every node, before it is executed.
+    fn visit_node(&mut self, _node: &RedeemNode<J>) {}
+
+    /// Called on every jet node, after the jet has been called.
+    fn track_jet_call(&mut self, _call: &JetCall<J>) {}
+
+    /// Called on every `case`, `assertl` and `assertr` node, right before it continues
+    /// with one of its branches.
+    ///
+    /// The branch is chosen by the tag bit of the input, which is `false` for the left
+    /// branch and `true` for the right branch.
+    fn track_case(&mut self, _node: &RedeemNode<J>, _right: bool) {}
+}
+
+/// Tracker that does nothing.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoTracker;
+
+impl<J: Jet> ExecTracker<J> for NoTracker {}
+
+/// Call of a jet during the execution of a program, as seen by an [`ExecTracker`].
+///
+/// The input and output of the jet are decoded lazily,
+/// so tracking jet calls costs nothing unless they are inspected.
+pub struct JetCall<'a, J> {
+    jet: J,
+    input_buffer: &'a [UWORD],
+    output_buffer: &'a [UWORD],
+    success: bool,
+}
+
+impl<'a, J: Jet> JetCall<'a, J> {
+    /// Accessor for the jet that was called.
+    pub fn jet(&self) -> J {
+        self.jet
+    }
+
+    /// Return whether the jet succeeded.
+    pub fn succeeded(&self) -> bool {
+        self.success
+    }
+
+    /// Return the input that the jet read from the active read frame.
+    pub fn input(&self) -> Arc<Value> {
+        decode_c_buffer(self.input_buffer, &self.jet.source_ty().to_final())
+    }
+
+    /// Return the output that the jet wrote to the active write frame.
+    ///
+    /// Returns `None` if the jet failed, in which case nothing was written.
+    pub fn output(&self) -> Option<Arc<Value>> {
+        if self.success {
+            Some(decode_c_buffer(
+                self.output_buffer,
+                &self.jet.target_ty().to_final(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, J: Jet> JetCall<'a, J> {
+    /// Return the bits that the jet wrote to the active write frame.
+    ///
+    /// Returns `None` if the jet failed.
+    fn output_bits(&self) -> Option<Vec<bool>> {
+        use crate::ffi::c_jets::frame_ffi::{c_readBit, CFrameItem};
+
+        if !self.success {
+            return None;
+        }
+        let bit_width = self.jet.target_ty().to_bit_width();
+        // Safety: the frame does not outlive the buffer, which is as wide as the jet output
+        let mut read_frame =
+            unsafe { CFrameItem::new_read(bit_width, self.output_buffer.as_ptr()) };
+        Some(
+            (0..bit_width)
+                .map(|_| unsafe { c_readBit(&mut read_frame) })
+                .collect(),
+        )
+    }
+}
+
+impl<'a, J: Jet> fmt::Debug for JetCall<'a, J> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JetCall")
+            .field("jet", &self.jet)
+            .field("input", &self.input())
+            .field("output", &self.output())
+            .finish()
+    }
+}
+
+/// Decode a value of the given type from a buffer that is used for C frames.
+fn decode_c_buffer(buffer: &[UWORD], ty: &Final) -> Arc<Value> {
+    use crate::ffi::c_jets::frame_ffi::{c_readBit, CFrameItem};
+
+    let bit_width = ty.bit_width();
+    assert!(crate::ffi::c_jets::uword_width(bit_width) <= buffer.len());
+    // Safety: the frame does not outlive the buffer, which is large enough
+    let mut read_frame = unsafe { CFrameItem::new_read(bit_width, buffer.as_ptr()) };
+    let mut bytes = vec![0u8; (bit_width + 7) / 8];
+    for i in 0..bit_width {
+        if unsafe { c_readBit(&mut read_frame) } {
+            bytes[i / 8] |= 1 << (7 - i % 8);
+        }
+    }
+
+    crate::BitIter::from(bytes)
+        .read_value(ty)
+        .expect("buffer is large enough")
+}
+
 /// Errors related to simplicity Execution
 #[derive(Debug)]
 pub enum ExecutionError {
@@ -500,6 +1146,48 @@ pub enum ExecutionError {
     ReachedPrunedBranch(Cmr),
     /// Jet failed during execution
     JetFailed(JetFailed),
+    /// A node read cells of the Bit Machine which had not been written.
+    ///
+    /// The node is identified by its position in the encoding of the program.
+    /// Well-typed programs can only do this if some witness value has the wrong type.
+    ///
+    /// This is only checked if enabled via [`BitMachine::set_uninit_checks`].
+    UninitializedRead { node_index: usize },
+    /// A replayed transcript does not match the execution of the program.
+    TranscriptMismatch,
+    /// The witness provider gave no value of the right type for the witness node
+    /// at the given position.
+    WitnessUnavailable { node_index: usize },
+    /// The frames of the Bit Machine do not fit the type of the node at the given position.
+    ///
+    /// This is only checked if enabled via [`BitMachine::set_type_checks`].
+    TypeAnnotationMismatch { node_index: usize },
+    /// Execution would take more than the given number of steps.
+    ///
+    /// This is only checked by [`BitMachine::exec_with_limits`].
+    StepLimitExceeded(u64),
+    /// Execution would use more than the given number of cells.
+    ///
+    /// This is only checked by [`BitMachine::exec_with_limits`].
+    CellLimitExceeded(usize),
+}
+
+impl ExecutionError {
+    /// Construct an error for an uninitialized read by the given node of the program.
+    fn uninitialized_read<J: Jet>(program: &RedeemNode<J>, node: &RedeemNode<J>) -> Self {
+        ExecutionError::UninitializedRead {
+            node_index: crate::encode::node_index(program, node)
+                .expect("executed node is part of program"),
+        }
+    }
+
+    /// Construct an error for a type mismatch of the given node of the program.
+    fn type_annotation_mismatch<J: Jet>(program: &RedeemNode<J>, node: &RedeemNode<J>) -> Self {
+        ExecutionError::TypeAnnotationMismatch {
+            node_index: crate::encode::node_index(program, node)
+                .expect("executed node is part of program"),
+        }
+    }
 }
 
 impl fmt::Display for ExecutionError {
@@ -515,6 +1203,24 @@ impl fmt::Display for ExecutionError {
                 write!(f, "Execution reached a pruned branch: {}", hash)
             }
             ExecutionError::JetFailed(jet_failed) => fmt::Display::fmt(jet_failed, f),
+            ExecutionError::UninitializedRead { node_index } => {
+                write!(f, "Node {} read uninitialized cells", node_index)
+            }
+            ExecutionError::TranscriptMismatch => {
+                f.write_str("Transcript does not match the execution of the program")
+            }
+            ExecutionError::WitnessUnavailable { node_index } => {
+                write!(f, "No witness value for node {}", node_index)
+            }
+            ExecutionError::TypeAnnotationMismatch { node_index } => {
+                write!(f, "Frames do not fit the type of node {}", node_index)
+            }
+            ExecutionError::StepLimitExceeded(max_steps) => {
+                write!(f, "Execution exceeded the limit of {} steps", max_steps)
+            }
+            ExecutionError::CellLimitExceeded(max_cells) => {
+                write!(f, "Execution exceeded the limit of {} cells", max_cells)
+            }
         }
     }
 }
@@ -529,12 +1235,16 @@ impl From<JetFailed> for ExecutionError {
 
 #[cfg(test)]
 mod tests {
-    #[cfg(feature = "elements")]
     use super::*;
 
+    use crate::jet::Core;
+    use crate::node::{
+        ConstructNode, CoreConstructible, JetConstructible, NoWitness, SimpleFinalizer,
+        WitnessConstructible,
+    };
+
     #[cfg(feature = "elements")]
     use crate::jet::{elements::ElementsEnv, Elements};
-    #[cfg(feature = "elements")]
     use crate::{node::RedeemNode, BitIter};
     #[cfg(feature = "elements")]
     use hex::DisplayHex;
@@ -583,7 +1293,20 @@ mod tests {
 
         // Try to run it on the bit machine and return the result
         let env = ElementsEnv::dummy();
-        BitMachine::for_program(&prog).exec(&prog, &env)
+        let result = BitMachine::for_program(&prog).exec(&prog, &env);
+
+        // Reusing frames must not change the result
+        let mut mac = BitMachine::for_program(&prog);
+        mac.set_frame_reuse(true);
+        let reusing_result = mac.exec(&prog, &env);
+        assert_eq!(
+            result.as_ref().ok(),
+            reusing_result.as_ref().ok(),
+            "frame reuse changed result of program {}",
+            prog_hex,
+        );
+
+        result
     }
 
     #[test]
@@ -611,4 +1334,433 @@ mod tests {
         );
         assert_eq!(res.unwrap(), Value::unit());
     }
+
+    #[test]
+    fn track_jet_call() {
+        use hashes::sha256::{HashEngine, Midstate};
+        use hashes::HashEngine as _;
+
+        #[derive(Default)]
+        struct JetLogger {
+            n_nodes: usize,
+            calls: Vec<(Core, Arc<Value>, Option<Arc<Value>>)>,
+        }
+
+        impl ExecTracker<Core> for JetLogger {
+            fn visit_node(&mut self, _: &RedeemNode<Core>) {
+                self.n_nodes += 1;
+            }
+
+            fn track_jet_call(&mut self, call: &JetCall<Core>) {
+                self.calls.push((call.jet(), call.input(), call.output()));
+            }
+        }
+
+        let iv = [0xab; 32];
+        let block = [0xcd; 64];
+        // main = comp (pair (const iv) (const block)) jet_sha256_block
+        let iv_node = Arc::<ConstructNode<Core>>::const_word(Value::u256_from_slice(&iv));
+        let block_node = Arc::<ConstructNode<Core>>::const_word(Value::u512_from_slice(&block));
+        let pair = Arc::<ConstructNode<Core>>::pair(&iv_node, &block_node).unwrap();
+        let jet = Arc::<ConstructNode<Core>>::jet(Core::Sha256Block);
+        let prog = Arc::<ConstructNode<Core>>::comp(&pair, &jet)
+            .unwrap()
+            .finalize_types_non_program()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+            .unwrap();
+
+        let mut logger = JetLogger::default();
+        let output = BitMachine::for_program(&prog)
+            .exec_with_tracker(&prog, &(), &mut logger)
+            .expect("executing program");
+        assert_eq!(logger.n_nodes, 5);
+        assert_eq!(logger.calls.len(), 1);
+
+        let (jet, input, jet_output) = &logger.calls[0];
+        assert_eq!(*jet, Core::Sha256Block);
+        assert_eq!(
+            *input,
+            Value::prod(Value::u256_from_slice(&iv), Value::u512_from_slice(&block))
+        );
+
+        let mut engine = HashEngine::from_midstate(Midstate::from_byte_array(iv), 0);
+        engine.input(&block);
+        let expected = Value::u256_from_slice(&engine.midstate().to_byte_array());
+        assert_eq!(jet_output.as_ref(), Some(&expected));
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn uninitialized_read() {
+        // pair witness unit :: 1 → 2 × 1
+        let witness = Arc::<ConstructNode<Core>>::witness(NoWitness);
+        let unit = Arc::<ConstructNode<Core>>::unit();
+        let pair = Arc::<ConstructNode<Core>>::pair(&witness, &unit).unwrap();
+        // case unit unit :: 2 × 1 → 1
+        let case_unit = Arc::<ConstructNode<Core>>::unit();
+        let case = Arc::<ConstructNode<Core>>::case(&case_unit, &case_unit).unwrap();
+        let program = Arc::<ConstructNode<Core>>::comp(&pair, &case)
+            .unwrap()
+            .finalize_types()
+            .unwrap();
+
+        let run = |witness: Arc<Value>, checks: bool| {
+            let program = program
+                .finalize(&mut SimpleFinalizer::new(std::iter::once(witness)))
+                .unwrap();
+            let mut mac = BitMachine::for_program(&program);
+            mac.set_uninit_checks(checks);
+            let case_index = program.node_indices()[&case.cmr()][0];
+            (mac.exec(&program, &()), case_index)
+        };
+
+        let (result, _) = run(Value::u1(0), true);
+        assert!(result.is_ok());
+        // Hand-built witness of the wrong type: the bit of the `case` is never written
+        let (result, case_index) = run(Value::unit(), true);
+        match result {
+            Err(ExecutionError::UninitializedRead { node_index }) => {
+                assert_eq!(node_index, case_index)
+            }
+            _ => panic!("expected uninitialized read"),
+        }
+        // Without checks, the `case` reads whatever is in the cell
+        let (result, _) = run(Value::unit(), false);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn transcript_replay() {
+        // main = comp (pair (const iv) (const block)) jet_sha256_block
+        let iv_node = Arc::<ConstructNode<Core>>::const_word(Value::u256_from_slice(&[0xab; 32]));
+        let block_node =
+            Arc::<ConstructNode<Core>>::const_word(Value::u512_from_slice(&[0xcd; 64]));
+        let pair = Arc::<ConstructNode<Core>>::pair(&iv_node, &block_node).unwrap();
+        let jet = Arc::<ConstructNode<Core>>::jet(Core::Sha256Block);
+        let prog = Arc::<ConstructNode<Core>>::comp(&pair, &jet)
+            .unwrap()
+            .finalize_types_non_program()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+            .unwrap();
+
+        let (output, transcript) = BitMachine::for_program(&prog).exec_with_transcript(&prog, &());
+        let output = output.expect("executing program");
+        assert_eq!(transcript.nodes().len(), 5);
+        assert_eq!(transcript.jet_outputs().len(), 1);
+
+        let bytes = transcript.encode_to_vec();
+        let decoded = Transcript::decode(&mut BitIter::from(&bytes[..])).unwrap();
+        assert_eq!(decoded, transcript);
+
+        let replayed = BitMachine::for_program(&prog)
+            .replay(&prog, &decoded)
+            .expect("replaying transcript");
+        assert_eq!(replayed, output);
+
+        // Transcript of another execution
+        let mut tampered = transcript.clone();
+        tampered.jet_outputs.clear();
+        assert!(matches!(
+            BitMachine::for_program(&prog).replay(&prog, &tampered),
+            Err(ExecutionError::TranscriptMismatch)
+        ));
+        let mut tampered = transcript;
+        tampered.nodes.pop();
+        assert!(matches!(
+            BitMachine::for_program(&prog).replay(&prog, &tampered),
+            Err(ExecutionError::TranscriptMismatch)
+        ));
+    }
+
+    #[test]
+    fn exec_with_provider() {
+        #[derive(Default)]
+        struct Provider {
+            queried: Vec<usize>,
+        }
+
+        impl WitnessProvider for Provider {
+            fn provide(&mut self, node_index: usize, ty: &Final) -> Option<Arc<Value>> {
+                assert_eq!(ty.bit_width(), 1);
+                self.queried.push(node_index);
+                // Select the right branch and pass its verification
+                Some(Value::u1(1))
+            }
+        }
+
+        // case (take (comp witness jet_verify)) (take (comp witness jet_verify))
+        let branch = || {
+            let witness = Arc::<ConstructNode<Core>>::witness(NoWitness);
+            let verify = Arc::<ConstructNode<Core>>::jet(Core::Verify);
+            Arc::<ConstructNode<Core>>::take(
+                &Arc::<ConstructNode<Core>>::comp(&witness, &verify).unwrap(),
+            )
+        };
+        let case = Arc::<ConstructNode<Core>>::case(&branch(), &branch()).unwrap();
+        // comp (pair witness unit) case
+        let selector = Arc::<ConstructNode<Core>>::witness(NoWitness);
+        let unit = Arc::<ConstructNode<Core>>::unit();
+        let pair = Arc::<ConstructNode<Core>>::pair(&selector, &unit).unwrap();
+        // Placeholder witness values, which the provider overrides
+        let placeholders = std::iter::repeat(Value::u1(0)).take(3);
+        let program = Arc::<ConstructNode<Core>>::comp(&pair, &case)
+            .unwrap()
+            .finalize_types()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(placeholders))
+            .unwrap();
+        let witness_indices: Vec<usize> = program
+            .as_ref()
+            .post_order_iter::<InternalSharing>()
+            .filter(|data| matches!(data.node.inner(), node::Inner::Witness(..)))
+            .map(|data| data.index)
+            .collect();
+        assert_eq!(witness_indices.len(), 3);
+
+        let mut provider = Provider::default();
+        BitMachine::for_program(&program)
+            .exec_with_provider(&program, &(), &mut provider)
+            .expect("executing program");
+        // The selector comes first in post order, then the left and right branch
+        assert_eq!(
+            provider.queried,
+            vec![witness_indices[0], witness_indices[2]]
+        );
+
+        // The placeholder values select the left branch, which fails
+        assert!(BitMachine::for_program(&program)
+            .exec(&program, &())
+            .is_err());
+    }
+
+    #[test]
+    fn frame_reuse_comp_chain() {
+        // comp (const 0xdeadbeef) (comp iden (comp iden ... iden)) :: 1 → 2^32
+        let mut chain = Arc::<ConstructNode<Core>>::iden();
+        for _ in 0..50 {
+            let iden = Arc::<ConstructNode<Core>>::iden();
+            chain = Arc::<ConstructNode<Core>>::comp(&iden, &chain).unwrap();
+        }
+        let word = Arc::<ConstructNode<Core>>::const_word(Value::u32(0xdeadbeef));
+        let prog = Arc::<ConstructNode<Core>>::comp(&word, &chain)
+            .unwrap()
+            .finalize_types_non_program()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+            .unwrap();
+
+        let mut naive = BitMachine::for_program(&prog);
+        let naive_output = naive.exec(&prog, &()).expect("executing program");
+        let mut reusing = BitMachine::for_program(&prog);
+        reusing.set_frame_reuse(true);
+        reusing.set_type_checks(true);
+        let reusing_output = reusing.exec(&prog, &()).expect("executing program");
+
+        assert_eq!(naive_output, Value::u32(0xdeadbeef));
+        assert_eq!(naive_output, reusing_output);
+        // Every composition of the chain keeps a frame alive in the naive executor
+        assert_eq!(naive.peak_cells(), 52 * 32);
+        assert_eq!(reusing.peak_cells(), 3 * 32);
+    }
+
+    #[test]
+    #[cfg(feature = "test-vectors")]
+    fn bounds_cover_peak_usage() {
+        use crate::ffi::tests::ffi::SimplicityErr;
+
+        let env = ElementsEnv::dummy();
+        let mut n_checked = 0;
+        for vector in crate::test_vectors::all() {
+            let mut bits = BitIter::from(&vector.program[..]);
+            let prog = match RedeemNode::<Elements>::decode(&mut bits) {
+                Ok(prog) => prog,
+                // Only expressions that are not programs may fail to decode
+                Err(error) => {
+                    assert_eq!(
+                        vector.result,
+                        SimplicityErr::TypeInferenceNotProgram,
+                        "{}: {}",
+                        vector.name,
+                        error
+                    );
+                    continue;
+                }
+            };
+            let io_width = prog.arrow().source.bit_width() + prog.arrow().target.bit_width();
+            let bounds = prog.bounds();
+
+            // Failing jets stop execution early, so the bounds must cover any prefix
+            let mut mac = BitMachine::for_program(&prog);
+            let _ = mac.exec(&prog, &env);
+            assert!(
+                mac.peak_cells() <= io_width + bounds.extra_cells,
+                "{}",
+                vector.name
+            );
+            assert!(
+                mac.peak_frames() <= bounds.extra_frames + analysis::IO_EXTRA_FRAMES,
+                "{}",
+                vector.name
+            );
+            n_checked += 1;
+        }
+        assert_eq!(n_checked, 4);
+    }
+
+    #[test]
+    #[cfg(feature = "test-vectors")]
+    fn exec_limits() {
+        struct StepCounter(u64);
+
+        impl ExecTracker<Elements> for StepCounter {
+            fn visit_node(&mut self, _node: &RedeemNode<Elements>) {
+                self.0 += 1;
+            }
+        }
+
+        let env = ElementsEnv::dummy();
+        let vector = crate::test_vectors::all()
+            .into_iter()
+            .find(|vector| vector.name == "schnorr0")
+            .unwrap();
+        let prog = RedeemNode::<Elements>::decode(&mut BitIter::from(&vector.program[..]))
+            .expect("decoding schnorr0");
+
+        let mut mac = BitMachine::for_program(&prog);
+        let mut counter = StepCounter(0);
+        let output = mac.exec_with_tracker(&prog, &env, &mut counter).unwrap();
+        let exact = ExecLimits {
+            max_steps: counter.0,
+            max_cells: mac.peak_cells(),
+        };
+
+        let mut mac = BitMachine::for_program(&prog);
+        assert_eq!(mac.exec_with_limits(&prog, &env, exact).unwrap(), output);
+        assert_eq!(
+            mac.exec_with_limits(&prog, &env, ExecLimits::default())
+                .unwrap(),
+            output
+        );
+
+        let few_steps = ExecLimits {
+            max_steps: exact.max_steps - 1,
+            ..exact
+        };
+        let few_cells = ExecLimits {
+            max_cells: exact.max_cells - 1,
+            ..exact
+        };
+        // The same Bit Machine executes the program again after each abort
+        let mut mac = BitMachine::for_program(&prog);
+        for _ in 0..2 {
+            match mac.exec_with_limits(&prog, &env, few_steps) {
+                Err(ExecutionError::StepLimitExceeded(n)) => assert_eq!(n, exact.max_steps - 1),
+                res => panic!("unexpected result {:?}", res),
+            }
+            match mac.exec_with_limits(&prog, &env, few_cells) {
+                Err(ExecutionError::CellLimitExceeded(n)) => assert_eq!(n, exact.max_cells - 1),
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+        assert_eq!(mac.exec_with_limits(&prog, &env, exact).unwrap(), output);
+    }
+
+    #[test]
+    fn type_checks() {
+        use crate::node::{Inner, RedeemData};
+        use crate::types::arrow::FinalArrow;
+
+        type Node = Arc<ConstructNode<Core>>;
+        let finalize = |node: Node| {
+            node.finalize_types_non_program()
+                .unwrap()
+                .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+                .unwrap()
+        };
+        // comp (pair (const 0xdeadbeef) (const 0xdeadbeef)) jet_eq_32 :: 1 → 2
+        let word = Node::const_word(Value::u32(0xdeadbeef));
+        let eq = finalize(
+            Node::comp(&Node::pair(&word, &word).unwrap(), &Node::jet(Core::Eq32)).unwrap(),
+        );
+        // comp (pair (injl unit) unit) (case unit unit) :: 1 → 1
+        let case = finalize(
+            Node::comp(
+                &Node::pair(&Node::injl(&Node::unit()), &Node::unit()).unwrap(),
+                &Node::case(&Node::unit(), &Node::unit()).unwrap(),
+            )
+            .unwrap(),
+        );
+        for prog in [eq, case] {
+            let mut mac = BitMachine::for_program(&prog);
+            mac.set_type_checks(true);
+            mac.exec(&prog, &())
+                .expect("executing correctly typed program");
+        }
+
+        // comp unit iden, where iden is annotated as 2^8 → 2^8 instead of 1 → 1
+        let unit_arrow = || FinalArrow {
+            source: Final::unit(),
+            target: Final::unit(),
+        };
+        let word_arrow = FinalArrow {
+            source: Final::two_two_n(3),
+            target: Final::two_two_n(3),
+        };
+        let unit = Arc::new(RedeemNode::<Core>::from_parts(
+            Inner::Unit,
+            Arc::new(RedeemData::new(unit_arrow(), Inner::Unit)),
+        ));
+        let iden = Arc::new(RedeemNode::from_parts(
+            Inner::Iden,
+            Arc::new(RedeemData::new(word_arrow, Inner::Iden)),
+        ));
+        let data = RedeemData::new(
+            unit_arrow(),
+            Inner::Comp(unit.cached_data(), iden.cached_data()),
+        );
+        let prog = RedeemNode::from_parts(Inner::Comp(unit, iden), Arc::new(data));
+        let mut mac = BitMachine::for_program(&prog);
+        mac.set_type_checks(true);
+        assert!(matches!(
+            mac.exec(&prog, &()),
+            Err(ExecutionError::TypeAnnotationMismatch { node_index: 1 })
+        ));
+
+        // comp (const 0x00) iden :: 1 → 2^4, where iden is annotated as 2^4 → 2^4
+        // instead of 2^8 → 2^8. The frames have room for it, but not the exact size.
+        let nibble_arrow = || FinalArrow {
+            source: Final::two_two_n(2),
+            target: Final::two_two_n(2),
+        };
+        let word = Arc::new(RedeemNode::<Core>::from_parts(
+            Inner::Word(Value::u8(0)),
+            Arc::new(RedeemData::new(
+                FinalArrow {
+                    source: Final::unit(),
+                    target: Final::two_two_n(3),
+                },
+                Inner::Word(Value::u8(0)),
+            )),
+        ));
+        let iden = Arc::new(RedeemNode::from_parts(
+            Inner::Iden,
+            Arc::new(RedeemData::new(nibble_arrow(), Inner::Iden)),
+        ));
+        let data = RedeemData::new(
+            FinalArrow {
+                source: Final::unit(),
+                target: Final::two_two_n(2),
+            },
+            Inner::Comp(word.cached_data(), iden.cached_data()),
+        );
+        let prog = RedeemNode::from_parts(Inner::Comp(word, iden), Arc::new(data));
+        let mut mac = BitMachine::for_program(&prog);
+        mac.set_type_checks(true);
+        assert!(matches!(
+            mac.exec(&prog, &()),
+            Err(ExecutionError::TypeAnnotationMismatch { node_index: 1 })
+        ));
+    }
 }
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Execution Transcripts
+//!
+//! A transcript records the nodes that the Bit Machine visited and the outputs
+//! of the jets that it called. Every other read and write of the Bit Machine
+//! follows deterministically from these, so a transcript is enough to replay an
+//! execution without access to the environment.
+
+use std::collections::HashMap;
+use std::io;
+
+use super::{ExecTracker, JetCall};
+use crate::dag::{DagLike, InternalSharing};
+use crate::jet::Jet;
+use crate::{decode, encode, BitIter, BitWriter, RedeemNode};
+
+/// Transcript of the execution of a program on the Bit Machine.
+///
+/// Record a transcript using [`super::BitMachine::exec_with_transcript`]
+/// and replay it using [`super::BitMachine::replay`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Transcript {
+    /// Post-order indices of the visited nodes, in order of execution.
+    pub(super) nodes: Vec<usize>,
+    /// Output bits of the called jets, in order of execution.
+    ///
+    /// `None` means the jet failed.
+    pub(super) jet_outputs: Vec<Option<Vec<bool>>>,
+}
+
+impl Transcript {
+    /// Accessor for the post-order indices of the visited nodes, in order of execution.
+    ///
+    /// Nodes are indexed as in [`DagLike::post_order_iter`] with [`InternalSharing`].
+    pub fn nodes(&self) -> &[usize] {
+        &self.nodes
+    }
+
+    /// Accessor for the output bits of the called jets, in order of execution.
+    ///
+    /// `None` means the jet failed.
+    pub fn jet_outputs(&self) -> &[Option<Vec<bool>>] {
+        &self.jet_outputs
+    }
+
+    /// Encode the transcript into bits.
+    ///
+    /// Returns the number of written bits.
+    pub fn encode<W: io::Write>(&self, w: &mut BitWriter<W>) -> io::Result<usize> {
+        let start_n = w.n_total_written();
+        // Naturals start at one, so all numbers are shifted up by one
+        encode::encode_natural(self.nodes.len() + 1, w)?;
+        for index in &self.nodes {
+            encode::encode_natural(index + 1, w)?;
+        }
+        encode::encode_natural(self.jet_outputs.len() + 1, w)?;
+        for output in &self.jet_outputs {
+            match output {
+                Some(bits) => {
+                    w.write_bit(true)?;
+                    encode::encode_natural(bits.len() + 1, w)?;
+                    for bit in bits {
+                        w.write_bit(*bit)?;
+                    }
+                }
+                None => w.write_bit(false)?,
+            }
+        }
+        Ok(w.n_total_written() - start_n)
+    }
+
+    /// Encode the transcript into a byte vector.
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        crate::write_to_vec(|w| self.encode(w))
+    }
+
+    /// Decode a transcript from bits, as produced by [`Self::encode`].
+    pub fn decode<I: Iterator<Item = u8>>(bits: &mut BitIter<I>) -> Result<Self, decode::Error> {
+        let decode_number = |bits: &mut BitIter<I>| {
+            decode::decode_natural(bits, usize::BITS as usize).map(|n| n - 1)
+        };
+
+        let n_nodes = decode_number(bits)?;
+        let mut nodes = Vec::new();
+        for _ in 0..n_nodes {
+            nodes.push(decode_number(bits)?);
+        }
+        let n_jets = decode_number(bits)?;
+        let mut jet_outputs = Vec::new();
+        for _ in 0..n_jets {
+            if bits.read_bit()? {
+                let len = decode_number(bits)?;
+                let mut output = Vec::new();
+                for _ in 0..len {
+                    output.push(bits.read_bit()?);
+                }
+                jet_outputs.push(Some(output));
+            } else {
+                jet_outputs.push(None);
+            }
+        }
+
+        Ok(Transcript { nodes, jet_outputs })
+    }
+}
+
+/// Tracker that records a transcript of the execution.
+pub(super) struct Recorder<J: Jet> {
+    indices: HashMap<*const RedeemNode<J>, usize>,
+    transcript: Transcript,
+}
+
+impl<J: Jet> Recorder<J> {
+    /// Create a recorder for executions of the given program.
+    pub fn new(program: &RedeemNode<J>) -> Self {
+        let indices = program
+            .post_order_iter::<InternalSharing>()
+            .map(|data| data.node as *const RedeemNode<J>)
+            .enumerate()
+            .map(|(index, ptr)| (ptr, index))
+            .collect();
+        Recorder {
+            indices,
+            transcript: Transcript::default(),
+        }
+    }
+
+    /// Return the recorded transcript.
+    pub fn into_transcript(self) -> Transcript {
+        self.transcript
+    }
+}
+
+impl<J: Jet> ExecTracker<J> for Recorder<J> {
+    fn visit_node(&mut self, node: &RedeemNode<J>) {
+        let index = self.indices[&(node as *const RedeemNode<J>)];
+        self.transcript.nodes.push(index);
+    }
+
+    fn track_jet_call(&mut self, call: &JetCall<J>) {
+        self.transcript.jet_outputs.push(call.output_bits());
+    }
+}
@@ -22,6 +22,7 @@ use std::sync::Arc;
 
 pub use self::error::{Error, ErrorSet};
 pub use self::named_node::NamedCommitNode;
+pub(crate) use self::serialize::DisplayWord;
 
 /// Line/column pair
 ///
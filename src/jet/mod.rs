@@ -91,11 +91,109 @@ pub trait Jet:
 
 #[cfg(test)]
 mod tests {
-    use crate::jet::Core;
-    use crate::node::{ConstructNode, CoreConstructible, JetConstructible};
+    use crate::jet::{Core, Jet};
+    use crate::node::{ConstructNode, CoreConstructible, JetConstructible, SimpleFinalizer};
+    use crate::types::{CompleteBound, Final};
     use crate::{BitMachine, Value};
+    use std::iter;
     use std::sync::Arc;
 
+    /// Return all values of the given type.
+    fn all_values(ty: &Final) -> Vec<Arc<Value>> {
+        match ty.bound() {
+            CompleteBound::Unit => vec![Value::unit()],
+            CompleteBound::Sum(left, right) => all_values(left)
+                .into_iter()
+                .map(Value::sum_l)
+                .chain(all_values(right).into_iter().map(Value::sum_r))
+                .collect(),
+            CompleteBound::Product(left, right) => {
+                let right_values = all_values(right);
+                all_values(left)
+                    .into_iter()
+                    .flat_map(|l| {
+                        right_values
+                            .iter()
+                            .map(move |r| Value::prod(Arc::clone(&l), Arc::clone(r)))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Check that the jet and its expansion agree on every input.
+    ///
+    /// Returns `false` without checking anything if the input of the jet
+    /// is wider than `max_bits`.
+    fn check_expansion_exhaustively(
+        jet: Core,
+        expansion: &Arc<ConstructNode<Core>>,
+        max_bits: usize,
+    ) -> bool {
+        let source_ty = jet.source_ty().to_final();
+        if source_ty.bit_width() > max_bits {
+            return false;
+        }
+
+        let finalize = |program: &Arc<ConstructNode<Core>>| {
+            program
+                .finalize_types_non_program()
+                .expect("finalizing types")
+                .finalize(&mut SimpleFinalizer::new(iter::empty()))
+                .expect("finalizing")
+        };
+        let jet = finalize(&Arc::<ConstructNode<Core>>::jet(jet));
+        let expansion = finalize(expansion);
+        assert_eq!(jet.arrow().source, expansion.arrow().source);
+        assert_eq!(jet.arrow().target, expansion.arrow().target);
+
+        for input in all_values(&source_ty) {
+            let run = |program| {
+                let mut mac = BitMachine::for_program(program);
+                mac.input(&input).expect("input has source type");
+                mac.exec(program, &())
+            };
+            assert_eq!(
+                run(&jet).ok(),
+                run(&expansion).ok(),
+                "jet {} disagrees with its expansion on input {}",
+                jet,
+                input
+            );
+        }
+        true
+    }
+
+    #[test]
+    fn exhaustive_jet_expansions() {
+        type Node = Arc<ConstructNode<Core>>;
+        // Shared nodes would share their types, so every use gets a fresh node
+        let iden = Node::iden;
+        let bit0 = || Node::injl(&Node::unit());
+        let bit1 = || Node::injr(&Node::unit());
+        // not :: 2 → 2
+        let not = Node::comp(
+            &Node::pair(&iden(), &Node::unit()).unwrap(),
+            &Node::case(&bit1(), &bit0()).unwrap(),
+        )
+        .unwrap();
+        // and, or :: 2 × 2 → 2
+        let and = || Node::case(&bit0(), &Node::drop_(&iden())).unwrap();
+        let or = || Node::case(&Node::drop_(&iden()), &bit1()).unwrap();
+
+        // eq_1 :: 2 × 2 → 2
+        let eq = Node::case(&Node::drop_(&not), &Node::drop_(&iden())).unwrap();
+        assert!(check_expansion_exhaustively(Core::Eq1, &eq, 8));
+        // maj_1 :: 2 × (2 × 2) → 2
+        let maj = Node::case(&Node::drop_(&and()), &Node::drop_(&or())).unwrap();
+        assert!(check_expansion_exhaustively(Core::Maj1, &maj, 8));
+        assert!(check_expansion_exhaustively(Core::And1, &and(), 8));
+        assert!(check_expansion_exhaustively(Core::Or1, &or(), 8));
+
+        // The input of eq_32 is too wide to enumerate
+        assert!(!check_expansion_exhaustively(Core::Eq32, &eq, 8));
+    }
+
     #[test]
     fn test_ffi_jet() {
         let two_words = Arc::<ConstructNode<_>>::comp(
@@ -36,17 +36,21 @@ mod merkle;
 pub mod node;
 #[cfg(feature = "elements")]
 pub mod policy;
+#[cfg(feature = "bitcoin")]
+pub mod taproot;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
 pub mod types;
 mod value;
 
 pub use bit_encoding::decode;
 pub use bit_encoding::encode;
+pub use bit_encoding::{first_diff_bit, write_to_vec, BitWriter};
 pub use bit_encoding::{u2, BitIter, EarlyEndOfStreamError};
-pub use bit_encoding::{write_to_vec, BitWriter};
 
 #[cfg(feature = "elements")]
 pub use crate::policy::{
-    sighash, Policy, Preimage32, Satisfier, SimplicityKey, ToXOnlyPubkey, Translator,
+    sighash, Policy, Preimage32, Satisfier, SimplicityKey, ToXOnlyPubkey, Translator, TranslatorExt,
 };
 
 pub use crate::analysis::{Cost, NodeBounds};
@@ -57,7 +61,7 @@ pub use crate::merkle::{
     cmr::Cmr,
     imr::{FirstPassImr, Imr},
     tmr::Tmr,
-    FailEntropy,
+    FailEntropy, LEAF_VERSION,
 };
 pub use crate::node::{CommitNode, ConstructNode, RedeemNode, WitnessNode};
 pub use crate::value::Value;
@@ -67,7 +71,7 @@ use std::fmt;
 /// Return the version of Simplicity leaves inside a tap tree.
 #[cfg(feature = "elements")]
 pub fn leaf_version() -> elements::taproot::LeafVersion {
-    elements::taproot::LeafVersion::from_u8(0xbe).expect("constant leaf version")
+    elements::taproot::LeafVersion::from_u8(LEAF_VERSION).expect("constant leaf version")
 }
 
 /// Error type for simplicity
@@ -84,12 +88,17 @@ pub enum Error {
     Type(crate::types::Error),
     /// Witness iterator ended early
     NoMoreWitnesses,
+    /// No witness data was given for the witness node with this label
+    MissingWitness(String),
     /// Finalization failed; did not have enough witness data to satisfy program.
     IncompleteFinalization,
     /// Witness has different length than defined in its preamble
     InconsistentWitnessLength,
     /// Tried to parse a jet but the name wasn't recognized
     InvalidJetName(String),
+    /// Malformed JSON representation of a program
+    #[cfg(feature = "serde")]
+    Json(String),
     /// Policy error
     #[cfg(feature = "elements")]
     Policy(policy::Error),
@@ -111,7 +120,10 @@ impl fmt::Display for Error {
                 f.write_str("witness has different length than defined in its preamble")
             }
             Error::InvalidJetName(s) => write!(f, "unknown jet `{}`", s),
+            #[cfg(feature = "serde")]
+            Error::Json(s) => write!(f, "invalid JSON program: {}", s),
             Error::NoMoreWitnesses => f.write_str("no more witness data available"),
+            Error::MissingWitness(s) => write!(f, "no witness data for `{}`", s),
             #[cfg(feature = "elements")]
             Error::Policy(ref e) => fmt::Display::fmt(e, f),
         }
@@ -126,9 +138,12 @@ impl std::error::Error for Error {
             Error::DisconnectRedeemTime => None,
             Error::Type(ref e) => Some(e),
             Error::NoMoreWitnesses => None,
+            Error::MissingWitness(_) => None,
             Error::IncompleteFinalization => None,
             Error::InconsistentWitnessLength => None,
             Error::InvalidJetName(..) => None,
+            #[cfg(feature = "serde")]
+            Error::Json(..) => None,
             #[cfg(feature = "elements")]
             Error::Policy(ref e) => Some(e),
         }
@@ -42,3 +42,154 @@ macro_rules! impl_serde_string {
         }
     };
 }
+
+/// Construct a program from an inline combinator expression.
+///
+/// Combinators are written in prefix form, with parenthesized subexpressions:
+///
+/// * `unit`, `iden` and `witness` take no arguments;
+/// * `(injl t)`, `(injr t)`, `(take t)` and `(drop t)` take one subexpression;
+/// * `(comp s t)`, `(case s t)` and `(pair s t)` take two subexpressions;
+/// * `(jet j)` takes a jet and `(const v)` takes an `Arc<Value>`.
+///
+/// Passing the wrong number of arguments to a combinator is a compile error.
+/// The macro evaluates to `Result<Arc<CommitNode<J>>, Error>`, where the jet
+/// type `J` is inferred, and fails if the expression does not type-check.
+///
+/// ```
+/// use std::sync::Arc;
+/// use simplicity::jet::Core;
+/// use simplicity::CommitNode;
+///
+/// let program: Arc<CommitNode<Core>> =
+///     simplicity::simplicity!(comp (pair unit unit) (take unit)).unwrap();
+/// assert_eq!(program.arrow().to_string(), "1 → 1");
+/// ```
+#[macro_export]
+macro_rules! simplicity {
+    (@node $atom:ident) => {
+        $crate::simplicity!(@comb $atom)
+    };
+    (@node ($comb:ident $($args:tt)*)) => {
+        $crate::simplicity!(@comb $comb $($args)*)
+    };
+    (@node $other:tt) => {
+        compile_error!(concat!("expected a combinator, found `", stringify!($other), "`"))
+    };
+
+    (@comb unit) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::unit()
+    };
+    (@comb iden) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::iden()
+    };
+    (@comb witness) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::WitnessConstructible<_>>::witness(
+            $crate::node::NoWitness,
+        )
+    };
+    (@comb injl $t:tt) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::injl(
+            &$crate::simplicity!(@node $t),
+        )
+    };
+    (@comb injr $t:tt) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::injr(
+            &$crate::simplicity!(@node $t),
+        )
+    };
+    (@comb take $t:tt) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::take(
+            &$crate::simplicity!(@node $t),
+        )
+    };
+    (@comb drop $t:tt) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::drop_(
+            &$crate::simplicity!(@node $t),
+        )
+    };
+    (@comb comp $s:tt $t:tt) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::comp(
+            &$crate::simplicity!(@node $s),
+            &$crate::simplicity!(@node $t),
+        )?
+    };
+    (@comb case $s:tt $t:tt) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::case(
+            &$crate::simplicity!(@node $s),
+            &$crate::simplicity!(@node $t),
+        )?
+    };
+    (@comb pair $s:tt $t:tt) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::pair(
+            &$crate::simplicity!(@node $s),
+            &$crate::simplicity!(@node $t),
+        )?
+    };
+    (@comb jet $jet:expr) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::JetConstructible<_>>::jet($jet)
+    };
+    (@comb const $value:expr) => {
+        <std::sync::Arc<$crate::ConstructNode<_>> as $crate::node::CoreConstructible>::const_word(
+            $value,
+        )
+    };
+    (@comb $comb:ident $($args:tt)*) => {
+        compile_error!(concat!(
+            "unknown combinator or wrong number of arguments: `",
+            stringify!($comb $($args)*),
+            "`"
+        ))
+    };
+
+    ($($expr:tt)+) => {
+        (|| -> Result<std::sync::Arc<$crate::CommitNode<_>>, $crate::Error> {
+            let node = $crate::simplicity!(@node ($($expr)+));
+            node.finalize_types()
+        })()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::jet::Core;
+    use crate::node::{CoreConstructible, JetConstructible};
+    use crate::{CommitNode, ConstructNode, Value};
+
+    #[test]
+    fn simplicity_macro_cmr() {
+        let program: Arc<CommitNode<Core>> =
+            simplicity!(comp (pair unit unit) (take unit)).unwrap();
+        let pair = Arc::<ConstructNode<Core>>::pair(
+            &Arc::<ConstructNode<Core>>::unit(),
+            &Arc::<ConstructNode<Core>>::unit(),
+        )
+        .unwrap();
+        let take = Arc::<ConstructNode<Core>>::take(&Arc::<ConstructNode<Core>>::unit());
+        let expected = Arc::<ConstructNode<Core>>::comp(&pair, &take).unwrap();
+        assert_eq!(program.cmr(), expected.cmr());
+
+        let program: Arc<CommitNode<Core>> = simplicity!(
+            comp (const Value::u8(5)) (comp (comp (pair iden iden) (jet Core::Eq8)) (jet Core::Verify))
+        )
+        .unwrap();
+        let word = Arc::<ConstructNode<Core>>::const_word(Value::u8(5));
+        let iden = Arc::<ConstructNode<Core>>::iden();
+        let pair = Arc::<ConstructNode<Core>>::pair(&iden, &iden).unwrap();
+        let eq = Arc::<ConstructNode<Core>>::jet(Core::Eq8);
+        let verify = Arc::<ConstructNode<Core>>::jet(Core::Verify);
+        let eq = Arc::<ConstructNode<Core>>::comp(&pair, &eq).unwrap();
+        let check = Arc::<ConstructNode<Core>>::comp(&eq, &verify).unwrap();
+        let expected = Arc::<ConstructNode<Core>>::comp(&word, &check).unwrap();
+        assert_eq!(program.cmr(), expected.cmr());
+    }
+
+    #[test]
+    fn simplicity_macro_type_error() {
+        // unit has target type 1, which is not a product as needed by case
+        let program: Result<Arc<CommitNode<Core>>, _> = simplicity!(comp unit (case unit unit));
+        assert!(program.is_err());
+    }
+}
@@ -3,6 +3,7 @@
 use crate::impl_midstate_wrapper;
 use crate::jet::Jet;
 use crate::types::arrow::FinalArrow;
+use crate::types::Final;
 use crate::{Cmr, Tmr, Value};
 use hashes::sha256::Midstate;
 
@@ -100,6 +101,16 @@ impl FirstPassImr {
 
     /// Produce a CMR for a witness combinator
     pub fn witness(ty: &FinalArrow, value: &Value) -> Self {
+        Self::witness_commitment(value, &ty.target)
+    }
+
+    /// Produce the commitment to a witness value of the given type
+    ///
+    /// This is the first-pass IMR of a witness node, which depends only on the value
+    /// and its type. The first-pass IMR of a program combines the commitments of its
+    /// witness nodes like that of any other node, and [`Imr::compute_pass2`] turns it
+    /// into the IMR of the program.
+    pub fn witness_commitment(value: &Value, ty: &Final) -> Self {
         use hashes::{sha256, HashEngine};
 
         // 1 Bit-wise hash of `value`
@@ -107,7 +118,7 @@ impl FirstPassImr {
         // 2 Hash of hash of `value` and TMR of `value_type`
         let mut engine = sha256::HashEngine::from_midstate(Self::WITNESS_IV.0, 0);
         engine.input(&value_hash[..]);
-        engine.input(ty.target.tmr().as_ref());
+        engine.input(ty.tmr().as_ref());
         FirstPassImr(engine.midstate())
     }
 
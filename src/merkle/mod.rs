@@ -14,6 +14,11 @@ use crate::Value;
 use hashes::{sha256, Hash, HashEngine};
 use std::fmt;
 
+/// Tap leaf version of Simplicity leaves, which commit to the CMR of a program.
+///
+/// This is the leaf version that Elements uses for its Simplicity deployment.
+pub const LEAF_VERSION: u8 = 0xbe;
+
 /// 512-bit opaque blob of data used to seed `Fail` nodes
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct FailEntropy([u8; 64]);
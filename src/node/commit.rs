@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: CC0-1.0
 
-use crate::dag::{DagLike, MaxSharing, NoSharing, PostOrderIterItem};
+use crate::dag::{DagLike, InternalSharing, MaxSharing, NoSharing, PostOrderIterItem};
 use crate::jet::Jet;
 use crate::types::arrow::{Arrow, FinalArrow};
 use crate::{encode, types};
@@ -13,6 +13,7 @@ use super::{
 
 use std::io;
 use std::marker::PhantomData;
+use std::ops::Range;
 use std::sync::Arc;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
@@ -251,6 +252,33 @@ impl<J: Jet> CommitNode<J> {
     pub fn decode<I: Iterator<Item = u8>>(bits: &mut BitIter<I>) -> Result<Arc<Self>, Error> {
         // 1. Decode program with out witnesses.
         let construct = crate::decode::decode_expression(bits)?;
+        Self::from_decoded(construct)
+    }
+
+    /// Decode a Simplicity program from bits, without witness data, along with
+    /// the range of bits that each node occupied in the input.
+    ///
+    /// The ranges are listed in the order of the encoding, which is the post order
+    /// of the program with [`InternalSharing`].
+    /// See [`crate::decode::decode_expression_with_offsets`].
+    pub fn decode_with_offsets<I: Iterator<Item = u8>>(
+        bits: &mut BitIter<I>,
+    ) -> Result<(Arc<Self>, Vec<Range<usize>>), Error> {
+        let (construct, offsets) = crate::decode::decode_expression_with_offsets(bits)?;
+        Self::from_decoded(construct).map(|program| (program, offsets))
+    }
+
+    /// Parse a Simplicity program, without witness data, from its assembly listing.
+    ///
+    /// The listing is checked like the bit encoding in [`Self::decode`].
+    /// See [`crate::decode::decode_asm`] for the format.
+    pub fn parse_asm(s: &str) -> Result<Arc<Self>, Error> {
+        let construct = crate::decode::decode_asm(s)?;
+        Self::from_decoded(construct)
+    }
+
+    /// Finalize the types of a decoded expression and check its sharing.
+    fn from_decoded(construct: Arc<ConstructNode<J>>) -> Result<Arc<Self>, Error> {
         let program = construct.finalize_types()?;
         // 2. Do sharing check, using incomplete IMRs
         if program.as_ref().is_shared_as::<MaxSharing<Commit<J>>>() {
@@ -277,6 +305,72 @@ impl<J: Jet> CommitNode<J> {
 
         program_and_witness_bytes
     }
+
+    /// Return the assembly listing of the program, with one line per node
+    /// of its bit encoding.
+    ///
+    /// See [`crate::encode::encode_asm`] for the format.
+    pub fn to_asm(&self) -> String {
+        let mut s = String::new();
+        encode::encode_asm(self, &mut s).expect("writing to a string never fails");
+        s
+    }
+
+    /// Check whether the program is fully revealed and none of its nodes has the
+    /// given CMR.
+    ///
+    /// A program is fully revealed if it has no hidden children of assertions
+    /// and no disconnected expressions, which could contain any fragment.
+    fn is_revealed_without(&self, fragment: Cmr) -> bool {
+        self.post_order_iter::<InternalSharing>()
+            .all(|data| match data.node.inner() {
+                Inner::AssertL(..) | Inner::AssertR(..) | Inner::Disconnect(..) => false,
+                _ => data.node.cmr() != fragment,
+            })
+    }
+
+    /// Produce a proof that the fragment with the given CMR does not occur
+    /// anywhere in the program.
+    ///
+    /// Returns `None` if the fragment does occur, or if the program has hidden
+    /// or disconnected subexpressions that might contain it.
+    pub fn non_membership_proof(&self, fragment: Cmr) -> Option<NonMembershipProof> {
+        if self.is_revealed_without(fragment) {
+            Some(NonMembershipProof {
+                program: self.encode_to_vec(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Proof that a fragment does not occur in a program with a given CMR.
+///
+/// The proof consists of the encoding of the entire program, without witness data.
+/// Because the CMR of a program commits to all of its nodes, a verifier who
+/// decodes the program and obtains the expected CMR knows the set of nodes of
+/// the program, and can check that the fragment is not among them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NonMembershipProof {
+    program: Vec<u8>,
+}
+
+impl NonMembershipProof {
+    /// Accessor for the encoded program
+    pub fn program(&self) -> &[u8] {
+        &self.program
+    }
+
+    /// Verify that the fragment with CMR `fragment` does not occur in the program
+    /// with CMR `program_cmr`.
+    pub fn verify<J: Jet>(&self, program_cmr: Cmr, fragment: Cmr) -> bool {
+        let mut bits = BitIter::from(&self.program[..]);
+        match CommitNode::<J>::decode(&mut bits) {
+            Ok(program) => program.cmr() == program_cmr && program.is_revealed_without(fragment),
+            Err(_) => false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -289,7 +383,7 @@ mod tests {
     use crate::decode::Error;
     use crate::human_encoding::Forest;
     use crate::jet::Core;
-    use crate::node::SimpleFinalizer;
+    use crate::node::{CoreConstructible, SimpleFinalizer};
     use crate::{BitMachine, Value};
 
     fn assert_program_deserializable<J: Jet>(
@@ -368,6 +462,33 @@ mod tests {
         };
     }
 
+    #[test]
+    fn non_membership_proof() {
+        // wit1 = witness :: 1 -> 2^32
+        // wit2 = witness :: 1 -> 2^32
+        // wits_are_equal = comp (pair wit1 wit2) jet_eq_32 :: 1 -> 2
+        // main = comp wits_are_equal jet_verify            :: 1 -> 1
+        let eqwits = [0xcd, 0xdc, 0x51, 0xb6, 0xe2, 0x08, 0xc0, 0x40];
+        let program = CommitNode::<Core>::decode(&mut BitIter::from(&eqwits[..])).unwrap();
+        let absent = Cmr::jet(Core::Add32);
+        let present = Cmr::jet(Core::Eq32);
+
+        let proof = program.non_membership_proof(absent).unwrap();
+        assert!(proof.verify::<Core>(program.cmr(), absent));
+        assert!(!proof.verify::<Core>(Cmr::unit(), absent));
+        assert!(!proof.verify::<Core>(program.cmr(), present));
+        assert!(program.non_membership_proof(present).is_none());
+
+        // The hidden child of an assertion may contain anything
+        let unit = Arc::<ConstructNode<Core>>::unit();
+        let asserted = Arc::<ConstructNode<Core>>::assertl(&unit, absent)
+            .unwrap()
+            .finalize_types_non_program()
+            .unwrap();
+        assert!(asserted.non_membership_proof(absent).is_none());
+        assert!(asserted.non_membership_proof(present).is_none());
+    }
+
     #[test]
     fn canonical_order() {
         // "main = comp unit iden", but with the iden serialized before the unit
@@ -532,6 +653,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn decode_with_offsets() {
+        let forest = Forest::<Core>::parse("main := comp (pair unit unit) (take unit)").unwrap();
+        let main = &forest.roots()["main"];
+        let mut bytes = vec![];
+        let program_bits = main.encode(&mut BitWriter::new(&mut bytes)).unwrap();
+
+        let mut iter = BitIter::from(&bytes[..]);
+        let (program, offsets) = CommitNode::<Core>::decode_with_offsets(&mut iter).unwrap();
+        assert_eq!(program.cmr(), main.cmr());
+
+        // One range per node, from the end of the length prefix to the end of the program
+        let mut prefix = BitIter::from(&bytes[..]);
+        assert_eq!(prefix.read_natural(None).unwrap(), offsets.len());
+        assert_eq!(
+            offsets.len(),
+            program.post_order_iter::<InternalSharing>().count()
+        );
+        assert_eq!(offsets[0].start, prefix.n_total_read());
+        for pair in offsets.windows(2) {
+            assert!(!pair[0].is_empty());
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+        assert_eq!(offsets.last().unwrap().end, program_bits);
+    }
+
+    #[test]
+    fn asm_roundtrip() {
+        let forest = Forest::<Core>::parse(
+            "
+                wit := witness : 1 -> 2^32
+                eq := comp (pair wit (const 0xdeadbeef)) jet_eq_32 : 1 -> 2
+                cond := comp (pair eq unit) (assertl unit #{unit})
+                main := comp (pair cond (const 0b01)) unit
+            ",
+        )
+        .unwrap();
+        let main = forest.roots()["main"].to_commit_node();
+        let asm = main.to_asm();
+        let parsed = CommitNode::<Core>::parse_asm(&asm).unwrap();
+        assert_eq!(parsed.cmr(), main.cmr());
+        assert_eq!(parsed.encode_to_vec(), main.encode_to_vec());
+        assert_eq!(parsed.to_asm(), asm);
+
+        let errors: [(&str, &dyn fmt::Display); 5] = [
+            ("0: unit\n1: comp 0 1", &Error::BadIndex),
+            ("0: unit\n2: iden", &Error::NotInCanonicalOrder),
+            ("0: iden\n1: unit\n2: comp 1 0", &Error::NotInCanonicalOrder),
+            ("0: unit\n1: pair 0", &Error::AsmSyntax(2)),
+            ("0: jet frobnicate_32", &Error::InvalidJet),
+        ];
+        for (asm, err) in errors {
+            match CommitNode::<Core>::parse_asm(asm) {
+                Err(e) => assert_eq!(e.to_string(), err.to_string(), "{}", asm),
+                Ok(_) => panic!("parsed invalid listing {}", asm),
+            }
+        }
+    }
+
     #[test]
     fn extra_nodes() {
         // main = comp unit unit # but with an extra unconnected `unit` stuck on the beginning
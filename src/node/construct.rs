@@ -1,17 +1,20 @@
 // SPDX-License-Identifier: CC0-1.0
 
-use crate::dag::{InternalSharing, PostOrderIterItem};
+use crate::dag::{DagLike, InternalSharing, PostOrderIterItem};
 use crate::encode;
 use crate::jet::Jet;
 use crate::types::{self, arrow::Arrow};
 use crate::{BitIter, BitWriter, Cmr, FailEntropy, Value};
 
+use std::collections::HashMap;
 use std::io;
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::vec;
 
 use super::{
     Commit, CommitData, CommitNode, Converter, Inner, Marker, NoDisconnect, NoWitness, Node,
+    Redeem, RedeemData, RedeemNode,
 };
 use super::{CoreConstructible, DisconnectConstructible, JetConstructible, WitnessConstructible};
 
@@ -117,6 +120,100 @@ impl<J: Jet> ConstructNode<J> {
         self.convert::<InternalSharing, _, _>(&mut FinalizeTypes(PhantomData))
     }
 
+    /// Create a witness node with the given label.
+    ///
+    /// The label is used by [`Self::finalize_named`] to bind witness data by name.
+    /// It does not affect the commitment of the program.
+    pub fn labeled_witness(label: &str) -> Arc<Self> {
+        let data = ConstructData {
+            arrow: Arrow::witness(NoWitness),
+            witness_label: Some(Arc::from(label)),
+            phantom: PhantomData,
+        };
+        Arc::new(Node::from_parts(Inner::Witness(NoWitness), data))
+    }
+
+    /// Convert a [`ConstructNode`] to a [`RedeemNode`] by finalizing all of the types
+    /// and binding witness data by name.
+    ///
+    /// Witness nodes created by [`Self::labeled_witness`] are bound to the value under
+    /// their label. Unlabeled witness nodes fall back to positional binding: they are
+    /// bound to the value under their position among all witness nodes of the program,
+    /// in post order, so the first witness node is bound to `"0"`.
+    ///
+    /// Like [`crate::node::SimpleFinalizer`], this does not type-check the witness data.
+    pub fn finalize_named(
+        &self,
+        map: HashMap<String, Value>,
+    ) -> Result<Arc<RedeemNode<J>>, crate::Error> {
+        struct NamedFinalizer {
+            labels: vec::IntoIter<String>,
+            map: HashMap<String, Arc<Value>>,
+        }
+
+        impl<J: Jet> Converter<Commit<J>, Redeem<J>> for NamedFinalizer {
+            type Error = crate::Error;
+
+            fn convert_witness(
+                &mut self,
+                _: &PostOrderIterItem<&CommitNode<J>>,
+                _: &NoWitness,
+            ) -> Result<Arc<Value>, Self::Error> {
+                let label = self.labels.next().ok_or(crate::Error::NoMoreWitnesses)?;
+                self.map
+                    .get(&label)
+                    .map(Arc::clone)
+                    .ok_or(crate::Error::MissingWitness(label))
+            }
+
+            fn convert_disconnect(
+                &mut self,
+                _: &PostOrderIterItem<&CommitNode<J>>,
+                _: Option<&Arc<RedeemNode<J>>>,
+                _: &NoDisconnect,
+            ) -> Result<Arc<RedeemNode<J>>, Self::Error> {
+                Err(crate::Error::IncompleteFinalization)
+            }
+
+            fn convert_data(
+                &mut self,
+                data: &PostOrderIterItem<&CommitNode<J>>,
+                inner: Inner<&Arc<RedeemNode<J>>, J, &Arc<RedeemNode<J>>, &Arc<Value>>,
+            ) -> Result<Arc<RedeemData<J>>, Self::Error> {
+                let converted_data = inner
+                    .map(|node| node.cached_data())
+                    .map_disconnect(|node| node.cached_data())
+                    .map_witness(Arc::clone);
+                Ok(Arc::new(RedeemData::new(
+                    data.node.arrow().shallow_clone(),
+                    converted_data,
+                )))
+            }
+        }
+
+        // Type finalization preserves the sharing of the DAG, so both iterations
+        // visit the witness nodes in the same order.
+        let labels: Vec<String> = self
+            .post_order_iter::<InternalSharing>()
+            .filter(|data| matches!(data.node.inner(), Inner::Witness(_)))
+            .enumerate()
+            .map(|(position, data)| match data.node.data.witness_label() {
+                Some(label) => label.to_string(),
+                None => position.to_string(),
+            })
+            .collect();
+        let map = map
+            .into_iter()
+            .map(|(label, value)| (label, Arc::new(value)))
+            .collect();
+
+        let commit = self.finalize_types()?;
+        commit.convert::<InternalSharing, Redeem<J>, _>(&mut NamedFinalizer {
+            labels: labels.into_iter(),
+            map,
+        })
+    }
+
     /// Decode a Simplicity expression from bits, without witness data.
     ///
     /// # Usage
@@ -143,6 +240,8 @@ impl<J: Jet> ConstructNode<J> {
 #[derive(Clone, Debug)]
 pub struct ConstructData<J> {
     arrow: Arrow,
+    /// Label of a witness node, used to bind its witness data by name.
+    witness_label: Option<Arc<str>>,
     /// This isn't really necessary, but it helps type inference if every
     /// struct has a \<J\> parameter, since it forces the choice of jets to
     /// be consistent without the user needing to specify it too many times.
@@ -154,6 +253,7 @@ impl<J: Jet> ConstructData<J> {
     pub fn new(arrow: Arrow) -> Self {
         ConstructData {
             arrow,
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -162,12 +262,18 @@ impl<J: Jet> ConstructData<J> {
     pub fn arrow(&self) -> &Arrow {
         &self.arrow
     }
+
+    /// Accessor for the label of a witness node, if it has one
+    pub fn witness_label(&self) -> Option<&Arc<str>> {
+        self.witness_label.as_ref()
+    }
 }
 
 impl<J> CoreConstructible for ConstructData<J> {
     fn iden() -> Self {
         ConstructData {
             arrow: Arrow::iden(),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -175,6 +281,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn unit() -> Self {
         ConstructData {
             arrow: Arrow::unit(),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -182,6 +289,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn injl(child: &Self) -> Self {
         ConstructData {
             arrow: Arrow::injl(&child.arrow),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -189,6 +297,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn injr(child: &Self) -> Self {
         ConstructData {
             arrow: Arrow::injr(&child.arrow),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -196,6 +305,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn take(child: &Self) -> Self {
         ConstructData {
             arrow: Arrow::take(&child.arrow),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -203,6 +313,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn drop_(child: &Self) -> Self {
         ConstructData {
             arrow: Arrow::drop_(&child.arrow),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -210,6 +321,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn comp(left: &Self, right: &Self) -> Result<Self, types::Error> {
         Ok(ConstructData {
             arrow: Arrow::comp(&left.arrow, &right.arrow)?,
+            witness_label: None,
             phantom: PhantomData,
         })
     }
@@ -217,6 +329,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn case(left: &Self, right: &Self) -> Result<Self, types::Error> {
         Ok(ConstructData {
             arrow: Arrow::case(&left.arrow, &right.arrow)?,
+            witness_label: None,
             phantom: PhantomData,
         })
     }
@@ -224,6 +337,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn assertl(left: &Self, right: Cmr) -> Result<Self, types::Error> {
         Ok(ConstructData {
             arrow: Arrow::assertl(&left.arrow, right)?,
+            witness_label: None,
             phantom: PhantomData,
         })
     }
@@ -231,6 +345,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn assertr(left: Cmr, right: &Self) -> Result<Self, types::Error> {
         Ok(ConstructData {
             arrow: Arrow::assertr(left, &right.arrow)?,
+            witness_label: None,
             phantom: PhantomData,
         })
     }
@@ -238,6 +353,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn pair(left: &Self, right: &Self) -> Result<Self, types::Error> {
         Ok(ConstructData {
             arrow: Arrow::pair(&left.arrow, &right.arrow)?,
+            witness_label: None,
             phantom: PhantomData,
         })
     }
@@ -245,6 +361,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn fail(entropy: FailEntropy) -> Self {
         ConstructData {
             arrow: Arrow::fail(entropy),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -252,6 +369,7 @@ impl<J> CoreConstructible for ConstructData<J> {
     fn const_word(word: Arc<Value>) -> Self {
         ConstructData {
             arrow: Arrow::const_word(word),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -265,6 +383,7 @@ impl<J: Jet> DisconnectConstructible<Option<Arc<ConstructNode<J>>>> for Construc
         let right = right.as_ref();
         Ok(ConstructData {
             arrow: Arrow::disconnect(&left.arrow, &right.map(|n| n.arrow()))?,
+            witness_label: None,
             phantom: PhantomData,
         })
     }
@@ -274,6 +393,7 @@ impl<J> WitnessConstructible<NoWitness> for ConstructData<J> {
     fn witness(witness: NoWitness) -> Self {
         ConstructData {
             arrow: Arrow::witness(witness),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -283,6 +403,7 @@ impl<J: Jet> JetConstructible<J> for ConstructData<J> {
     fn jet(jet: J) -> Self {
         ConstructData {
             arrow: Arrow::jet(jet),
+            witness_label: None,
             phantom: PhantomData,
         }
     }
@@ -386,4 +507,62 @@ mod tests {
             Arc::<ConstructNode<Core>>::scribe(&Value::u2(1)).cmr()
         );
     }
+
+    fn eq8_program(
+        left: Arc<ConstructNode<Core>>,
+        right: Arc<ConstructNode<Core>>,
+    ) -> Arc<ConstructNode<Core>> {
+        let pair = Arc::<ConstructNode<Core>>::pair(&left, &right).unwrap();
+        let eq = Arc::<ConstructNode<Core>>::jet(Core::Eq8);
+        let verify = Arc::<ConstructNode<Core>>::jet(Core::Verify);
+        let comp = Arc::<ConstructNode<Core>>::comp(&pair, &eq).unwrap();
+        Arc::<ConstructNode<Core>>::comp(&comp, &verify).unwrap()
+    }
+
+    fn witness_values(redeem: &RedeemNode<Core>) -> Vec<Arc<Value>> {
+        redeem
+            .post_order_iter::<InternalSharing>()
+            .filter_map(|data| match data.node.inner() {
+                Inner::Witness(value) => Some(Arc::clone(value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finalize_named() {
+        let program = eq8_program(
+            ConstructNode::<Core>::labeled_witness("a"),
+            ConstructNode::<Core>::labeled_witness("b"),
+        );
+
+        // Insert the labels in the opposite order of the witness nodes
+        let mut map = HashMap::new();
+        map.insert("b".to_owned(), Value::u8(2).as_ref().clone());
+        map.insert("a".to_owned(), Value::u8(1).as_ref().clone());
+        let redeem = program.finalize_named(map).unwrap();
+        assert_eq!(witness_values(&redeem), vec![Value::u8(1), Value::u8(2)]);
+
+        let mut map = HashMap::new();
+        map.insert("a".to_owned(), Value::u8(1).as_ref().clone());
+        assert!(matches!(
+            program.finalize_named(map),
+            Err(crate::Error::MissingWitness(label)) if label == "b",
+        ));
+    }
+
+    #[test]
+    fn finalize_named_positional() {
+        let program = eq8_program(
+            ConstructNode::<Core>::labeled_witness("a"),
+            Arc::<ConstructNode<Core>>::witness(NoWitness),
+        );
+
+        // The unlabeled node is the second witness node
+        let mut map = HashMap::new();
+        map.insert("1".to_owned(), Value::u8(2).as_ref().clone());
+        map.insert("a".to_owned(), Value::u8(1).as_ref().clone());
+        let redeem = program.finalize_named(map).unwrap();
+        assert_eq!(witness_values(&redeem), vec![Value::u8(1), Value::u8(2)]);
+    }
 }
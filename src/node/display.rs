@@ -1,6 +1,7 @@
 use std::fmt;
 
 use crate::dag::{Dag, DagLike, InternalSharing, NoSharing};
+use crate::human_encoding::DisplayWord;
 use crate::node::{Inner, Marker, Node};
 
 /// Display a Simplicity expression as a linear string.
@@ -33,6 +34,10 @@ use crate::node::{Inner, Marker, Node};
 /// `drop` → `I` (looks like one)
 ///
 /// `iden` → `H`
+///
+/// ## Words
+///
+/// Constant words are written in hex, or in binary if they are shorter than a byte.
 pub struct DisplayExpr<'a, M: Marker>(&'a Node<M>);
 
 impl<'a, M: Marker> From<&'a Node<M>> for DisplayExpr<'a, M> {
@@ -100,7 +105,7 @@ where
                         Inner::Witness(..) => f.write_str("witness ")?,
                         Inner::Fail(..) => f.write_str("fail")?,
                         Inner::Jet(jet) => write!(f, "jet_{jet} ")?,
-                        Inner::Word(value) => write!(f, "const {value} ")?,
+                        Inner::Word(value) => write!(f, "const {} ", DisplayWord(value))?,
                     }
 
                     match data.node.inner().as_dag() {
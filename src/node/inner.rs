@@ -2,6 +2,7 @@
 
 use super::{Disconnectable, FailEntropy};
 use crate::dag::Dag;
+use crate::human_encoding::DisplayWord;
 use crate::{Cmr, Value};
 
 use std::fmt;
@@ -401,7 +402,7 @@ impl<C, J: fmt::Display, X, W> fmt::Display for Inner<C, J, X, W> {
             Inner::Witness(..) => f.write_str("witness"),
             Inner::Fail(..) => f.write_str("fail"),
             Inner::Jet(jet) => write!(f, "jet({})", jet),
-            Inner::Word(w) => write!(f, "word({})", w),
+            Inner::Word(w) => write!(f, "word({})", DisplayWord(w)),
         }
     }
 }
@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! JSON Representation of Programs
+//!
+//! Programs are represented as a list of nodes in post order. Each node refers
+//! to its children by their position in the list, and the root node comes last.
+//! Unlike the bit encoding, this format is meant for interchange with tooling
+//! that does not speak Simplicity, and it has no consensus meaning.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use hex::{DisplayHex, FromHex};
+
+use crate::dag::{DagLike, InternalSharing};
+use crate::jet::Jet;
+use crate::node::{
+    CommitNode, ConstructNode, CoreConstructible, DisconnectConstructible, Inner, JetConstructible,
+    NoWitness, WitnessConstructible,
+};
+use crate::{BitIter, Cmr, Error, FailEntropy};
+
+/// A single node of the JSON representation.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+#[serde(tag = "node", rename_all = "lowercase")]
+enum JsonNode {
+    Iden,
+    Unit,
+    InjL { child: usize },
+    InjR { child: usize },
+    Take { child: usize },
+    Drop { child: usize },
+    Comp { left: usize, right: usize },
+    Case { left: usize, right: usize },
+    AssertL { left: usize, right: Cmr },
+    AssertR { left: Cmr, right: usize },
+    Pair { left: usize, right: usize },
+    Disconnect { left: usize },
+    Witness,
+    Fail { entropy: String },
+    Jet { name: String },
+    Word { bit_length: usize, hex: String },
+}
+
+impl<J: Jet> CommitNode<J> {
+    /// Serialize the program as JSON.
+    ///
+    /// Programs can be reconstructed from their JSON using [`CommitNode::from_json`].
+    pub fn to_json(&self) -> String {
+        let mut nodes = vec![];
+        for data in self.post_order_iter::<InternalSharing>() {
+            let left = data.left_index.unwrap_or_default();
+            let right = data.right_index.unwrap_or_default();
+            let node = match data.node.inner() {
+                Inner::Iden => JsonNode::Iden,
+                Inner::Unit => JsonNode::Unit,
+                Inner::InjL(_) => JsonNode::InjL { child: left },
+                Inner::InjR(_) => JsonNode::InjR { child: left },
+                Inner::Take(_) => JsonNode::Take { child: left },
+                Inner::Drop(_) => JsonNode::Drop { child: left },
+                Inner::Comp(_, _) => JsonNode::Comp { left, right },
+                Inner::Case(_, _) => JsonNode::Case { left, right },
+                Inner::AssertL(_, cmr) => JsonNode::AssertL { left, right: *cmr },
+                Inner::AssertR(cmr, _) => JsonNode::AssertR {
+                    left: *cmr,
+                    right: left,
+                },
+                Inner::Pair(_, _) => JsonNode::Pair { left, right },
+                Inner::Disconnect(_, _) => JsonNode::Disconnect { left },
+                Inner::Witness(_) => JsonNode::Witness,
+                Inner::Fail(entropy) => JsonNode::Fail {
+                    entropy: entropy.as_ref().to_lower_hex_string(),
+                },
+                Inner::Jet(jet) => JsonNode::Jet {
+                    name: jet.to_string(),
+                },
+                Inner::Word(value) => {
+                    let (bytes, bit_length) = value.to_bytes_len();
+                    JsonNode::Word {
+                        bit_length,
+                        hex: bytes.to_lower_hex_string(),
+                    }
+                }
+            };
+            nodes.push(node);
+        }
+
+        serde_json::to_string(&nodes).expect("serializing to a string never fails")
+    }
+
+    /// Reconstruct a program from its JSON representation, as produced by
+    /// [`CommitNode::to_json`].
+    ///
+    /// Like [`CommitNode::decode`], the root of the reconstructed expression must have
+    /// unit source and target types.
+    pub fn from_json(s: &str) -> Result<Arc<Self>, Error> {
+        let json_nodes: Vec<JsonNode> =
+            serde_json::from_str(s).map_err(|e| Error::Json(e.to_string()))?;
+        let mut converted = Vec::<Arc<ConstructNode<J>>>::with_capacity(json_nodes.len());
+
+        for (index, node) in json_nodes.into_iter().enumerate() {
+            // Children must come before their parents
+            let get = |i: usize| {
+                converted
+                    .get(i)
+                    .ok_or_else(|| Error::Json(format!("node {} has bad child {}", index, i)))
+            };
+            let new = match node {
+                JsonNode::Iden => Arc::<ConstructNode<J>>::iden(),
+                JsonNode::Unit => Arc::<ConstructNode<J>>::unit(),
+                JsonNode::InjL { child } => Arc::<ConstructNode<J>>::injl(get(child)?),
+                JsonNode::InjR { child } => Arc::<ConstructNode<J>>::injr(get(child)?),
+                JsonNode::Take { child } => Arc::<ConstructNode<J>>::take(get(child)?),
+                JsonNode::Drop { child } => Arc::<ConstructNode<J>>::drop_(get(child)?),
+                JsonNode::Comp { left, right } => {
+                    Arc::<ConstructNode<J>>::comp(get(left)?, get(right)?)?
+                }
+                JsonNode::Case { left, right } => {
+                    Arc::<ConstructNode<J>>::case(get(left)?, get(right)?)?
+                }
+                JsonNode::AssertL { left, right } => {
+                    Arc::<ConstructNode<J>>::assertl(get(left)?, right)?
+                }
+                JsonNode::AssertR { left, right } => {
+                    Arc::<ConstructNode<J>>::assertr(left, get(right)?)?
+                }
+                JsonNode::Pair { left, right } => {
+                    Arc::<ConstructNode<J>>::pair(get(left)?, get(right)?)?
+                }
+                JsonNode::Disconnect { left } => {
+                    Arc::<ConstructNode<J>>::disconnect(get(left)?, &None)?
+                }
+                JsonNode::Witness => Arc::<ConstructNode<J>>::witness(NoWitness),
+                JsonNode::Fail { entropy } => {
+                    let bytes = <[u8; 64]>::from_hex(&entropy)
+                        .map_err(|e| Error::Json(format!("bad fail entropy: {}", e)))?;
+                    Arc::<ConstructNode<J>>::fail(FailEntropy::from_byte_array(bytes))
+                }
+                JsonNode::Jet { name } => {
+                    let jet = name.parse::<J>().map_err(|_| Error::InvalidJetName(name))?;
+                    Arc::<ConstructNode<J>>::jet(jet)
+                }
+                JsonNode::Word { bit_length, hex } => {
+                    if bit_length.count_ones() != 1 || u32::try_from(bit_length).is_err() {
+                        return Err(Error::Json(format!("bad word length {}", bit_length)));
+                    }
+                    let bytes = Vec::<u8>::from_hex(&hex)
+                        .map_err(|e| Error::Json(format!("bad word: {}", e)))?;
+                    let mut bits = BitIter::from(bytes.into_iter());
+                    let word = crate::decode::decode_power_of_2(&mut bits, bit_length)?;
+                    Arc::<ConstructNode<J>>::const_word(word)
+                }
+            };
+            converted.push(new);
+        }
+
+        let root = converted
+            .pop()
+            .ok_or_else(|| Error::Json("empty program".to_owned()))?;
+        root.finalize_types()
+    }
+}
+
+#[cfg(all(test, feature = "elements"))]
+mod tests {
+    use super::*;
+    use crate::jet::Core;
+    use crate::policy::Policy;
+    use elements::bitcoin::key::XOnlyPublicKey;
+    use elements::secp256k1_zkp;
+    use hashes::{sha256, Hash};
+
+    #[test]
+    fn json_roundtrip() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let keypair = secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let key: XOnlyPublicKey = keypair.x_only_public_key().0;
+        let policy = Policy::Or {
+            left: Arc::new(Policy::Key(key)),
+            right: Arc::new(Policy::And {
+                left: Arc::new(Policy::Sha256(sha256::Hash::from_byte_array([0; 32]))),
+                right: Arc::new(Policy::Older(144)),
+            }),
+            weights: [1, 1],
+        };
+
+        let commit = policy.commit().unwrap();
+        let json = commit.to_json();
+        let decoded = CommitNode::from_json(&json).unwrap();
+        assert_eq!(decoded, commit);
+        assert_eq!(decoded.cmr(), commit.cmr());
+    }
+
+    #[test]
+    fn json_bad_child() {
+        let json = r#"[{"node":"unit"},{"node":"injl","child":1}]"#;
+        assert!(matches!(
+            CommitNode::<Core>::from_json(json),
+            Err(Error::Json(_))
+        ));
+    }
+}
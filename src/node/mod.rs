@@ -63,10 +63,11 @@
 //!    completeness.
 //!
 
-use crate::dag::{DagLike, MaxSharing, NoSharing, SharingTracker};
+use crate::dag::{DagLike, InternalSharing, MaxSharing, NoSharing, SharingTracker};
 use crate::jet::Jet;
 use crate::{types, Cmr, FailEntropy, Value};
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::{fmt, hash};
 
@@ -76,10 +77,12 @@ mod convert;
 mod disconnect;
 mod display;
 mod inner;
+#[cfg(feature = "serde")]
+mod json;
 mod redeem;
 mod witness;
 
-pub use commit::{Commit, CommitData, CommitNode};
+pub use commit::{Commit, CommitData, CommitNode, NonMembershipProof};
 pub use construct::{Construct, ConstructData, ConstructNode};
 pub use convert::{Converter, Hide, SimpleFinalizer};
 pub use disconnect::{Disconnectable, NoDisconnect};
@@ -161,6 +164,15 @@ impl<J, X, W, T> Constructible<J, X, W> for T where
 {
 }
 
+/// Constructors for the core combinators.
+///
+/// Hidden children are given by their CMR, which only `assertl` and `assertr`
+/// accept. Therefore, the constructors cannot build a `case` node with two hidden
+/// children or a hidden child of any other node. The decoder rejects these with
+/// [`decode::Error::BothChildrenHidden`] and [`decode::Error::HiddenNode`].
+///
+/// [`decode::Error::BothChildrenHidden`]: crate::decode::Error::BothChildrenHidden
+/// [`decode::Error::HiddenNode`]: crate::decode::Error::HiddenNode
 pub trait CoreConstructible: Sized {
     fn iden() -> Self;
     fn unit() -> Self;
@@ -642,6 +654,43 @@ impl<N: Marker> Node<N> {
         Ok(converted.pop().unwrap())
     }
 
+    /// Return the positions of the nodes of the expression in its bit encoding,
+    /// grouped by CMR.
+    ///
+    /// See [`crate::encode::node_indices`] for details.
+    pub fn node_indices(&self) -> HashMap<Cmr, Vec<usize>> {
+        crate::encode::node_indices(self)
+    }
+
+    /// Return the positions of the assertions and `fail` nodes of the expression
+    /// in its bit encoding.
+    ///
+    /// See [`crate::encode::assertion_indices`] for details.
+    pub fn assertion_indices(&self) -> Vec<usize> {
+        crate::encode::assertion_indices(self)
+    }
+
+    /// Return the positions of the `comp` nodes of the expression in its bit encoding
+    /// that have `iden` as a child.
+    ///
+    /// See [`crate::encode::redundant_comp_indices`] for details.
+    pub fn redundant_comp_indices(&self) -> Vec<usize> {
+        crate::encode::redundant_comp_indices(self)
+    }
+
+    /// Return the bit widths and values of the `word` nodes of the expression,
+    /// in post order.
+    ///
+    /// Nodes that are shared inside the expression are listed once.
+    pub fn word_constants(&self) -> Vec<(usize, Arc<Value>)> {
+        self.post_order_iter::<InternalSharing>()
+            .filter_map(|data| match data.node.inner() {
+                Inner::Word(value) => Some((value.len(), Arc::clone(value))),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Display the Simplicity expression as a linear string.
     ///
     /// The linear string has no sharing and may be **exponentially larger**
@@ -652,17 +701,22 @@ impl<N: Marker> Node<N> {
 }
 
 #[cfg(test)]
-#[cfg(all(feature = "test-utils", feature = "elements"))]
 mod tests {
-
+    #[cfg(all(feature = "test-utils", feature = "elements"))]
     use ffi::tests::TestData;
 
+    #[cfg(all(feature = "test-utils", feature = "elements"))]
     use crate::analysis::Cost;
+    #[cfg(all(feature = "test-utils", feature = "elements"))]
     use crate::ffi;
+    #[cfg(all(feature = "test-utils", feature = "elements"))]
     use crate::jet::Elements;
+    #[cfg(all(feature = "test-utils", feature = "elements"))]
     use crate::BitIter;
+    #[cfg(all(feature = "test-utils", feature = "elements"))]
     use crate::RedeemNode;
 
+    #[cfg(all(feature = "test-utils", feature = "elements"))]
     fn check_merkle_roots(test: &TestData) {
         let mut bits = BitIter::from(test.prog.as_slice());
         ffi::tests::run_program(&test.prog, ffi::tests::TestUpTo::CheckOneOne).unwrap();
@@ -674,6 +728,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "test-utils", feature = "elements"))]
     fn progs_cmr() {
         let schnorr0 = ffi::tests::schnorr0_test_data();
         let schnorr6 = ffi::tests::schnorr6_test_data();
@@ -685,4 +740,42 @@ mod tests {
         check_merkle_roots(&ctx8_unpruned);
         check_merkle_roots(&ctx8_pruned);
     }
+
+    #[test]
+    fn word_constants() {
+        use crate::jet::Core;
+        use crate::node::{CoreConstructible, JetConstructible};
+        use crate::{ConstructNode, Value};
+        use std::sync::Arc;
+
+        // comp (pair (const 0x0000_0090) (const 0x0000_0090)) jet_eq_32 :: 1 → 2
+        let word = Arc::<ConstructNode<Core>>::const_word(Value::u32(144));
+        let pair = Arc::<ConstructNode<Core>>::pair(&word, &word).unwrap();
+        let eq = Arc::<ConstructNode<Core>>::jet(Core::Eq32);
+        let program = Arc::<ConstructNode<Core>>::comp(&pair, &eq).unwrap();
+
+        assert_eq!(program.word_constants(), vec![(32, Value::u32(144))]);
+    }
+
+    #[test]
+    #[cfg(feature = "elements")]
+    fn display_jet_names() {
+        use crate::policy::Policy;
+        use elements::bitcoin::key::XOnlyPublicKey;
+        use elements::secp256k1_zkp;
+
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let keypair = secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let key: XOnlyPublicKey = keypair.x_only_public_key().0;
+        let program = Policy::Key(key).commit().unwrap();
+
+        let display = program.to_string();
+        assert!(display.contains("jet(bip_0340_verify)"), "{}", display);
+        let key_word = format!("word(0x{})", key);
+        assert!(display.contains(&key_word), "{}", display);
+
+        let expr = program.display_expr().to_string();
+        assert!(expr.contains("jet_bip_0340_verify"), "{}", expr);
+        assert!(expr.contains(&format!("const 0x{}", key)), "{}", expr);
+    }
 }
@@ -1,18 +1,19 @@
 // SPDX-License-Identifier: CC0-1.0
 
 use crate::analysis::NodeBounds;
-use crate::dag::{DagLike, InternalSharing, MaxSharing, PostOrderIterItem};
+use crate::bit_machine::{ExecTracker, ExecutionError};
+use crate::dag::{DagLike, InternalSharing, MaxSharing, NoSharing, PostOrderIterItem};
 use crate::jet::Jet;
 use crate::types::{self, arrow::FinalArrow};
 use crate::{encode, write_to_vec, WitnessNode};
-use crate::{Amr, BitIter, BitWriter, Cmr, Error, FirstPassImr, Imr, Value};
+use crate::{Amr, BitIter, BitMachine, BitWriter, Cmr, Error, FirstPassImr, Imr, Value};
 
 use super::{
-    Commit, CommitData, CommitNode, Construct, ConstructNode, Constructible, Converter, Inner,
-    Marker, NoDisconnect, NoWitness, Node, Witness, WitnessData,
+    Commit, CommitData, CommitNode, Construct, ConstructNode, Constructible, Converter, Hide,
+    Inner, Marker, NoDisconnect, NoWitness, Node, Witness, WitnessData,
 };
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::marker::PhantomData;
 use std::sync::Arc;
@@ -176,6 +177,11 @@ impl<J: Jet> RedeemNode<J> {
     }
 
     /// Accessor for the node's bit machine bounds
+    ///
+    /// The bounds are computed statically when the node is constructed, from the
+    /// bounds of its children, so shared sub-expressions are analyzed only once
+    /// and nothing is executed. Executing the node never uses more cells or frames
+    /// than these bounds plus the input and output of the node.
     pub fn bounds(&self) -> NodeBounds {
         self.data.bounds
     }
@@ -220,6 +226,51 @@ impl<J: Jet> RedeemNode<J> {
         self.convert::<MaxSharing<Redeem<J>>, _, _>(&mut Unfinalizer(PhantomData))
     }
 
+    /// Split a [`RedeemNode`] into its commitment-time program and its witness values.
+    ///
+    /// The witness values are listed in the order in which [`CommitNode::finalize`]
+    /// consumes them, so the two parts can be recombined using a
+    /// [`super::SimpleFinalizer`]. Note that programs with disconnect nodes cannot be
+    /// recombined this way, since the disconnected branches are dropped.
+    pub fn to_parts(&self) -> (Arc<CommitNode<J>>, Vec<Arc<Value>>) {
+        let program = self
+            .unfinalize()
+            .expect("unfinalizing a finalized program cannot fail");
+        let witness = self
+            .post_order_iter::<NoSharing>()
+            .into_witnesses()
+            .cloned()
+            .collect();
+        (program, witness)
+    }
+
+    /// Return the groups of witness slots which are bound to the same value.
+    ///
+    /// Slots are indexed in the order in which [`CommitNode::finalize`] consumes
+    /// witness values, like in [`Self::to_parts`]. Witness nodes of the same type
+    /// with the same value are collapsed by maximal sharing, so each group is
+    /// encoded as a single witness value. Only groups of at least two slots are
+    /// returned, ordered by their first slot.
+    pub fn duplicate_witnesses(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = vec![];
+        let mut group_index = HashMap::new();
+        let witness_imrs = self
+            .post_order_iter::<NoSharing>()
+            .filter(|data| matches!(data.node.inner(), Inner::Witness(_)))
+            .map(|data| data.node.imr());
+
+        for (slot, imr) in witness_imrs.enumerate() {
+            let index = *group_index.entry(imr).or_insert_with(|| {
+                groups.push(vec![]);
+                groups.len() - 1
+            });
+            groups[index].push(slot);
+        }
+
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+
     /// Convert a [`RedeemNode`] back into a [`WitnessNode`]
     /// by loosening the finalized types, witness data and disconnected branches.
     pub fn to_witness_node(&self) -> Arc<WitnessNode<J>> {
@@ -266,11 +317,160 @@ impl<J: Jet> RedeemNode<J> {
             .unwrap()
     }
 
+    /// Execute the program in the given environment and decide which branches
+    /// of the executed `case` nodes are hidden by pruning.
+    ///
+    /// Only `case` nodes with exactly one taken branch are included.
+    fn pruning_decisions(
+        &self,
+        env: &J::Environment,
+    ) -> Result<HashMap<Imr, Hide>, ExecutionError> {
+        /// Tracker which records the taken branches of every executed `case` node.
+        #[derive(Default)]
+        struct BranchTracker {
+            /// Whether the left and right branch of a `case` node was taken.
+            taken: HashMap<Imr, (bool, bool)>,
+        }
+
+        impl<J: Jet> ExecTracker<J> for BranchTracker {
+            fn track_case(&mut self, node: &RedeemNode<J>, right: bool) {
+                // Both children may have the same IMR, so only the tag bit tells them apart
+                if let Inner::Case(..) = node.inner() {
+                    let taken = self.taken.entry(node.imr()).or_default();
+                    if right {
+                        taken.1 = true;
+                    } else {
+                        taken.0 = true;
+                    }
+                }
+            }
+        }
+
+        let mut tracker = BranchTracker::default();
+        let mut mac = BitMachine::for_program(self);
+        mac.exec_with_tracker(self, env, &mut tracker)?;
+
+        Ok(tracker
+            .taken
+            .into_iter()
+            .filter_map(|(imr, taken)| match taken {
+                (true, false) => Some((imr, Hide::Right)),
+                (false, true) => Some((imr, Hide::Left)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Prune the program by hiding every branch of a `case` node which is not
+    /// taken during execution in the given environment.
+    ///
+    /// The pruned program has the same CMR as the original one.
+    ///
+    /// Which branches are taken depends on the witness data of the program, so the
+    /// pruned program is specific to it. Shared `case` nodes may be executed more
+    /// than once, on different inputs. Their branches are hidden only if they are
+    /// not taken in any of these executions.
+    ///
+    /// Consensus rejects programs with branches that are not taken,
+    /// so branches are hidden even if their CMR is longer than their encoding.
+    /// Use [`Self::pruning_savings`] to find out how much shorter the program becomes.
+    pub fn prune(&self, env: &J::Environment) -> Result<Arc<Self>, ExecutionError> {
+        struct Pruner<J> {
+            hide: HashMap<Imr, Hide>,
+            phantom: PhantomData<J>,
+        }
+
+        impl<J: Jet> Converter<Redeem<J>, Witness<J>> for Pruner<J> {
+            type Error = ();
+
+            fn convert_witness(
+                &mut self,
+                _: &PostOrderIterItem<&Node<Redeem<J>>>,
+                witness: &Arc<Value>,
+            ) -> Result<Option<Arc<Value>>, Self::Error> {
+                Ok(Some(witness.clone()))
+            }
+
+            fn convert_disconnect(
+                &mut self,
+                _: &PostOrderIterItem<&Node<Redeem<J>>>,
+                right: Option<&Arc<Node<Witness<J>>>>,
+                _: &Arc<RedeemNode<J>>,
+            ) -> Result<Option<Arc<Node<Witness<J>>>>, Self::Error> {
+                Ok(right.cloned())
+            }
+
+            fn prune_case(
+                &mut self,
+                data: &PostOrderIterItem<&Node<Redeem<J>>>,
+                _: &Arc<Node<Witness<J>>>,
+                _: &Arc<Node<Witness<J>>>,
+            ) -> Result<Hide, Self::Error> {
+                // Case nodes which were never executed are themselves part of a hidden
+                // branch, so there is no need to hide any of their children.
+                Ok(self
+                    .hide
+                    .get(&data.node.imr())
+                    .copied()
+                    .unwrap_or(Hide::Neither))
+            }
+
+            fn convert_data(
+                &mut self,
+                _: &PostOrderIterItem<&Node<Redeem<J>>>,
+                inner: Inner<
+                    &Arc<Node<Witness<J>>>,
+                    J,
+                    &Option<Arc<WitnessNode<J>>>,
+                    &Option<Arc<Value>>,
+                >,
+            ) -> Result<WitnessData<J>, Self::Error> {
+                let inner = inner
+                    .map(|node| node.cached_data())
+                    .map_witness(|maybe_value| maybe_value.clone());
+                Ok(WitnessData::from_inner(inner).expect("pruning only removes type constraints"))
+            }
+        }
+
+        let mut pruner = Pruner {
+            hide: self.pruning_decisions(env)?,
+            phantom: PhantomData,
+        };
+        let pruned = self
+            .convert::<InternalSharing, _, _>(&mut pruner)
+            .expect("pruner never fails");
+        Ok(pruned
+            .finalize()
+            .expect("pruning a finalized program cannot fail"))
+    }
+
+    /// Return the number of bits by which the encoding of the program would shrink
+    /// if it was pruned for the given environment.
+    ///
+    /// Pruning replaces every `case` branch which is not taken during execution by
+    /// its CMR. A hidden CMR takes up 256 bits, which may be more than the branch itself.
+    /// If pruning would not make the encoding shorter, then the savings are zero.
+    ///
+    /// The program is executed once, but the pruned program is never constructed.
+    pub fn pruning_savings(&self, env: &J::Environment) -> Result<usize, ExecutionError> {
+        let hide = self.pruning_decisions(env)?;
+        Ok(self
+            .encoded_bit_len()
+            .saturating_sub(encode::pruned_bit_len(self, &hide)))
+    }
+
+    /// Return the number of bits of the encoding of the program, including witness data.
+    fn encoded_bit_len(&self) -> usize {
+        self.encode(&mut BitWriter::new(io::sink()))
+            .expect("writing to a sink never fails")
+    }
+
     /// Decode a Simplicity program from bits, including the witness data.
     pub fn decode<I: Iterator<Item = u8>>(bits: &mut BitIter<I>) -> Result<Arc<Self>, Error> {
         // 0. Set up a type to help with the call to `convert` below
         struct DecodeFinalizer<'bits, J: Jet, I: Iterator<Item = u8>> {
             bits: &'bits mut BitIter<I>,
+            has_witness_block: bool,
             phantom: PhantomData<J>,
         }
 
@@ -284,6 +484,9 @@ impl<J: Jet> RedeemNode<J> {
                 _: &NoWitness,
             ) -> Result<Arc<Value>, Self::Error> {
                 let target_ty = data.node.data.arrow().target.finalize()?;
+                if !self.has_witness_block && target_ty.bit_width() > 0 {
+                    return Err(Error::Decode(crate::decode::Error::MissingWitnessBlock));
+                }
                 self.bits.read_value(&target_ty).map_err(Error::from)
             }
 
@@ -319,7 +522,8 @@ impl<J: Jet> RedeemNode<J> {
         construct.set_arrow_to_program()?;
 
         // 2. Convert to RedeemNode, reading witnesses as we go
-        let witness_len = if bits.read_bit()? {
+        let has_witness_block = bits.read_bit()?;
+        let witness_len = if has_witness_block {
             bits.read_natural(None)?
         } else {
             0
@@ -331,6 +535,7 @@ impl<J: Jet> RedeemNode<J> {
         let program: Arc<Self> =
             construct.convert::<InternalSharing, _, _>(&mut DecodeFinalizer {
                 bits,
+                has_witness_block,
                 phantom: PhantomData,
             })?;
 
@@ -380,7 +585,8 @@ mod tests {
     use std::fmt;
 
     use crate::jet::Core;
-    use crate::node::SimpleFinalizer;
+    use crate::node::{CoreConstructible, SimpleFinalizer, WitnessConstructible};
+    use crate::types::Final;
 
     fn assert_program_deserializable<J: Jet>(
         prog_bytes: &[u8],
@@ -477,6 +683,78 @@ mod tests {
         );
     }
 
+    #[test]
+    fn into_parts_roundtrip() {
+        // Same program as `encode_shared_witnesses`, but with distinct witnesses
+        // so that the order in which they are returned matters.
+        let eqwits = [0xcd, 0xdc, 0x51, 0xb6, 0xe2, 0x08, 0xc0, 0x40];
+        let mut iter = BitIter::from(&eqwits[..]);
+        let eqwits_prog = CommitNode::<Core>::decode(&mut iter).unwrap();
+
+        let witness = [Value::u32(0xDEADBEEF), Value::u32(0xCAFEBABE)];
+        let eqwits_final = eqwits_prog
+            .finalize(&mut SimpleFinalizer::new(witness.iter().cloned()))
+            .unwrap();
+
+        let (program, parts_witness) = eqwits_final.to_parts();
+        assert_eq!(program, eqwits_prog);
+        assert_eq!(parts_witness, witness);
+
+        let recombined = program
+            .finalize(&mut SimpleFinalizer::new(parts_witness.into_iter()))
+            .unwrap();
+        assert_eq!(recombined, eqwits_final);
+        assert_eq!(recombined.imr(), eqwits_final.imr());
+    }
+
+    #[test]
+    fn imr_from_witness_commitments() {
+        // Same program as `encode_shared_witnesses`, with distinct witnesses
+        let eqwits = [0xcd, 0xdc, 0x51, 0xb6, 0xe2, 0x08, 0xc0, 0x40];
+        let mut iter = BitIter::from(&eqwits[..]);
+        let eqwits_prog = CommitNode::<Core>::decode(&mut iter).unwrap();
+
+        let witness = [Value::u32(0xDEADBEEF), Value::u32(0xCAFEBABE)];
+        let eqwits_final = eqwits_prog
+            .finalize(&mut SimpleFinalizer::new(witness.iter().cloned()))
+            .unwrap();
+
+        let word = Final::two_two_n(5);
+        let wit1 = FirstPassImr::witness_commitment(&witness[0], &word);
+        let wit2 = FirstPassImr::witness_commitment(&witness[1], &word);
+        let first_pass = FirstPassImr::comp(
+            FirstPassImr::comp(
+                FirstPassImr::pair(wit1, wit2),
+                FirstPassImr::jet(Core::Eq32),
+            ),
+            FirstPassImr::jet(Core::Verify),
+        );
+        assert_eq!(
+            Imr::compute_pass2(first_pass, eqwits_final.arrow()),
+            eqwits_final.imr()
+        );
+    }
+
+    #[test]
+    fn duplicate_witnesses() {
+        // Same program as `encode_shared_witnesses`
+        let eqwits = [0xcd, 0xdc, 0x51, 0xb6, 0xe2, 0x08, 0xc0, 0x40];
+        let mut iter = BitIter::from(&eqwits[..]);
+        let eqwits_prog = CommitNode::<Core>::decode(&mut iter).unwrap();
+
+        let witness = [Value::u32(0xDEADBEEF), Value::u32(0xDEADBEEF)];
+        let eqwits_final = eqwits_prog
+            .finalize(&mut SimpleFinalizer::new(witness.iter().cloned()))
+            .unwrap();
+        assert_eq!(eqwits_final.duplicate_witnesses(), vec![vec![0, 1]]);
+
+        let witness = [Value::u32(0xDEADBEEF), Value::u32(0xCAFEBABE)];
+        let eqwits_final = eqwits_prog
+            .finalize(&mut SimpleFinalizer::new(witness.iter().cloned()))
+            .unwrap();
+        assert!(eqwits_final.duplicate_witnesses().is_empty());
+    }
+
     #[test]
     fn decode_shared_witnesses() {
         // This program is exactly the output from the `encode_shared_witnesses` test.
@@ -505,6 +783,136 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pruning_savings_case() {
+        type Node = Arc<ConstructNode<Core>>;
+        // drop (comp (const big) unit) :: 1 × 1 → 1, which encodes to more bits than its CMR
+        let big = Node::comp(
+            &Node::pair(
+                &Node::const_word(Value::u256_from_slice(&[0xab; 32])),
+                &Node::const_word(Value::u256_from_slice(&[0xcd; 32])),
+            )
+            .unwrap(),
+            &Node::unit(),
+        )
+        .unwrap();
+        let branch = Node::drop_(&big);
+        // comp (pair witness unit) case :: 1 → 1
+        let finalize = |case: Node, bit: u8| {
+            Node::comp(
+                &Node::pair(&Node::witness(NoWitness), &Node::unit()).unwrap(),
+                &case,
+            )
+            .unwrap()
+            .finalize_types()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(std::iter::once(Value::u1(bit))))
+            .unwrap()
+        };
+        let case = Node::case(&Node::unit(), &branch).unwrap();
+
+        // The big branch is hidden
+        let redeem = finalize(case.clone(), 0);
+        let pruned = finalize(Node::assertl(&Node::unit(), branch.cmr()).unwrap(), 0);
+        assert_eq!(pruned.cmr(), redeem.cmr());
+        assert_eq!(
+            redeem.pruning_savings(&()).unwrap(),
+            redeem.encoded_bit_len() - pruned.encoded_bit_len()
+        );
+
+        // The unit branch is hidden, and its CMR is longer than its encoding
+        let redeem = finalize(case, 1);
+        let pruned = finalize(Node::assertr(Node::unit().cmr(), &branch).unwrap(), 1);
+        assert!(pruned.encoded_bit_len() > redeem.encoded_bit_len());
+        assert_eq!(redeem.pruning_savings(&()).unwrap(), 0);
+    }
+
+    #[test]
+    fn prune_untaken_branches() {
+        type Node = Arc<ConstructNode<Core>>;
+        // drop (comp (const big) unit) :: 1 × 1 → 1, which encodes to more bits than its CMR
+        let big = Node::comp(
+            &Node::pair(
+                &Node::const_word(Value::u256_from_slice(&[0xab; 32])),
+                &Node::const_word(Value::u256_from_slice(&[0xcd; 32])),
+            )
+            .unwrap(),
+            &Node::unit(),
+        )
+        .unwrap();
+        let case = Node::case(&Node::unit(), &Node::drop_(&big)).unwrap();
+        // comp (pair witness unit) (case unit big) :: 1 → 1
+        let program = Node::comp(
+            &Node::pair(&Node::witness(NoWitness), &Node::unit()).unwrap(),
+            &case,
+        )
+        .unwrap()
+        .finalize_types()
+        .unwrap();
+
+        for bit in 0..2 {
+            let redeem = program
+                .finalize(&mut SimpleFinalizer::new(std::iter::once(Value::u1(bit))))
+                .unwrap();
+            let pruned = redeem.prune(&()).unwrap();
+            assert_eq!(pruned.cmr(), redeem.cmr());
+            BitMachine::for_program(&pruned).exec(&pruned, &()).unwrap();
+
+            let savings = redeem.pruning_savings(&()).unwrap();
+            if bit == 0 {
+                // The big branch is hidden
+                assert!(pruned.encoded_bit_len() < redeem.encoded_bit_len());
+                assert_eq!(savings, redeem.encoded_bit_len() - pruned.encoded_bit_len());
+            } else {
+                // The unit branch is hidden, and its CMR is longer than its encoding
+                assert!(pruned.encoded_bit_len() > redeem.encoded_bit_len());
+                assert_eq!(savings, 0);
+            }
+        }
+
+        // The shared case node takes both branches, once for each input bit
+        let select = |inj: &Node| Node::comp(&Node::pair(inj, &Node::unit()).unwrap(), &case);
+        let both = Node::pair(
+            &select(&Node::injl(&Node::unit())).unwrap(),
+            &select(&Node::injr(&Node::unit())).unwrap(),
+        )
+        .unwrap();
+        let redeem = Node::comp(&both, &Node::unit())
+            .unwrap()
+            .finalize_types()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+            .unwrap();
+        let pruned = redeem.prune(&()).unwrap();
+        assert_eq!(pruned.encode_to_vec(), redeem.encode_to_vec());
+        assert_eq!(redeem.pruning_savings(&()).unwrap(), 0);
+    }
+
+    #[test]
+    fn prune_identical_branches() {
+        type Node = Arc<ConstructNode<Core>>;
+        // comp (pair (injr unit) unit) (case unit unit) :: 1 → 1
+        let redeem = Node::comp(
+            &Node::pair(&Node::injr(&Node::unit()), &Node::unit()).unwrap(),
+            &Node::case(&Node::unit(), &Node::unit()).unwrap(),
+        )
+        .unwrap()
+        .finalize_types()
+        .unwrap()
+        .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+        .unwrap();
+        BitMachine::for_program(&redeem).exec(&redeem, &()).unwrap();
+
+        // The right branch is taken, even though both branches have the same IMR
+        let pruned = redeem.prune(&()).unwrap();
+        assert_eq!(pruned.cmr(), redeem.cmr());
+        BitMachine::for_program(&pruned).exec(&pruned, &()).unwrap();
+        match pruned.inner() {
+            Inner::Comp(_, case) => assert!(matches!(case.inner(), Inner::AssertR(..))),
+            _ => unreachable!(),
+        }
+    }
+
     #[test]
     fn witness_consumed() {
         // "main = unit", but with a witness attached. Found by fuzzer.
@@ -8,16 +8,23 @@
 //! These policies can be compiled to Simplicity and also be lifted back up from
 //! Simplicity expressions to policy.
 
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
 use std::convert::TryFrom;
 use std::sync::Arc;
 use std::{fmt, iter, mem};
 
+use elements::locktime::Height;
+
+use crate::dag::{DagLike, NoSharing};
 use crate::jet::Elements;
 use crate::node::{
-    ConstructNode, CoreConstructible, JetConstructible, NoWitness, WitnessConstructible,
+    ConstructNode, CoreConstructible, Inner, JetConstructible, NoWitness, SimpleFinalizer,
+    WitnessConstructible,
 };
-use crate::policy::serialize::{self, AssemblyConstructible};
-use crate::{Cmr, CommitNode, FailEntropy};
+use crate::policy::serialize::{self, AssemblyConstructible, JetSet, DEFAULT_PREIMAGE_BITS};
+use crate::policy::TranslatorExt;
+use crate::{Cmr, CommitNode, Cost, FailEntropy, Value};
 use crate::{SimplicityKey, ToXOnlyPubkey, Translator};
 
 /// Policy that expresses spending conditions for Simplicity.
@@ -36,63 +43,114 @@ pub enum Policy<Pk: SimplicityKey> {
     Key(Pk),
     /// Absolute timelock
     After(u32),
-    /// Relative timelock
+    /// Relative timelock in blocks
     Older(u16),
+    /// Relative timelock in units of 512 seconds
+    OlderTime(u16),
     /// Provide the preimage of the given SHA256 hash image
     Sha256(Pk::Sha256),
+    /// Provide the preimage of the given double SHA256 hash image
+    Hash256(Pk::Hash256),
     /// Satisfy both of the given sub-policies
     And {
         left: Arc<Policy<Pk>>,
         right: Arc<Policy<Pk>>,
     },
     /// Satisfy exactly one of the given sub-policies
+    ///
+    /// The weights say how likely each sub-policy is to be satisfied, relative to
+    /// the other one. Both branches of a single `or` fragment cost the same to select,
+    /// so the weights of the fragment do not change its compiled program. They matter
+    /// when many branches are nested by [`Policy::weighted_or`], which puts
+    /// likely branches higher up. The default weights are one.
     Or {
         left: Arc<Policy<Pk>>,
         right: Arc<Policy<Pk>>,
+        weights: [u32; 2],
     },
     /// Satisfy exactly `k` of the given sub-policies
-    Threshold(usize, Vec<Policy<Pk>>),
+    ///
+    /// Each sub-policy comes with a weight that says how likely it is to be satisfied,
+    /// relative to the other sub-policies. Weights are hints for analysis: the compiled
+    /// program depends only on the sub-policies and their order. The only effect of the
+    /// weights on the program is the order that [`Policy::weighted_threshold`] picks.
+    /// [`Policy::threshold`] makes all weights one.
+    Threshold(usize, Vec<(u32, Policy<Pk>)>),
     /// Satisfy the program with the given CMR
     Assembly(Cmr),
 }
 
 impl<Pk: ToXOnlyPubkey> Policy<Pk> {
     /// Serializes the policy as a Simplicity fragment, with all witness nodes unpopulated.
-    fn serialize_no_witness<N>(&self) -> Option<N>
+    ///
+    /// Hash preimages are `preimage_bits` wide.
+    fn serialize_no_witness<N>(&self, preimage_bits: usize) -> Option<N>
+    where
+        N: CoreConstructible
+            + JetConstructible<Elements>
+            + WitnessConstructible<NoWitness>
+            + AssemblyConstructible,
+    {
+        self.serialize_no_witness_with(preimage_bits, &mut |sub| {
+            sub.serialize_no_witness(preimage_bits)
+        })
+    }
+
+    /// Serializes the policy as a Simplicity fragment, with all witness nodes unpopulated,
+    /// where the fragments of the sub-policies are obtained from `serialize_sub`.
+    ///
+    /// Hash preimages are `preimage_bits` wide.
+    pub(super) fn serialize_no_witness_with<N, F>(
+        &self,
+        preimage_bits: usize,
+        serialize_sub: &mut F,
+    ) -> Option<N>
     where
         N: CoreConstructible
             + JetConstructible<Elements>
             + WitnessConstructible<NoWitness>
             + AssemblyConstructible,
+        F: FnMut(&Self) -> Option<N>,
     {
         match *self {
             Policy::Unsatisfiable(entropy) => Some(serialize::unsatisfiable(entropy)),
             Policy::Trivial => Some(serialize::trivial()),
             Policy::After(n) => Some(serialize::after(n)),
             Policy::Older(n) => Some(serialize::older(n)),
+            Policy::OlderTime(n) => Some(serialize::older_time(n)),
             Policy::Key(ref key) => Some(serialize::key(key, NoWitness)),
-            Policy::Sha256(ref hash) => Some(serialize::sha256::<Pk, _, _>(hash, NoWitness)),
+            Policy::Sha256(ref hash) => Some(serialize::sha256_with_width::<Pk, _, _>(
+                hash,
+                NoWitness,
+                preimage_bits,
+            )),
+            Policy::Hash256(ref hash) => Some(serialize::hash256_with_width::<Pk, _, _>(
+                hash,
+                NoWitness,
+                preimage_bits,
+            )),
             Policy::And {
                 ref left,
                 ref right,
             } => {
-                let left = left.serialize_no_witness()?;
-                let right = right.serialize_no_witness()?;
+                let left = serialize_sub(left)?;
+                let right = serialize_sub(right)?;
                 Some(serialize::and(&left, &right))
             }
             Policy::Or {
                 ref left,
                 ref right,
+                ..
             } => {
-                let left = left.serialize_no_witness()?;
-                let right = right.serialize_no_witness()?;
+                let left = serialize_sub(left)?;
+                let right = serialize_sub(right)?;
                 Some(serialize::or(&left, &right, NoWitness))
             }
             Policy::Threshold(k, ref subs) => {
                 let k = u32::try_from(k).expect("can have k at most 2^32 in a threshold");
                 let subs = subs
                     .iter()
-                    .map(Self::serialize_no_witness)
+                    .map(|(_, sub)| serialize_sub(sub))
                     .collect::<Option<Vec<N>>>()?;
                 let wits = iter::repeat(NoWitness)
                     .take(subs.len())
@@ -105,51 +163,234 @@ impl<Pk: ToXOnlyPubkey> Policy<Pk> {
 
     /// Return the program commitment of the policy.
     pub fn commit(&self) -> Option<Arc<CommitNode<Elements>>> {
-        let construct: Arc<ConstructNode<Elements>> = self.serialize_no_witness()?;
+        self.commit_with_preimage_bits(DEFAULT_PREIMAGE_BITS)
+    }
+
+    /// Return the program commitment of the policy,
+    /// where hash preimages are `preimage_bits` wide instead of the default 256.
+    ///
+    /// This is meant for non-standard applications. Only powers of two
+    /// between 8 and 4096 are supported, because there is a SHA256 jet for each of them.
+    ///
+    /// ## Panics
+    ///
+    /// The policy contains a hash fragment and the preimage width is unsupported.
+    pub fn commit_with_preimage_bits(
+        &self,
+        preimage_bits: usize,
+    ) -> Option<Arc<CommitNode<Elements>>> {
+        let construct: Arc<ConstructNode<Elements>> = self.serialize_no_witness(preimage_bits)?;
         let commit = construct.finalize_types().expect("policy has sound types");
         Some(commit)
     }
 
     /// Return the CMR of the policy.
+    ///
+    /// The CMR is computed from the fragments of the policy directly, without
+    /// constructing the program, and is equal to the CMR of [`Policy::commit`].
     pub fn cmr(&self) -> Cmr {
-        self.serialize_no_witness()
+        self.serialize_no_witness(DEFAULT_PREIMAGE_BITS)
             .expect("CMR is defined for asm fragment")
     }
+
+    /// Return the jets that the program commitment of the policy uses, in ascending order,
+    /// without compiling the policy.
+    ///
+    /// Returns `None` if the policy contains assembly fragments.
+    pub fn required_jets(&self) -> Option<Vec<Elements>> {
+        let jets: JetSet = self.serialize_no_witness(DEFAULT_PREIMAGE_BITS)?;
+        Some(jets.0.into_iter().collect())
+    }
+
+    /// Return the worst-case cost of executing the program commitment of the policy.
+    ///
+    /// Returns `None` if the policy contains assembly fragments.
+    pub fn cost(&self) -> Option<Cost> {
+        let commit = self.commit()?;
+        // Witness values do not affect the cost, so zeroes of the right types will do
+        let witness: Vec<_> = commit
+            .as_ref()
+            .post_order_iter::<NoSharing>()
+            .filter_map(|data| match data.node.inner() {
+                Inner::Witness(_) => Some(Value::zero(&data.node.arrow().target)),
+                _ => None,
+            })
+            .collect();
+        let redeem = commit
+            .finalize(&mut SimpleFinalizer::new(witness.into_iter()))
+            .expect("policy has no disconnect nodes");
+        Some(redeem.bounds().cost)
+    }
+}
+
+/// Compare two policies by the worst-case cost of their program commitments.
+///
+/// Returns [`Ordering::Less`] if `a` is cheaper than `b`.
+/// Policies that cannot be committed to (due to assembly fragments)
+/// are more expensive than any other policy.
+/// Ties are broken by comparing the policies themselves.
+pub fn cheaper_of<Pk: ToXOnlyPubkey>(a: &Policy<Pk>, b: &Policy<Pk>) -> Ordering {
+    match (a.cost(), b.cost()) {
+        (Some(a_cost), Some(b_cost)) => a_cost.cmp(&b_cost),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then_with(|| a.cmp(b))
 }
 
 impl<Pk: SimplicityKey> Policy<Pk> {
+    /// Construct a threshold of equally likely sub-policies.
+    pub fn threshold(k: usize, subs: Vec<Policy<Pk>>) -> Self {
+        Policy::Threshold(k, subs.into_iter().map(|sub| (1, sub)).collect())
+    }
+
+    /// Construct a threshold of sub-policies with the given probability weights.
+    ///
+    /// Sub-policies with higher weights are more likely to be satisfied, so they are
+    /// placed first in the counting circuit of the compiled program. Sub-policies of
+    /// equal weight keep their relative order. The weights are kept in the policy,
+    /// so weights of one produce the same policy as [`Policy::threshold`].
+    pub fn weighted_threshold(k: usize, mut weighted_subs: Vec<(u32, Policy<Pk>)>) -> Self {
+        weighted_subs.sort_by_key(|&(weight, _)| Reverse(weight));
+        Policy::Threshold(k, weighted_subs)
+    }
+
+    /// Construct a disjunction of sub-policies with the given probability weights.
+    ///
+    /// Every level of nested `or` fragments adds a `case` combinator and a witness bit
+    /// to the satisfaction of the sub-policies below it. The sub-policies are nested
+    /// like the leaves of a Huffman tree, so likely sub-policies are cheaper to satisfy.
+    /// Within each `or` fragment, the heavier branch comes first, and branches of equal
+    /// weight keep their relative order. Each `or` fragment is weighted by the total
+    /// weights of its two branches, saturating at `u32::MAX`.
+    ///
+    /// A single sub-policy is returned as it is. Without sub-policies,
+    /// the disjunction is unsatisfiable.
+    pub fn weighted_or(weighted_subs: Vec<(u32, Policy<Pk>)>) -> Self {
+        // Lightest first, ties broken by order of insertion
+        let mut heap = BinaryHeap::new();
+        for (index, (weight, sub)) in weighted_subs.into_iter().enumerate() {
+            heap.push(Reverse((u64::from(weight), index, sub)));
+        }
+        let mut next_index = heap.len();
+        while heap.len() > 1 {
+            let Reverse((light_weight, _, light)) = heap.pop().unwrap();
+            let Reverse((heavy_weight, _, heavy)) = heap.pop().unwrap();
+            let (left, right) = if heavy_weight > light_weight {
+                (heavy, light)
+            } else {
+                (light, heavy)
+            };
+            let saturate = |weight| u32::try_from(weight).unwrap_or(u32::MAX);
+            let weights = if heavy_weight > light_weight {
+                [saturate(heavy_weight), saturate(light_weight)]
+            } else {
+                [saturate(light_weight), saturate(heavy_weight)]
+            };
+            let or = Policy::Or {
+                left: Arc::new(left),
+                right: Arc::new(right),
+                weights,
+            };
+            heap.push(Reverse((light_weight + heavy_weight, next_index, or)));
+            next_index += 1;
+        }
+        match heap.pop() {
+            Some(Reverse((_, _, policy))) => policy,
+            None => Policy::Unsatisfiable(FailEntropy::ZERO),
+        }
+    }
+
     /// Convert a policy using one kind of public key to another
     /// type of public key
     pub fn translate<T, Q, E>(&self, translator: &mut T) -> Result<Policy<Q>, E>
     where
         T: Translator<Pk, Q, E>,
         Q: SimplicityKey,
+    {
+        /// Translator that leaves timelocks unchanged.
+        struct KeysOnly<'a, T>(&'a mut T);
+
+        impl<'a, P, Q, E, T> Translator<P, Q, E> for KeysOnly<'a, T>
+        where
+            P: SimplicityKey,
+            Q: SimplicityKey,
+            T: Translator<P, Q, E>,
+        {
+            fn pk(&mut self, pk: &P) -> Result<Q, E> {
+                self.0.pk(pk)
+            }
+
+            fn sha256(&mut self, sha256: &P::Sha256) -> Result<Q::Sha256, E> {
+                self.0.sha256(sha256)
+            }
+
+            fn hash256(&mut self, hash256: &P::Hash256) -> Result<Q::Hash256, E> {
+                self.0.hash256(hash256)
+            }
+        }
+
+        impl<'a, P, Q, E, T> TranslatorExt<P, Q, E> for KeysOnly<'a, T>
+        where
+            P: SimplicityKey,
+            Q: SimplicityKey,
+            T: Translator<P, Q, E>,
+        {
+            fn after(&mut self, n: u32) -> Result<u32, E> {
+                Ok(n)
+            }
+
+            fn older(&mut self, n: u16) -> Result<u16, E> {
+                Ok(n)
+            }
+
+            fn older_time(&mut self, n: u16) -> Result<u16, E> {
+                Ok(n)
+            }
+        }
+
+        self.translate_ext(&mut KeysOnly(translator))
+    }
+
+    /// Convert a policy using one kind of public key to another
+    /// type of public key, and adjust its timelocks
+    pub fn translate_ext<T, Q, E>(&self, translator: &mut T) -> Result<Policy<Q>, E>
+    where
+        T: TranslatorExt<Pk, Q, E>,
+        Q: SimplicityKey,
     {
         match *self {
             Policy::Unsatisfiable(entropy) => Ok(Policy::Unsatisfiable(entropy)),
             Policy::Trivial => Ok(Policy::Trivial),
             Policy::Key(ref pk) => translator.pk(pk).map(Policy::Key),
             Policy::Sha256(ref h) => translator.sha256(h).map(Policy::Sha256),
-            Policy::After(n) => Ok(Policy::After(n)),
-            Policy::Older(n) => Ok(Policy::Older(n)),
+            Policy::Hash256(ref h) => translator.hash256(h).map(Policy::Hash256),
+            Policy::After(n) => translator.after(n).map(Policy::After),
+            Policy::Older(n) => translator.older(n).map(Policy::Older),
+            Policy::OlderTime(n) => translator.older_time(n).map(Policy::OlderTime),
             Policy::Threshold(k, ref subs) => {
-                let new_subs: Result<Vec<Policy<Q>>, _> =
-                    subs.iter().map(|sub| sub.translate(translator)).collect();
+                let new_subs: Result<Vec<(u32, Policy<Q>)>, _> = subs
+                    .iter()
+                    .map(|(weight, sub)| Ok((*weight, sub.translate_ext(translator)?)))
+                    .collect();
                 new_subs.map(|ok| Policy::Threshold(k, ok))
             }
             Policy::And {
                 ref left,
                 ref right,
             } => Ok(Policy::And {
-                left: Arc::new(left.translate(translator)?),
-                right: Arc::new(right.translate(translator)?),
+                left: Arc::new(left.translate_ext(translator)?),
+                right: Arc::new(right.translate_ext(translator)?),
             }),
             Policy::Or {
                 ref left,
                 ref right,
+                weights,
             } => Ok(Policy::Or {
-                left: Arc::new(left.translate(translator)?),
-                right: Arc::new(right.translate(translator)?),
+                left: Arc::new(left.translate_ext(translator)?),
+                right: Arc::new(right.translate_ext(translator)?),
+                weights,
             }),
             Policy::Assembly(cmr) => Ok(Policy::Assembly(cmr)),
         }
@@ -175,7 +416,11 @@ impl<Pk: SimplicityKey> Policy<Pk> {
                     }
                 }
             }
-            Policy::Or { left, right } => {
+            Policy::Or {
+                left,
+                right,
+                weights,
+            } => {
                 if *left == Policy::Trivial || *right == Policy::Trivial {
                     Policy::Trivial
                 } else if let Policy::Unsatisfiable(..) = *left {
@@ -186,6 +431,7 @@ impl<Pk: SimplicityKey> Policy<Pk> {
                     Policy::Or {
                         left: Arc::new(left.as_ref().clone().normalized()),
                         right: Arc::new(right.as_ref().clone().normalized()),
+                        weights,
                     }
                 }
             }
@@ -207,27 +453,177 @@ impl<Pk: SimplicityKey> Policy<Pk> {
             Policy::And {
                 ref mut left,
                 ref mut right,
+            } => {
+                Arc::make_mut(left).sort();
+                Arc::make_mut(right).sort();
+                if right > left {
+                    mem::swap(left, right);
+                }
             }
-            | Policy::Or {
+            Policy::Or {
                 ref mut left,
                 ref mut right,
+                ref mut weights,
             } => {
-                left.as_ref().clone().sort();
-                right.as_ref().clone().sort();
+                Arc::make_mut(left).sort();
+                Arc::make_mut(right).sort();
+                // Weights stay with their sub-policies
                 if right > left {
                     mem::swap(left, right);
+                    weights.swap(0, 1);
                 }
             }
             Policy::Threshold(_, ref mut subs) => {
-                for sub in &mut *subs {
+                for (_, sub) in &mut *subs {
                     sub.sort();
                 }
-                subs.sort();
+                // Weights stay with their sub-policies
+                subs.sort_by(|(left_weight, left), (right_weight, right)| {
+                    left.cmp(right).then(left_weight.cmp(right_weight))
+                });
             }
             _ => {}
         }
     }
 
+    /// Check whether two policies are equal up to the order of their branches.
+    ///
+    /// Both policies are normalized and sorted before they are compared,
+    /// without modifying the originals. Weights are ignored, because they do
+    /// not change the compiled program of a given policy.
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        self.unweighted().normalized().sorted() == other.unweighted().normalized().sorted()
+    }
+
+    /// Return a copy of the policy where every weight is one.
+    pub(crate) fn unweighted(&self) -> Policy<Pk> {
+        let mut policy = self.clone();
+        policy.clear_weights();
+        policy
+    }
+
+    fn clear_weights(&mut self) {
+        match self {
+            Policy::And {
+                ref mut left,
+                ref mut right,
+            } => {
+                Arc::make_mut(left).clear_weights();
+                Arc::make_mut(right).clear_weights();
+            }
+            Policy::Or {
+                ref mut left,
+                ref mut right,
+                ref mut weights,
+            } => {
+                Arc::make_mut(left).clear_weights();
+                Arc::make_mut(right).clear_weights();
+                *weights = [1, 1];
+            }
+            Policy::Threshold(_, ref mut subs) => {
+                for (weight, sub) in &mut *subs {
+                    *weight = 1;
+                    sub.clear_weights();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Return a copy of the policy where every occurrence of `needle` is replaced
+    /// by `replacement`.
+    ///
+    /// Fragments are compared to `needle` after normalization. Occurrences inside
+    /// the replacement are not replaced again.
+    pub fn replace(&self, needle: &Policy<Pk>, replacement: &Policy<Pk>) -> Policy<Pk> {
+        self.replace_normalized(&needle.clone().normalized(), replacement)
+    }
+
+    fn replace_normalized(&self, needle: &Policy<Pk>, replacement: &Policy<Pk>) -> Policy<Pk> {
+        if self.clone().normalized() == *needle {
+            return replacement.clone();
+        }
+        match self {
+            Policy::And { left, right } => Policy::And {
+                left: Arc::new(left.replace_normalized(needle, replacement)),
+                right: Arc::new(right.replace_normalized(needle, replacement)),
+            },
+            Policy::Or {
+                left,
+                right,
+                weights,
+            } => Policy::Or {
+                left: Arc::new(left.replace_normalized(needle, replacement)),
+                right: Arc::new(right.replace_normalized(needle, replacement)),
+                weights: *weights,
+            },
+            Policy::Threshold(k, sub_policies) => Policy::Threshold(
+                *k,
+                sub_policies
+                    .iter()
+                    .map(|(weight, sub)| (*weight, sub.replace_normalized(needle, replacement)))
+                    .collect(),
+            ),
+            _ => self.clone(),
+        }
+    }
+
+    /// Describe the spending condition of the policy in plain English,
+    /// e.g. `signature from A AND (signature from B OR 144 blocks elapsed)`.
+    ///
+    /// Nested conjunctions and disjunctions are parenthesized unless they
+    /// repeat the operator of their parent.
+    pub fn explain(&self) -> String {
+        match self {
+            Policy::Unsatisfiable(..) => "unsatisfiable".to_owned(),
+            Policy::Trivial => "always satisfied".to_owned(),
+            Policy::Key(pk) => format!("signature from {}", pk),
+            Policy::After(n) => format!("lock time {} reached", n),
+            Policy::Older(n) => format!("{} blocks elapsed", n),
+            Policy::OlderTime(n) => format!("{} seconds elapsed", u32::from(*n) * 512),
+            Policy::Sha256(h) => format!("preimage of SHA256 hash {}", h),
+            Policy::Hash256(h) => format!("preimage of double SHA256 hash {}", h),
+            Policy::And { left, right } => format!(
+                "{} AND {}",
+                left.explain_nested(self),
+                right.explain_nested(self)
+            ),
+            Policy::Or { left, right, .. } => format!(
+                "{} OR {}",
+                left.explain_nested(self),
+                right.explain_nested(self)
+            ),
+            Policy::Threshold(k, subs) => {
+                let quantifier = if *k == subs.len() {
+                    "all".to_owned()
+                } else if *k == 1 {
+                    "any".to_owned()
+                } else {
+                    k.to_string()
+                };
+                let subs: Vec<_> = subs
+                    .iter()
+                    .map(|(_, sub)| sub.explain_nested(self))
+                    .collect();
+                format!("{} of ({})", quantifier, subs.join(", "))
+            }
+            Policy::Assembly(cmr) => format!("assembly program {}", cmr),
+        }
+    }
+
+    /// Explain a sub-policy of `parent`, parenthesizing it where needed.
+    fn explain_nested(&self, parent: &Self) -> String {
+        match (self, parent) {
+            (Policy::And { .. }, Policy::And { .. }) | (Policy::Or { .. }, Policy::Or { .. }) => {
+                self.explain()
+            }
+            (Policy::And { .. } | Policy::Or { .. }, Policy::And { .. } | Policy::Or { .. }) => {
+                format!("({})", self.explain())
+            }
+            _ => self.explain(),
+        }
+    }
+
     /// Return an iterator over the fragments of the policy.
     pub fn iter(&self) -> PolicyIter<'_, Pk> {
         PolicyIter::new(self)
@@ -240,6 +636,149 @@ impl<Pk: SimplicityKey> Policy<Pk> {
             _ => None,
         })
     }
+
+    /// Return the worst-case number of signature verifications that a satisfaction
+    /// of the policy performs.
+    ///
+    /// A threshold of `k` sub-policies counts the `k` sub-policies with the most
+    /// signature verifications, because unsatisfied sub-policies are pruned before
+    /// execution. The committed program contains the verifications of all
+    /// sub-policies, which this method does not count.
+    pub fn sigop_count(&self) -> usize {
+        match self {
+            Policy::Unsatisfiable(..)
+            | Policy::Trivial
+            | Policy::After(..)
+            | Policy::Older(..)
+            | Policy::OlderTime(..)
+            | Policy::Sha256(..)
+            | Policy::Hash256(..)
+            | Policy::Assembly(..) => 0,
+            Policy::Key(..) => 1,
+            Policy::And { left, right } => left.sigop_count() + right.sigop_count(),
+            Policy::Or { left, right, .. } => left.sigop_count().max(right.sigop_count()),
+            Policy::Threshold(k, sub_policies) => {
+                let mut counts: Vec<usize> = sub_policies
+                    .iter()
+                    .map(|(_, sub)| sub.sigop_count())
+                    .collect();
+                counts.sort_unstable_by_key(|&count| Reverse(count));
+                counts.into_iter().take(*k).sum()
+            }
+        }
+    }
+
+    /// Return the estimated probability of each branch of each disjunction
+    /// (`or` or `thresh` fragment) of the policy, in the order of [`Policy::iter`].
+    ///
+    /// The probabilities are the weights of the branches, normalized so that they
+    /// sum to one within each disjunction. Branches are equally likely if all of
+    /// their weights are zero.
+    pub fn estimated_branch_probabilities(&self) -> Vec<(&Self, Vec<f64>)> {
+        self.iter()
+            .filter_map(|fragment| {
+                let weights: Vec<u32> = match fragment {
+                    Policy::Or { weights, .. } => weights.to_vec(),
+                    Policy::Threshold(_, subs) if !subs.is_empty() => {
+                        subs.iter().map(|&(weight, _)| weight).collect()
+                    }
+                    _ => return None,
+                };
+                let total: f64 = weights.iter().copied().map(f64::from).sum();
+                let probabilities = if total > 0.0 {
+                    weights.iter().map(|&w| f64::from(w) / total).collect()
+                } else {
+                    vec![1.0 / weights.len() as f64; weights.len()]
+                };
+                Some((fragment, probabilities))
+            })
+            .collect()
+    }
+
+    /// Return a minimal set of fragments that make the policy unsatisfiable,
+    /// or `None` if some satisfier can satisfy the policy.
+    ///
+    /// A fragment can never be satisfied if it is `UNSATISFIABLE`, if it is an
+    /// absolute timelock that is not a valid block height, or if it is a threshold
+    /// with more required sub-policies than it has. A conjunction can never be
+    /// satisfied if it requires both a relative timelock in blocks and one in time,
+    /// because an input's sequence number encodes only one of them. The same holds
+    /// for thresholds that require all of their sub-policies. Absolute
+    /// timelocks that mix heights and times are covered by the first rule. The
+    /// policy remains unsatisfiable as long as none of the returned fragments change.
+    pub fn unsatisfiable_core(&self) -> Option<Vec<&Self>> {
+        match self {
+            Policy::Unsatisfiable(..) => Some(vec![self]),
+            Policy::After(n) => Height::from_consensus(*n).err().map(|_| vec![self]),
+            Policy::Trivial
+            | Policy::Key(..)
+            | Policy::Older(..)
+            | Policy::OlderTime(..)
+            | Policy::Sha256(..)
+            | Policy::Hash256(..)
+            | Policy::Assembly(..) => None,
+            Policy::And { left, right } => {
+                match (left.unsatisfiable_core(), right.unsatisfiable_core()) {
+                    (Some(left), Some(right)) if right.len() < left.len() => Some(right),
+                    (Some(core), _) | (None, Some(core)) => Some(core),
+                    (None, None) => self.mixed_relative_locks(),
+                }
+            }
+            Policy::Or { left, right, .. } => {
+                let mut core = left.unsatisfiable_core()?;
+                core.extend(right.unsatisfiable_core()?);
+                Some(core)
+            }
+            Policy::Threshold(k, sub_policies) => {
+                if *k > sub_policies.len() {
+                    return Some(vec![self]);
+                }
+                // All but k - 1 sub-policies must be unsatisfiable,
+                // so take the unsatisfiable sub-policies with the smallest cores
+                let mut cores: Vec<_> = sub_policies
+                    .iter()
+                    .filter_map(|(_, sub)| sub.unsatisfiable_core())
+                    .collect();
+                let n_required = sub_policies.len() + 1 - *k;
+                if cores.len() < n_required {
+                    return if *k == sub_policies.len() {
+                        self.mixed_relative_locks()
+                    } else {
+                        None
+                    };
+                }
+                cores.sort_by_key(Vec::len);
+                Some(cores.into_iter().take(n_required).flatten().collect())
+            }
+        }
+    }
+
+    /// Return a relative timelock in blocks and one in time that every satisfaction
+    /// of the policy must meet, if there are such timelocks.
+    fn mixed_relative_locks(&self) -> Option<Vec<&Self>> {
+        let mut locks = vec![];
+        self.required_relative_locks(&mut locks);
+        let blocks = locks.iter().find(|l| matches!(l, Policy::Older(..)))?;
+        let time = locks.iter().find(|l| matches!(l, Policy::OlderTime(..)))?;
+        Some(vec![*blocks, *time])
+    }
+
+    /// Collect the nonzero relative timelocks that every satisfaction of the policy meets.
+    fn required_relative_locks<'a>(&'a self, locks: &mut Vec<&'a Self>) {
+        match self {
+            Policy::Older(n) | Policy::OlderTime(n) if *n > 0 => locks.push(self),
+            Policy::And { left, right } => {
+                left.required_relative_locks(locks);
+                right.required_relative_locks(locks);
+            }
+            Policy::Threshold(k, sub_policies) if *k == sub_policies.len() => {
+                for (_, sub) in sub_policies {
+                    sub.required_relative_locks(locks);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 impl<Pk: SimplicityKey> fmt::Debug for Policy<Pk> {
@@ -250,13 +789,30 @@ impl<Pk: SimplicityKey> fmt::Debug for Policy<Pk> {
             Policy::Key(pk) => write!(f, "pk({})", pk),
             Policy::After(n) => write!(f, "after({})", n),
             Policy::Older(n) => write!(f, "older({})", n),
+            Policy::OlderTime(n) => write!(f, "older_time({})", n),
             Policy::Sha256(h) => write!(f, "sha256({})", h),
+            Policy::Hash256(h) => write!(f, "hash256({})", h),
             Policy::And { left, right } => write!(f, "and({},{})", left, right),
-            Policy::Or { left, right } => write!(f, "or({},{})", left, right),
+            Policy::Or {
+                left,
+                right,
+                weights: [1, 1],
+            } => write!(f, "or({},{})", left, right),
+            Policy::Or {
+                left,
+                right,
+                weights: [left_weight, right_weight],
+            } => write!(f, "or({}@{},{}@{})", left_weight, left, right_weight, right),
             Policy::Threshold(k, sub_policies) => {
                 write!(f, "thresh({}", k)?;
-                for sub in sub_policies {
-                    write!(f, ",{:?}", sub)?;
+                // Weights are only displayed if some of them are not the default
+                let weighted = sub_policies.iter().any(|&(weight, _)| weight != 1);
+                for (weight, sub) in sub_policies {
+                    if weighted {
+                        write!(f, ",{}@{:?}", weight, sub)?;
+                    } else {
+                        write!(f, ",{:?}", sub)?;
+                    }
                 }
                 f.write_str(")")
             }
@@ -294,15 +850,592 @@ impl<'a, Pk: SimplicityKey> Iterator for PolicyIter<'a, Pk> {
     fn next(&mut self) -> Option<Self::Item> {
         let top = self.stack.pop()?;
         match top {
-            Policy::And { left, right } | Policy::Or { left, right } => {
+            Policy::And { left, right } | Policy::Or { left, right, .. } => {
                 self.stack.push(right);
                 self.stack.push(left);
             }
             Policy::Threshold(_, children) => {
-                self.stack.extend(children.iter().rev());
+                self.stack.extend(children.iter().map(|(_, sub)| sub).rev());
             }
             _ => {}
         }
         Some(top)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::bitcoin::key::XOnlyPublicKey;
+    use elements::secp256k1_zkp;
+    use hashes::{sha256, Hash};
+
+    fn get_keys(n: u8) -> Vec<XOnlyPublicKey> {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        (1..=n)
+            .map(|i| {
+                let keypair = secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[i; 32])
+                    .expect("valid secret key");
+                keypair.x_only_public_key().0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cheaper_of_multi_thresh() {
+        let keys = get_keys(2);
+        // 2-of-2 multisig as a conjunction of keys
+        let multi = Policy::And {
+            left: Arc::new(Policy::Key(keys[0])),
+            right: Arc::new(Policy::Key(keys[1])),
+        };
+        // The same spending condition as a threshold, which additionally
+        // has to count the satisfied sub-policies
+        let thresh = Policy::threshold(2, vec![Policy::Key(keys[0]), Policy::Key(keys[1])]);
+
+        assert!(multi.cost().unwrap() < thresh.cost().unwrap());
+        assert_eq!(cheaper_of(&multi, &thresh), Ordering::Less);
+        assert_eq!(cheaper_of(&thresh, &multi), Ordering::Greater);
+        assert_eq!(cheaper_of(&multi, &multi), Ordering::Equal);
+    }
+
+    #[test]
+    fn sha256_preimage_width() {
+        let image = sha256::Hash::from_byte_array([0; 32]);
+        let policy = Policy::<XOnlyPublicKey>::Sha256(image);
+
+        let witness_widths = |commit: Arc<CommitNode<Elements>>| -> Vec<usize> {
+            commit
+                .as_ref()
+                .post_order_iter::<NoSharing>()
+                .filter(|data| matches!(data.node.inner(), Inner::Witness(_)))
+                .map(|data| data.node.arrow().target.bit_width())
+                .collect()
+        };
+
+        let commit = policy.commit().unwrap();
+        assert_eq!(witness_widths(commit), vec![DEFAULT_PREIMAGE_BITS]);
+        assert_eq!(
+            policy
+                .commit_with_preimage_bits(DEFAULT_PREIMAGE_BITS)
+                .unwrap()
+                .cmr(),
+            policy.cmr(),
+        );
+
+        let commit = policy.commit_with_preimage_bits(128).unwrap();
+        assert_eq!(witness_widths(commit), vec![128]);
+    }
+
+    #[test]
+    fn cmr_matches_commit() {
+        let keys = get_keys(3);
+        let image = sha256::Hash::from_byte_array([1; 32]);
+        let policies = vec![
+            Policy::Trivial,
+            Policy::Unsatisfiable(FailEntropy::ZERO),
+            Policy::Key(keys[0]),
+            Policy::After(1000),
+            Policy::Older(144),
+            Policy::Sha256(image),
+            Policy::And {
+                left: Arc::new(Policy::Key(keys[0])),
+                right: Arc::new(Policy::Older(144)),
+            },
+            Policy::Or {
+                left: Arc::new(Policy::Key(keys[1])),
+                right: Arc::new(Policy::Sha256(image)),
+                weights: [1, 1],
+            },
+            Policy::threshold(2, keys.iter().copied().map(Policy::Key).collect()),
+        ];
+
+        for policy in policies {
+            assert_eq!(policy.cmr(), policy.commit().unwrap().cmr(), "{}", policy);
+        }
+    }
+
+    #[test]
+    fn required_jets() {
+        let keys = get_keys(1);
+        let policy = Policy::And {
+            left: Arc::new(Policy::Key(keys[0])),
+            right: Arc::new(Policy::Sha256(sha256::Hash::from_byte_array([0; 32]))),
+        };
+
+        let required = policy.required_jets().unwrap();
+        assert!(required.contains(&Elements::Bip0340Verify));
+        assert!(required.contains(&Elements::Sha256Ctx8Finalize));
+
+        let commit = policy.commit().unwrap();
+        let mut compiled: Vec<_> = commit
+            .as_ref()
+            .post_order_iter::<NoSharing>()
+            .filter_map(|data| match data.node.inner() {
+                Inner::Jet(jet) => Some(*jet),
+                _ => None,
+            })
+            .collect();
+        compiled.sort();
+        compiled.dedup();
+        assert_eq!(required, compiled);
+
+        assert!(Policy::<XOnlyPublicKey>::Assembly(Cmr::unit())
+            .required_jets()
+            .is_none());
+    }
+
+    #[test]
+    fn assertion_indices() {
+        let keys = get_keys(2);
+        // Each unsatisfiable fragment compiles to a fail node
+        let policy = Policy::And {
+            left: Arc::new(Policy::Or {
+                left: Arc::new(Policy::Key(keys[0])),
+                right: Arc::new(Policy::Unsatisfiable(FailEntropy::ZERO)),
+                weights: [1, 1],
+            }),
+            right: Arc::new(Policy::Or {
+                left: Arc::new(Policy::Unsatisfiable(FailEntropy::from_byte_array([1; 64]))),
+                right: Arc::new(Policy::Key(keys[1])),
+                weights: [1, 1],
+            }),
+        };
+        let commit = policy.commit().unwrap();
+        let indices = commit.node_indices();
+        let fail0 = Cmr::fail(FailEntropy::ZERO);
+        let fail1 = Cmr::fail(FailEntropy::from_byte_array([1; 64]));
+
+        let mut expected = [indices[&fail0].clone(), indices[&fail1].clone()].concat();
+        expected.sort_unstable();
+        assert_eq!(commit.assertion_indices(), expected);
+        assert_eq!(expected.len(), 2);
+
+        let trivial = Policy::<XOnlyPublicKey>::Trivial.commit().unwrap();
+        assert!(trivial.assertion_indices().is_empty());
+    }
+
+    #[test]
+    fn weighted_threshold() {
+        let keys = get_keys(3);
+        let subs: Vec<_> = keys.iter().copied().map(Policy::Key).collect();
+        let unweighted = Policy::threshold(2, subs.clone());
+
+        let uniform = Policy::weighted_threshold(2, subs.iter().cloned().map(|p| (1, p)).collect());
+        assert_eq!(uniform, unweighted);
+
+        let weights = [1, 10, 5];
+        let weighted = Policy::weighted_threshold(
+            2,
+            weights.iter().copied().zip(subs.iter().cloned()).collect(),
+        );
+        assert_eq!(
+            weighted,
+            Policy::Threshold(
+                2,
+                vec![
+                    (10, subs[1].clone()),
+                    (5, subs[2].clone()),
+                    (1, subs[0].clone()),
+                ],
+            ),
+        );
+        // The branch ordering is visible in the compiled program
+        assert_ne!(weighted.cmr(), unweighted.cmr());
+
+        // The weights are displayed and stay with their sub-policies when sorting
+        assert_eq!(
+            weighted
+                .to_string()
+                .parse::<Policy<XOnlyPublicKey>>()
+                .unwrap(),
+            weighted
+        );
+        let sorted = match weighted.sorted() {
+            Policy::Threshold(_, subs) => subs,
+            _ => unreachable!("sorting keeps the threshold"),
+        };
+        for (weight, sub) in sorted {
+            let index = subs.iter().position(|s| *s == sub).unwrap();
+            assert_eq!(weight, weights[index]);
+        }
+    }
+
+    #[test]
+    fn weighted_or() {
+        let keys = get_keys(3);
+        let subs: Vec<_> = keys.iter().copied().map(Policy::Key).collect();
+        let or = |left: &Policy<_>, right: &Policy<_>, weights| Policy::Or {
+            left: Arc::new(left.clone()),
+            right: Arc::new(right.clone()),
+            weights,
+        };
+
+        let uniform = Policy::weighted_or(vec![(1, subs[0].clone()), (1, subs[1].clone())]);
+        assert_eq!(uniform, or(&subs[0], &subs[1], [1, 1]));
+        let biased = Policy::weighted_or(vec![(1, subs[0].clone()), (99, subs[1].clone())]);
+        assert_eq!(biased, or(&subs[1], &subs[0], [99, 1]));
+
+        // The heavy sub-policy is nested less deeply than the light ones
+        let weighted = Policy::weighted_or(
+            [1, 1, 10]
+                .iter()
+                .copied()
+                .zip(subs.iter().cloned())
+                .collect(),
+        );
+        let light = or(&subs[0], &subs[1], [1, 1]);
+        assert_eq!(weighted, or(&subs[2], &light, [10, 2]));
+        // The weights stay with their sub-policies when sorting
+        match weighted.clone().sorted() {
+            Policy::Or { left, weights, .. } if *left == subs[2] => assert_eq!(weights, [10, 2]),
+            Policy::Or { right, weights, .. } if *right == subs[2] => assert_eq!(weights, [2, 10]),
+            _ => unreachable!("sorting keeps the disjunction"),
+        }
+
+        let saturated = Policy::weighted_or(vec![
+            (u32::MAX, subs[0].clone()),
+            (u32::MAX, subs[1].clone()),
+            (1, subs[2].clone()),
+        ]);
+        match saturated {
+            Policy::Or { weights, .. } => assert_eq!(weights, [u32::MAX, u32::MAX]),
+            _ => unreachable!("three sub-policies make a disjunction"),
+        }
+
+        assert_eq!(Policy::weighted_or(vec![(5, subs[0].clone())]), subs[0]);
+        assert_eq!(
+            Policy::<XOnlyPublicKey>::weighted_or(vec![]),
+            Policy::Unsatisfiable(FailEntropy::ZERO)
+        );
+    }
+
+    #[test]
+    fn structurally_eq() {
+        let keys = get_keys(3);
+        let or = |left, right| Policy::Or {
+            left: Arc::new(left),
+            right: Arc::new(right),
+            weights: [1, 1],
+        };
+        let a = Policy::Key(keys[0]);
+        let b = Policy::Key(keys[1]);
+        let c = Policy::Key(keys[2]);
+
+        let ab = or(a.clone(), b.clone());
+        let ba = or(b.clone(), a.clone());
+        assert_ne!(ab, ba);
+        assert!(ab.structurally_eq(&ba));
+        assert!(!ab.structurally_eq(&or(a.clone(), c.clone())));
+
+        // Nested branches are sorted, too
+        let ab_c = or(ab.clone(), c.clone());
+        let c_ba = or(c.clone(), ba.clone());
+        assert!(ab_c.structurally_eq(&c_ba));
+
+        // Normalization removes trivial branches
+        let a_trivial = Policy::And {
+            left: Arc::new(a.clone()),
+            right: Arc::new(Policy::Trivial),
+        };
+        assert!(a_trivial.structurally_eq(&a));
+
+        // Weights are ignored
+        let weighted = Policy::Or {
+            left: Arc::new(a.clone()),
+            right: Arc::new(b.clone()),
+            weights: [9, 1],
+        };
+        assert_ne!(weighted, ab);
+        assert!(weighted.structurally_eq(&ab));
+        assert!(weighted.structurally_eq(&ba));
+        let weighted_thresh = Policy::weighted_threshold(1, vec![(1, a.clone()), (5, b.clone())]);
+        assert!(weighted_thresh.structurally_eq(&Policy::threshold(1, vec![b, a])));
+    }
+
+    #[test]
+    fn explain_decaying_multisig() {
+        let key = |name: &str| Policy::Key(name.to_owned());
+        // 3-of-3 multisig that decays to 2-of-3 after a day
+        let policy = Policy::<String>::Or {
+            left: Arc::new(Policy::threshold(3, vec![key("A"), key("B"), key("C")])),
+            right: Arc::new(Policy::And {
+                left: Arc::new(Policy::threshold(2, vec![key("A"), key("B"), key("C")])),
+                right: Arc::new(Policy::Older(144)),
+            }),
+            weights: [1, 1],
+        };
+
+        assert_eq!(
+            policy.explain(),
+            "all of (signature from A, signature from B, signature from C) \
+             OR (2 of (signature from A, signature from B, signature from C) \
+             AND 144 blocks elapsed)",
+        );
+
+        let nested = Policy::<String>::And {
+            left: Arc::new(key("A")),
+            right: Arc::new(Policy::And {
+                left: Arc::new(key("B")),
+                right: Arc::new(Policy::threshold(1, vec![key("C"), Policy::Older(144)])),
+            }),
+        };
+        assert_eq!(
+            nested.explain(),
+            "signature from A AND signature from B \
+             AND any of (signature from C, 144 blocks elapsed)",
+        );
+    }
+
+    #[test]
+    fn translate_ext_sha256_and_timelocks() {
+        struct Retarget;
+
+        impl Translator<String, String, ()> for Retarget {
+            fn pk(&mut self, pk: &String) -> Result<String, ()> {
+                Ok(pk.clone())
+            }
+
+            fn sha256(&mut self, sha256: &String) -> Result<String, ()> {
+                Ok(format!("new_{}", sha256))
+            }
+
+            fn hash256(&mut self, hash256: &String) -> Result<String, ()> {
+                Ok(hash256.clone())
+            }
+        }
+
+        impl TranslatorExt<String, String, ()> for Retarget {
+            fn after(&mut self, n: u32) -> Result<u32, ()> {
+                n.checked_mul(2).ok_or(())
+            }
+
+            fn older(&mut self, n: u16) -> Result<u16, ()> {
+                Ok(n)
+            }
+
+            fn older_time(&mut self, n: u16) -> Result<u16, ()> {
+                Ok(n)
+            }
+        }
+
+        let policy = Policy::<String>::And {
+            left: Arc::new(Policy::Sha256("H".to_owned())),
+            right: Arc::new(Policy::Or {
+                left: Arc::new(Policy::Key("A".to_owned())),
+                right: Arc::new(Policy::After(1000)),
+                weights: [1, 1],
+            }),
+        };
+        let translated = policy.translate_ext(&mut Retarget).unwrap();
+        assert_eq!(
+            translated.to_string(),
+            "and(sha256(new_H),or(pk(A),after(2000)))"
+        );
+        // Key-only translation leaves timelocks alone
+        let translated = policy.translate(&mut Retarget).unwrap();
+        assert_eq!(
+            translated.to_string(),
+            "and(sha256(new_H),or(pk(A),after(1000)))"
+        );
+
+        let overflow = Policy::<String>::After(u32::MAX);
+        assert!(overflow.translate_ext(&mut Retarget).is_err());
+    }
+
+    #[test]
+    fn unsatisfiable_core_conflicting_and() {
+        let key = |name: &str| Policy::<String>::Key(name.to_owned());
+        // Absolute timelocks must be block heights
+        let time = Policy::<String>::After(600_000_000);
+        let too_few = Policy::threshold(3, vec![key("B"), key("C")]);
+        let policy = Policy::Or {
+            left: Arc::new(Policy::And {
+                left: Arc::new(key("A")),
+                right: Arc::new(time.clone()),
+            }),
+            right: Arc::new(too_few.clone()),
+            weights: [1, 1],
+        };
+        assert_eq!(policy.unsatisfiable_core(), Some(vec![&time, &too_few]));
+
+        let policy = Policy::And {
+            left: Arc::new(time.clone()),
+            right: Arc::new(Policy::threshold(1, vec![key("B"), time.clone()])),
+        };
+        assert_eq!(policy.unsatisfiable_core(), Some(vec![&time]));
+        assert_eq!(
+            Policy::threshold(1, vec![key("B"), time]).unsatisfiable_core(),
+            None
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_core_timelock_mix() {
+        let key = |name: &str| Policy::<String>::Key(name.to_owned());
+        let blocks = Policy::<String>::Older(144);
+        let time = Policy::<String>::OlderTime(10);
+        let policy = Policy::And {
+            left: Arc::new(blocks.clone()),
+            right: Arc::new(Policy::threshold(2, vec![key("A"), time.clone()])),
+        };
+        assert_eq!(policy.unsatisfiable_core(), Some(vec![&blocks, &time]));
+        // Thresholds that require all sub-policies are conjunctions
+        let policy = Policy::<String>::threshold(2, vec![Policy::Older(10), Policy::OlderTime(10)]);
+        assert_eq!(
+            policy.unsatisfiable_core(),
+            Some(vec![&Policy::Older(10), &Policy::OlderTime(10)])
+        );
+        let policy = Policy::<String>::threshold(1, vec![Policy::Older(10), Policy::OlderTime(10)]);
+        assert_eq!(policy.unsatisfiable_core(), None);
+
+        // Either lock alone is fine, and so is a disjunction of both
+        let policy = Policy::And {
+            left: Arc::new(blocks.clone()),
+            right: Arc::new(Policy::Or {
+                left: Arc::new(key("A")),
+                right: Arc::new(time.clone()),
+                weights: [1, 1],
+            }),
+        };
+        assert_eq!(policy.unsatisfiable_core(), None);
+        // A zero lock always holds
+        let policy = Policy::And {
+            left: Arc::new(blocks.clone()),
+            right: Arc::new(Policy::OlderTime(0)),
+        };
+        assert_eq!(policy.unsatisfiable_core(), None);
+
+        // Absolute time locks are unsatisfiable on their own
+        let height = Policy::<String>::After(1000);
+        let time = Policy::<String>::After(500_000_000);
+        let policy = Policy::And {
+            left: Arc::new(height),
+            right: Arc::new(time.clone()),
+        };
+        assert_eq!(policy.unsatisfiable_core(), Some(vec![&time]));
+    }
+
+    #[test]
+    fn sigop_count_nested_multisig() {
+        let key = |name: &str| Policy::Key(name.to_owned());
+        // 2-of-3 where one of the sub-policies is itself a 2-of-3 multisig
+        let inner = Policy::<String>::threshold(2, vec![key("C"), key("D"), key("E")]);
+        let policy = Policy::<String>::threshold(2, vec![key("A"), inner, Policy::Older(144)]);
+        assert_eq!(policy.sigop_count(), 3);
+
+        let policy = Policy::<String>::Or {
+            left: Arc::new(key("A")),
+            right: Arc::new(Policy::And {
+                left: Arc::new(policy),
+                right: Arc::new(key("B")),
+            }),
+            weights: [1, 1],
+        };
+        assert_eq!(policy.sigop_count(), 4);
+    }
+
+    #[test]
+    fn estimated_branch_probabilities() {
+        let key = |name: &str| Policy::Key(name.to_owned());
+        let weighted = Policy::<String>::weighted_threshold(
+            1,
+            vec![(1, key("B")), (9, key("C")), (5, key("D"))],
+        );
+        let policy = Policy::<String>::Or {
+            left: Arc::new(key("A")),
+            right: Arc::new(Policy::And {
+                left: Arc::new(weighted.clone()),
+                right: Arc::new(Policy::Older(144)),
+            }),
+            weights: [1, 1],
+        };
+
+        let probabilities = policy.estimated_branch_probabilities();
+        assert_eq!(probabilities.len(), 2);
+        assert_eq!(probabilities[0], (&policy, vec![0.5, 0.5]));
+        // The weighted threshold puts its heaviest sub-policies first
+        assert_eq!(
+            probabilities[1],
+            (&weighted, vec![0.6, 1.0 / 3.0, 1.0 / 15.0])
+        );
+        for (_, branches) in probabilities {
+            assert!((branches.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+        assert!(key("A").estimated_branch_probabilities().is_empty());
+
+        let weighted_or = Policy::<String>::Or {
+            left: Arc::new(key("A")),
+            right: Arc::new(key("B")),
+            weights: [3, 1],
+        };
+        let probabilities = weighted_or.estimated_branch_probabilities();
+        assert_eq!(probabilities, vec![(&weighted_or, vec![0.75, 0.25])]);
+        let never = Policy::<String>::Or {
+            left: Arc::new(key("A")),
+            right: Arc::new(key("B")),
+            weights: [0, 0],
+        };
+        assert_eq!(never.estimated_branch_probabilities()[0].1, vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn replace_key() {
+        let key = |name: &str| Policy::<String>::Key(name.to_owned());
+        let policy = Policy::Or {
+            left: Arc::new(Policy::And {
+                left: Arc::new(key("A")),
+                right: Arc::new(key("B")),
+            }),
+            right: Arc::new(Policy::threshold(
+                2,
+                vec![key("A"), key("C"), Policy::Older(144)],
+            )),
+            weights: [1, 1],
+        };
+        let rotated = Policy::Or {
+            left: Arc::new(key("A1")),
+            right: Arc::new(key("A2")),
+            weights: [1, 1],
+        };
+
+        let expected = Policy::Or {
+            left: Arc::new(Policy::And {
+                left: Arc::new(rotated.clone()),
+                right: Arc::new(key("B")),
+            }),
+            right: Arc::new(Policy::threshold(
+                2,
+                vec![rotated.clone(), key("C"), Policy::Older(144)],
+            )),
+            weights: [1, 1],
+        };
+        let replaced = policy.replace(&key("A"), &rotated);
+        assert_eq!(replaced, expected);
+        assert!(!replaced.iter().any(|fragment| *fragment == key("A")));
+
+        // Needles are compared after normalization
+        let needle = Policy::And {
+            left: Arc::new(Policy::Trivial),
+            right: Arc::new(key("B")),
+        };
+        assert_eq!(
+            policy.replace(&needle, &key("D")),
+            policy.replace(&key("B"), &key("D"))
+        );
+    }
+
+    #[test]
+    fn cheaper_of_ties() {
+        let keys = get_keys(2);
+        let a = Policy::Key(keys[0]);
+        let b = Policy::Key(keys[1]);
+        assert_eq!(a.cost(), b.cost());
+        assert_eq!(cheaper_of(&a, &b), a.cmp(&b));
+        assert_eq!(cheaper_of(&b, &a), b.cmp(&a));
+
+        let asm = Policy::Assembly(Cmr::unit());
+        assert_eq!(asm.cost(), None);
+        assert_eq!(cheaper_of(&a, &asm), Ordering::Less);
+    }
+}
@@ -2,6 +2,8 @@
 
 use std::fmt;
 
+use crate::Cmr;
+
 pub enum Error {
     InvalidSequence,
     PublicKeyHash,
@@ -10,6 +12,8 @@ pub enum Error {
     Multisig,
     Extensions,
     CouldNotSatisfy,
+    /// A sub-program with the given CMR is not a fragment of a compiled policy
+    UnrecognizedFragment(Cmr),
 }
 
 impl fmt::Debug for Error {
@@ -22,6 +26,9 @@ impl fmt::Debug for Error {
             Error::Multisig => writeln!(f, "Multisig is not supported"),
             Error::Extensions => writeln!(f, "Extensions are not supported"),
             Error::CouldNotSatisfy => writeln!(f, "Could not satisfy the given policy"),
+            Error::UnrecognizedFragment(cmr) => {
+                writeln!(f, "Sub-program {} is not a policy fragment", cmr)
+            }
         }
     }
 }
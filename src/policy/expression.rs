@@ -0,0 +1,379 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Expression Trees
+//!
+//! Policies can be converted to and from the expression trees of miniscript,
+//! so tools that operate on expression trees also work on Simplicity policies.
+//! The expression syntax is also the string form of policies for parsing and serde.
+
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bitcoin_miniscript::expression::{self, FromTree, Tree};
+use hex::FromHex;
+
+use super::{Policy, SimplicityKey};
+use crate::{Cmr, FailEntropy};
+
+impl<Pk: SimplicityKey> Policy<Pk> {
+    /// Return the expression tree of the policy.
+    ///
+    /// The policy is written to `buffer` in expression syntax, and the names of the
+    /// tree point into the buffer. This is the inverse of [`FromTree::from_tree`].
+    ///
+    /// Returns an error if a key or hash is displayed with characters that are
+    /// not allowed in expressions.
+    pub fn to_expression_tree<'a>(
+        &self,
+        buffer: &'a mut String,
+    ) -> Result<Tree<'a>, bitcoin_miniscript::Error> {
+        buffer.clear();
+        self.write_expression(buffer)
+            .expect("writing to a string never fails");
+        Tree::from_str(buffer)
+    }
+
+    /// Write the policy in expression syntax.
+    ///
+    /// Unlike [`fmt::Display`], this includes the entropy of unsatisfiable fragments.
+    fn write_expression<W: Write>(&self, w: &mut W) -> fmt::Result {
+        match self {
+            Policy::Unsatisfiable(entropy) => write!(w, "UNSATISFIABLE({})", entropy),
+            Policy::Trivial => w.write_str("TRIVIAL"),
+            Policy::Key(pk) => write!(w, "pk({})", pk),
+            Policy::After(n) => write!(w, "after({})", n),
+            Policy::Older(n) => write!(w, "older({})", n),
+            Policy::OlderTime(n) => write!(w, "older_time({})", n),
+            Policy::Sha256(h) => write!(w, "sha256({})", h),
+            Policy::Hash256(h) => write!(w, "hash256({})", h),
+            Policy::And { left, right } => {
+                w.write_str("and(")?;
+                left.write_expression(w)?;
+                w.write_str(",")?;
+                right.write_expression(w)?;
+                w.write_str(")")
+            }
+            Policy::Or {
+                left,
+                right,
+                weights,
+            } => {
+                w.write_str("or(")?;
+                if *weights != [1, 1] {
+                    write!(w, "{}@", weights[0])?;
+                }
+                left.write_expression(w)?;
+                w.write_str(",")?;
+                if *weights != [1, 1] {
+                    write!(w, "{}@", weights[1])?;
+                }
+                right.write_expression(w)?;
+                w.write_str(")")
+            }
+            Policy::Threshold(k, subs) => {
+                write!(w, "thresh({}", k)?;
+                let weighted = subs.iter().any(|&(weight, _)| weight != 1);
+                for (weight, sub) in subs {
+                    w.write_str(",")?;
+                    if weighted {
+                        write!(w, "{}@", weight)?;
+                    }
+                    sub.write_expression(w)?;
+                }
+                w.write_str(")")
+            }
+            Policy::Assembly(cmr) => write!(w, "asm({})", cmr),
+        }
+    }
+}
+
+impl<Pk> FromTree for Policy<Pk>
+where
+    Pk: SimplicityKey + FromStr,
+    <Pk as FromStr>::Err: ToString,
+    Pk::Sha256: FromStr,
+    <Pk::Sha256 as FromStr>::Err: ToString,
+    Pk::Hash256: FromStr,
+    <Pk::Hash256 as FromStr>::Err: ToString,
+{
+    fn from_tree(top: &Tree) -> Result<Self, bitcoin_miniscript::Error> {
+        Self::from_tree_named(top, top.name)
+    }
+}
+
+impl<Pk> Policy<Pk>
+where
+    Pk: SimplicityKey + FromStr,
+    <Pk as FromStr>::Err: ToString,
+    Pk::Sha256: FromStr,
+    <Pk::Sha256 as FromStr>::Err: ToString,
+    Pk::Hash256: FromStr,
+    <Pk::Hash256 as FromStr>::Err: ToString,
+{
+    /// Parse a sub-policy of an `or` or `thresh` fragment, which may be prefixed by
+    /// a probability weight, as in `9@pk(A)`. The default weight is one.
+    fn from_tree_weighted(top: &Tree) -> Result<(u32, Self), bitcoin_miniscript::Error> {
+        match top.name.split_once('@') {
+            Some((weight, name)) => {
+                let weight = expression::parse_num(weight)?;
+                Ok((weight, Self::from_tree_named(top, name)?))
+            }
+            None => Ok((1, Self::from_tree(top)?)),
+        }
+    }
+
+    /// Parse the expression tree as if its top-level name was `name`.
+    fn from_tree_named(top: &Tree, name: &str) -> Result<Self, bitcoin_miniscript::Error> {
+        let unexpected = || bitcoin_miniscript::Error::Unexpected(top.name.to_owned());
+        let timelock = |top: &Tree| {
+            let n = expression::terminal(&top.args[0], expression::parse_num)?;
+            u16::try_from(n).map_err(|_| unexpected())
+        };
+
+        match (name, top.args.len()) {
+            ("UNSATISFIABLE", 1) => expression::terminal(&top.args[0], |s| {
+                <[u8; 64]>::from_hex(s)
+                    .map(|bytes| Policy::Unsatisfiable(FailEntropy::from_byte_array(bytes)))
+            }),
+            // As displayed, without entropy
+            ("UNSATISFIABLE", 0) => Ok(Policy::Unsatisfiable(FailEntropy::ZERO)),
+            ("TRIVIAL", 0) => Ok(Policy::Trivial),
+            ("pk", 1) => expression::terminal(&top.args[0], |s| Pk::from_str(s).map(Policy::Key)),
+            ("after", 1) => {
+                expression::terminal(&top.args[0], expression::parse_num).map(Policy::After)
+            }
+            ("older", 1) => timelock(top).map(Policy::Older),
+            ("older_time", 1) => timelock(top).map(Policy::OlderTime),
+            ("sha256", 1) => expression::terminal(&top.args[0], |s| {
+                Pk::Sha256::from_str(s).map(Policy::Sha256)
+            }),
+            ("hash256", 1) => expression::terminal(&top.args[0], |s| {
+                Pk::Hash256::from_str(s).map(Policy::Hash256)
+            }),
+            ("and", 2) => expression::binary(top, |left, right| Policy::And {
+                left: Arc::new(left),
+                right: Arc::new(right),
+            }),
+            ("or", 2) => {
+                let (left_weight, left) = Self::from_tree_weighted(&top.args[0])?;
+                let (right_weight, right) = Self::from_tree_weighted(&top.args[1])?;
+                Ok(Policy::Or {
+                    left: Arc::new(left),
+                    right: Arc::new(right),
+                    weights: [left_weight, right_weight],
+                })
+            }
+            // More than two branches are nested by weight, see `Policy::weighted_or`
+            ("or", n) if n > 2 => {
+                let weighted_subs = top
+                    .args
+                    .iter()
+                    .map(Self::from_tree_weighted)
+                    .collect::<Result<_, _>>()?;
+                Ok(Policy::weighted_or(weighted_subs))
+            }
+            ("thresh", n) if n > 0 => {
+                let k = expression::terminal(&top.args[0], expression::parse_num)?;
+                let subs = top.args[1..]
+                    .iter()
+                    .map(Policy::from_tree_weighted)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Policy::Threshold(k as usize, subs))
+            }
+            ("asm", 1) => {
+                expression::terminal(&top.args[0], |s| Cmr::from_str(s).map(Policy::Assembly))
+            }
+            _ => Err(unexpected()),
+        }
+    }
+}
+
+impl<Pk> FromStr for Policy<Pk>
+where
+    Pk: SimplicityKey + FromStr,
+    <Pk as FromStr>::Err: ToString,
+    Pk::Sha256: FromStr,
+    <Pk::Sha256 as FromStr>::Err: ToString,
+    Pk::Hash256: FromStr,
+    <Pk::Hash256 as FromStr>::Err: ToString,
+{
+    type Err = bitcoin_miniscript::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tree = Tree::from_str(s)?;
+        Self::from_tree(&tree)
+    }
+}
+
+/// Policies are serialized in expression syntax, which includes the entropy
+/// of unsatisfiable fragments, in human-readable and compact formats alike.
+#[cfg(feature = "serde")]
+impl<Pk: SimplicityKey> serde::Serialize for Policy<Pk> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = String::new();
+        self.write_expression(&mut s)
+            .expect("writing to a string never fails");
+        serializer.serialize_str(&s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, Pk> serde::Deserialize<'de> for Policy<Pk>
+where
+    Pk: SimplicityKey + FromStr,
+    <Pk as FromStr>::Err: ToString,
+    Pk::Sha256: FromStr,
+    <Pk::Sha256 as FromStr>::Err: ToString,
+    Pk::Hash256: FromStr,
+    <Pk::Hash256 as FromStr>::Err: ToString,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Policy::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin_miniscript::hash256;
+    use elements::bitcoin::key::XOnlyPublicKey;
+    use elements::secp256k1_zkp;
+    use hashes::{sha256, Hash};
+
+    #[test]
+    fn expression_tree_roundtrip() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let keys: Vec<XOnlyPublicKey> = (1..=3)
+            .map(|i| {
+                secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[i; 32])
+                    .unwrap()
+                    .x_only_public_key()
+                    .0
+            })
+            .collect();
+        let policy = Policy::Or {
+            left: Arc::new(Policy::threshold(
+                2,
+                vec![
+                    Policy::Key(keys[0]),
+                    Policy::Key(keys[1]),
+                    Policy::Sha256(sha256::Hash::hash(b"preimage")),
+                    Policy::Hash256(hash256::Hash::hash(b"preimage")),
+                ],
+            )),
+            right: Arc::new(Policy::And {
+                left: Arc::new(Policy::Key(keys[2])),
+                right: Arc::new(Policy::threshold(
+                    1,
+                    vec![
+                        Policy::After(500_000),
+                        Policy::Older(144),
+                        Policy::OlderTime(10),
+                        Policy::Unsatisfiable(FailEntropy::from_byte_array([7; 64])),
+                        Policy::Trivial,
+                        Policy::Assembly(Cmr::unit()),
+                    ],
+                )),
+            }),
+            weights: [1, 1],
+        };
+
+        let mut buffer = String::new();
+        let tree = policy.to_expression_tree(&mut buffer).unwrap();
+        assert_eq!(tree.name, "or");
+        assert_eq!(tree.args.len(), 2);
+        assert_eq!(tree.args[0].name, "thresh");
+        assert_eq!(Policy::<XOnlyPublicKey>::from_tree(&tree).unwrap(), policy);
+
+        let tree = Tree::from_str("older(65536)").unwrap();
+        assert!(Policy::<XOnlyPublicKey>::from_tree(&tree).is_err());
+    }
+
+    #[test]
+    fn parse_display() {
+        let policy = Policy::<XOnlyPublicKey>::threshold(
+            1,
+            vec![
+                Policy::Unsatisfiable(FailEntropy::ZERO),
+                Policy::And {
+                    left: Arc::new(Policy::Trivial),
+                    right: Arc::new(Policy::Older(144)),
+                },
+            ],
+        );
+        assert_eq!(policy.to_string().parse::<Policy<_>>().unwrap(), policy);
+        assert!("and(TRIVIAL)".parse::<Policy<XOnlyPublicKey>>().is_err());
+    }
+
+    #[test]
+    fn parse_weighted_or() {
+        let parse = |s: &str| s.parse::<Policy<XOnlyPublicKey>>();
+        let older = |n| Arc::new(Policy::Older(n));
+        let or = |weights| Policy::Or {
+            left: older(1),
+            right: older(2),
+            weights,
+        };
+
+        assert_eq!(parse("or(older(1),older(2))").unwrap(), or([1, 1]));
+        assert_eq!(parse("or(9@older(1),1@older(2))").unwrap(), or([9, 1]));
+        assert_eq!(parse("or(older(1),3@older(2))").unwrap(), or([1, 3]));
+        assert_eq!(or([9, 1]).to_string(), "or(9@older(1),1@older(2))");
+        assert_eq!(parse(&or([1, 9]).to_string()).unwrap(), or([1, 9]));
+
+        // More than two branches are nested by weight, which brings the heavy branch
+        // closer to the root
+        fn depth(policy: &Policy<XOnlyPublicKey>, leaf: &Policy<XOnlyPublicKey>) -> Option<usize> {
+            match policy {
+                _ if policy == leaf => Some(0),
+                Policy::Or { left, right, .. } => depth(left, leaf)
+                    .or_else(|| depth(right, leaf))
+                    .map(|depth| depth + 1),
+                _ => None,
+            }
+        }
+        let uniform = parse("or(older(3),older(1),older(2))").unwrap();
+        let weighted = parse("or(8@older(3),1@older(1),1@older(2))").unwrap();
+        assert_eq!(depth(&uniform, &Policy::Older(3)), Some(2));
+        assert_eq!(depth(&weighted, &Policy::Older(3)), Some(1));
+        assert_eq!(parse(&weighted.to_string()).unwrap(), weighted);
+
+        assert!(parse("9@older(1)").is_err());
+        assert!(parse("and(9@older(1),older(2))").is_err());
+        assert!(parse("or(1@2@older(1),older(2))").is_err());
+        assert!(parse("or(older(1))").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        let policy = Policy::<XOnlyPublicKey>::Or {
+            left: Arc::new(Policy::threshold(
+                2,
+                vec![
+                    Policy::Trivial,
+                    Policy::Unsatisfiable(FailEntropy::from_byte_array([7; 64])),
+                    Policy::After(100),
+                ],
+            )),
+            right: Arc::new(Policy::And {
+                left: Arc::new(Policy::Sha256(sha256::Hash::hash(b"preimage"))),
+                right: Arc::new(Policy::Or {
+                    left: Arc::new(Policy::Trivial),
+                    right: Arc::new(Policy::Unsatisfiable(FailEntropy::ZERO)),
+                    weights: [1, 1],
+                }),
+            }),
+            weights: [1, 1],
+        };
+
+        let json = serde_json::to_string(&policy).unwrap();
+        assert!(json.starts_with("\"or(thresh(2,TRIVIAL,UNSATISFIABLE(0707"));
+        let deserialized: Policy<XOnlyPublicKey> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, policy);
+        assert!(serde_json::from_str::<Policy<XOnlyPublicKey>>("\"thresh\"").is_err());
+    }
+}
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: CC0-1.0
 
-use bitcoin_miniscript::{MiniscriptKey, ToPublicKey};
+use bitcoin_miniscript::{hash256, MiniscriptKey, ToPublicKey};
 use elements::bitcoin::key::XOnlyPublicKey;
 use hashes::sha256;
 use std::fmt::{Debug, Display};
@@ -9,10 +9,14 @@ use std::fmt::{Debug, Display};
 pub trait SimplicityKey: Clone + Eq + Ord + Debug + Display + std::hash::Hash {
     /// SHA 256 hash associated with this key, used in the sha256 fragment.
     type Sha256: Clone + Eq + Ord + Display + Debug + std::hash::Hash;
+
+    /// Double SHA 256 hash associated with this key, used in the hash256 fragment.
+    type Hash256: Clone + Eq + Ord + Display + Debug + std::hash::Hash;
 }
 
 impl<Pk: MiniscriptKey> SimplicityKey for Pk {
     type Sha256 = <Pk as MiniscriptKey>::Sha256;
+    type Hash256 = <Pk as MiniscriptKey>::Hash256;
 }
 
 /// Public key which can be converted to a (x-only) public key which can be used in Simplicity.
@@ -22,6 +26,9 @@ pub trait ToXOnlyPubkey: SimplicityKey {
 
     /// Convert the generic associated [`SimplicityKey::Sha256`] to [`sha256::Hash`].
     fn to_sha256(hash: &Self::Sha256) -> sha256::Hash;
+
+    /// Convert the generic associated [`SimplicityKey::Hash256`] to [`hash256::Hash`].
+    fn to_hash256(hash: &Self::Hash256) -> hash256::Hash;
 }
 
 impl<Pk: ToPublicKey> ToXOnlyPubkey for Pk {
@@ -32,6 +39,10 @@ impl<Pk: ToPublicKey> ToXOnlyPubkey for Pk {
     fn to_sha256(hash: &Self::Sha256) -> sha256::Hash {
         <Pk as ToPublicKey>::to_sha256(hash)
     }
+
+    fn to_hash256(hash: &Self::Hash256) -> hash256::Hash {
+        <Pk as ToPublicKey>::to_hash256(hash)
+    }
 }
 
 /// Object which can translate one key type to another, including all associated hashes.
@@ -45,4 +56,24 @@ where
 
     /// Translates SHA 256 hashes `P::Sha256` → `Q::Sha256`.
     fn sha256(&mut self, sha256: &P::Sha256) -> Result<Q::Sha256, E>;
+
+    /// Translates double SHA 256 hashes `P::Hash256` → `Q::Hash256`.
+    fn hash256(&mut self, hash256: &P::Hash256) -> Result<Q::Hash256, E>;
+}
+
+/// Object which can translate one key type to another, including all associated hashes,
+/// and which can adjust timelocks.
+pub trait TranslatorExt<P, Q, E>: Translator<P, Q, E>
+where
+    P: SimplicityKey,
+    Q: SimplicityKey,
+{
+    /// Translates absolute timelocks.
+    fn after(&mut self, n: u32) -> Result<u32, E>;
+
+    /// Translates relative timelocks in blocks.
+    fn older(&mut self, n: u16) -> Result<u16, E>;
+
+    /// Translates relative timelocks in units of 512 seconds.
+    fn older_time(&mut self, n: u16) -> Result<u16, E>;
 }
@@ -0,0 +1,399 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Lifting
+//!
+//! Programs that were compiled from policies consist of the fragments that the
+//! compiler emits for each kind of sub-policy. Lifting recognizes these fragments
+//! and reconstructs the policy, so the spending conditions of a program can be
+//! inspected without knowing the policy it was compiled from.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use bitcoin_miniscript::hash256;
+use elements::bitcoin::key::XOnlyPublicKey;
+use hashes::{sha256, Hash};
+
+use super::{Error, Policy};
+use crate::jet::Elements;
+use crate::node::Inner;
+use crate::{CommitNode, Value};
+
+type Node = CommitNode<Elements>;
+
+/// Structure of a recognized fragment, with the sub-programs of its sub-policies.
+enum Fragment<'a> {
+    Leaf(Policy<XOnlyPublicKey>),
+    And(&'a Node, &'a Node),
+    Or(&'a Node, &'a Node),
+    Threshold(usize, Vec<&'a Node>),
+}
+
+impl Policy<XOnlyPublicKey> {
+    /// Lift a program that was compiled from a policy back to the policy.
+    ///
+    /// The sub-programs are matched against the fragments of [`Policy::commit`].
+    /// Hash preimages may have any supported width, but the lifted policy forgets it,
+    /// so it compiles with the default width.
+    ///
+    /// Weights do not change the compiled program, so they cannot be recovered and
+    /// every lifted branch has weight one. The weights of the original policy are
+    /// reflected in the order and nesting of its branches, see [`Policy::weighted_or`]
+    /// and [`Policy::weighted_threshold`]. The lifted policy keeps both, so it
+    /// compiles to the same program.
+    ///
+    /// Returns an error with the CMR of the first sub-program that is not a fragment.
+    pub fn lift(program: &CommitNode<Elements>) -> Result<Self, Error> {
+        let fragment = fragment(program).ok_or(Error::UnrecognizedFragment(program.cmr()))?;
+        match fragment {
+            Fragment::Leaf(policy) => Ok(policy),
+            Fragment::And(left, right) => Ok(Policy::And {
+                left: Arc::new(Self::lift(left)?),
+                right: Arc::new(Self::lift(right)?),
+            }),
+            Fragment::Or(left, right) => Ok(Policy::Or {
+                left: Arc::new(Self::lift(left)?),
+                right: Arc::new(Self::lift(right)?),
+                weights: [1, 1],
+            }),
+            Fragment::Threshold(k, subs) => {
+                let subs = subs.into_iter().map(Self::lift).collect::<Result<_, _>>()?;
+                Ok(Policy::threshold(k, subs))
+            }
+        }
+    }
+}
+
+fn fragment(node: &Node) -> Option<Fragment<'_>> {
+    match node.inner() {
+        Inner::Fail(entropy) => return Some(Fragment::Leaf(Policy::Unsatisfiable(*entropy))),
+        Inner::Unit => return Some(Fragment::Leaf(Policy::Trivial)),
+        _ => {}
+    }
+
+    let (left, right) = comp(node)?;
+    if let Some(policy) = key(left, right)
+        .or_else(|| timelock(left, right))
+        .or_else(|| sha256(left, right))
+    {
+        return Some(Fragment::Leaf(policy));
+    }
+    if let Some(k) = threshold_k(left, right) {
+        let sum = pair(comp(left)?.0)?.1;
+        let mut subs = vec![];
+        summands(sum, &mut subs)?;
+        return Some(Fragment::Threshold(k, subs));
+    }
+    if let Some((left, right)) = or(left, right) {
+        return Some(Fragment::Or(left, right));
+    }
+    require(is_unit_arrow(left) && is_unit_arrow(right))?;
+    Some(Fragment::And(left, right))
+}
+
+/// comp (pair (pair (const key) sig_all_hash) witness) bip_0340_verify
+fn key(left: &Node, right: &Node) -> Option<Policy<XOnlyPublicKey>> {
+    require(jet(right)? == Elements::Bip0340Verify)?;
+    let (key_msg, sig) = pair(left)?;
+    require(matches!(sig.inner(), Inner::Witness(..)))?;
+    let (key, msg) = pair(key_msg)?;
+    require(jet(msg)? == Elements::SigAllHash)?;
+    let key = XOnlyPublicKey::from_slice(&word_bytes(key, 32)?).ok()?;
+    Some(Policy::Key(key))
+}
+
+/// comp (const n) check_lock_{height, distance, duration}
+fn timelock(left: &Node, right: &Node) -> Option<Policy<XOnlyPublicKey>> {
+    match jet(right)? {
+        Elements::CheckLockHeight => {
+            let bytes = <[u8; 4]>::try_from(word_bytes(left, 4)?).ok()?;
+            Some(Policy::After(u32::from_be_bytes(bytes)))
+        }
+        Elements::CheckLockDistance => {
+            let bytes = <[u8; 2]>::try_from(word_bytes(left, 2)?).ok()?;
+            Some(Policy::Older(u16::from_be_bytes(bytes)))
+        }
+        Elements::CheckLockDuration => {
+            let bytes = <[u8; 2]>::try_from(word_bytes(left, 2)?).ok()?;
+            Some(Policy::OlderTime(u16::from_be_bytes(bytes)))
+        }
+        _ => None,
+    }
+}
+
+/// comp (comp (pair (const hash) (compute_sha256 witness)) eq_256) verify
+///
+/// or, for double SHA256,
+///
+/// comp (comp (pair (const hash) (compute_sha256 (compute_sha256 witness))) eq_256) verify
+fn sha256(left: &Node, right: &Node) -> Option<Policy<XOnlyPublicKey>> {
+    let (hash, computed) = verify_bexp(left, right, Elements::Eq256)?;
+    let hash = word_bytes(hash, 32)?;
+    let preimage = compute_sha256(computed)?;
+    if matches!(preimage.inner(), Inner::Witness(..)) {
+        let hash = sha256::Hash::from_slice(&hash).ok()?;
+        return Some(Policy::Sha256(hash));
+    }
+    require(matches!(
+        compute_sha256(preimage)?.inner(),
+        Inner::Witness(..)
+    ))?;
+    let hash = hash256::Hash::from_slice(&hash).ok()?;
+    Some(Policy::Hash256(hash))
+}
+
+/// comp (comp (pair sha256_ctx_8_init preimage) sha256_ctx_8_add_n) sha256_ctx_8_finalize
+///
+/// Returns the preimage.
+fn compute_sha256(node: &Node) -> Option<&Node> {
+    let (digest_ctx, finalize) = comp(node)?;
+    require(jet(finalize)? == Elements::Sha256Ctx8Finalize)?;
+    let (ctx_preimage, _add) = comp(digest_ctx)?;
+    let (ctx, preimage) = pair(ctx_preimage)?;
+    require(jet(ctx)? == Elements::Sha256Ctx8Init)?;
+    Some(preimage)
+}
+
+/// comp (comp (pair (const k) sum) eq_32) verify
+fn threshold_k(left: &Node, right: &Node) -> Option<usize> {
+    let (k, _sum) = verify_bexp(left, right, Elements::Eq32)?;
+    let bytes = <[u8; 4]>::try_from(word_bytes(k, 4)?).ok()?;
+    usize::try_from(u32::from_be_bytes(bytes)).ok()
+}
+
+/// Collect the sub-programs of the summands of a threshold, from left to right.
+///
+/// The sum is either a summand or `comp (comp (pair sum summand) add_32) (drop iden)`.
+fn summands<'a>(sum: &'a Node, subs: &mut Vec<&'a Node>) -> Option<()> {
+    if let Some(child) = summand(sum) {
+        subs.push(child);
+        return Some(());
+    }
+    let (full_sum, drop_iden) = comp(sum)?;
+    require(matches!(drop_(drop_iden)?.inner(), Inner::Iden))?;
+    let (pair_sum_summand, add) = comp(full_sum)?;
+    require(jet(add)? == Elements::Add32)?;
+    let (sum, summand_node) = pair(pair_sum_summand)?;
+    summands(sum, subs)?;
+    subs.push(summand(summand_node)?);
+    Some(())
+}
+
+/// comp (pair witness unit) (case (drop (const 0)) (drop (comp child (const 1))))
+fn summand(node: &Node) -> Option<&Node> {
+    let (left, right) = comp(node)?;
+    let (zero, child_one) = or(left, right)?;
+    require(**word(zero)? == *Value::u32(0))?;
+    let (child, one) = comp(child_one)?;
+    require(**word(one)? == *Value::u32(1))?;
+    Some(child)
+}
+
+/// comp (pair witness unit) (case (drop left) (drop right))
+fn or<'a>(selector: &'a Node, case: &'a Node) -> Option<(&'a Node, &'a Node)> {
+    let (witness, unit) = pair(selector)?;
+    require(matches!(witness.inner(), Inner::Witness(..)))?;
+    require(matches!(unit.inner(), Inner::Unit))?;
+    match case.inner() {
+        Inner::Case(left, right) => Some((drop_(left)?, drop_(right)?)),
+        _ => None,
+    }
+}
+
+/// comp (comp (pair left right) bexp) verify
+fn verify_bexp<'a>(
+    left: &'a Node,
+    right: &'a Node,
+    bexp: Elements,
+) -> Option<(&'a Node, &'a Node)> {
+    require(jet(right)? == Elements::Verify)?;
+    let (input, computed_bexp) = comp(left)?;
+    require(jet(computed_bexp)? == bexp)?;
+    pair(input)
+}
+
+fn require(condition: bool) -> Option<()> {
+    if condition {
+        Some(())
+    } else {
+        None
+    }
+}
+
+fn is_unit_arrow(node: &Node) -> bool {
+    node.arrow().source.is_unit() && node.arrow().target.is_unit()
+}
+
+fn comp(node: &Node) -> Option<(&Node, &Node)> {
+    match node.inner() {
+        Inner::Comp(left, right) => Some((left, right)),
+        _ => None,
+    }
+}
+
+fn pair(node: &Node) -> Option<(&Node, &Node)> {
+    match node.inner() {
+        Inner::Pair(left, right) => Some((left, right)),
+        _ => None,
+    }
+}
+
+fn drop_(node: &Node) -> Option<&Node> {
+    match node.inner() {
+        Inner::Drop(child) => Some(child),
+        _ => None,
+    }
+}
+
+fn jet(node: &Node) -> Option<Elements> {
+    match node.inner() {
+        Inner::Jet(jet) => Some(*jet),
+        _ => None,
+    }
+}
+
+fn word(node: &Node) -> Option<&Arc<Value>> {
+    match node.inner() {
+        Inner::Word(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Return the bytes of a constant word of the given byte length.
+fn word_bytes(node: &Node, len: usize) -> Option<Vec<u8>> {
+    let bytes = word(node)?.try_to_bytes().ok()?;
+    require(bytes.len() == len)?;
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::CoreConstructible;
+    use crate::policy::serialize;
+    use crate::ConstructNode;
+    use crate::FailEntropy;
+    use elements::secp256k1_zkp;
+
+    #[test]
+    fn lift_compiled_policy() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let keys: Vec<XOnlyPublicKey> = (1..=4)
+            .map(|i| {
+                secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[i; 32])
+                    .unwrap()
+                    .x_only_public_key()
+                    .0
+            })
+            .collect();
+        let policy = Policy::Or {
+            left: Arc::new(Policy::threshold(
+                2,
+                vec![
+                    Policy::Key(keys[0]),
+                    Policy::Sha256(sha256::Hash::hash(b"preimage")),
+                    Policy::Hash256(hash256::Hash::hash(b"preimage")),
+                    Policy::And {
+                        left: Arc::new(Policy::Key(keys[1])),
+                        right: Arc::new(Policy::After(500_000)),
+                    },
+                ],
+            )),
+            right: Arc::new(Policy::And {
+                left: Arc::new(Policy::threshold(
+                    1,
+                    vec![Policy::Key(keys[2]), Policy::Older(144)],
+                )),
+                right: Arc::new(Policy::Or {
+                    left: Arc::new(Policy::OlderTime(10)),
+                    right: Arc::new(Policy::Or {
+                        left: Arc::new(Policy::Trivial),
+                        right: Arc::new(Policy::Unsatisfiable(FailEntropy::ZERO)),
+                        weights: [1, 1],
+                    }),
+                    weights: [1, 1],
+                }),
+            }),
+            weights: [1, 1],
+        };
+
+        let program = policy.commit().unwrap();
+        let lifted = Policy::lift(&program).unwrap();
+        assert_eq!(lifted, policy);
+        assert_eq!(
+            lifted.normalized().sorted(),
+            policy.clone().normalized().sorted()
+        );
+
+        // The preimage width is not part of the policy
+        let hash = sha256::Hash::hash(b"preimage");
+        let narrow = serialize::sha256_with_width::<XOnlyPublicKey, Arc<ConstructNode<_>>, _>(
+            &hash,
+            crate::node::NoWitness,
+            64,
+        )
+        .finalize_types()
+        .unwrap();
+        assert_eq!(Policy::lift(&narrow).unwrap(), Policy::Sha256(hash));
+    }
+
+    #[test]
+    fn lift_weighted_policy() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let keys: Vec<XOnlyPublicKey> = (1..=3)
+            .map(|i| {
+                secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[i; 32])
+                    .unwrap()
+                    .x_only_public_key()
+                    .0
+            })
+            .collect();
+        let or = |left, right| Policy::Or {
+            left: Arc::new(left),
+            right: Arc::new(right),
+            weights: [1, 1],
+        };
+
+        let s = format!("or(9@pk({}),1@pk({}),1@pk({}))", keys[0], keys[1], keys[2]);
+        let policy: Policy<XOnlyPublicKey> = s.parse().unwrap();
+        let program = policy.commit().unwrap();
+        let lifted = Policy::lift(&program).unwrap();
+        // The likely branch stays first and on top,
+        // and the unlikely branches stay in order below it
+        let expected = or(
+            Policy::Key(keys[0]),
+            or(Policy::Key(keys[1]), Policy::Key(keys[2])),
+        );
+        assert_eq!(lifted, expected);
+        assert!(lifted.structurally_eq(&policy));
+        assert_eq!(lifted.commit().unwrap().cmr(), program.cmr());
+
+        // The heavier sub-policies of a threshold stay first
+        let policy = Policy::weighted_threshold(
+            1,
+            vec![(1, Policy::Key(keys[0])), (5, Policy::Key(keys[1]))],
+        );
+        let program = policy.commit().unwrap();
+        let lifted = Policy::lift(&program).unwrap();
+        assert_eq!(
+            lifted,
+            Policy::threshold(1, vec![Policy::Key(keys[1]), Policy::Key(keys[0])])
+        );
+        assert_eq!(lifted.commit().unwrap().cmr(), program.cmr());
+    }
+
+    #[test]
+    fn lift_unrecognized() {
+        // comp (injl unit) unit has type 1 → 1, but is no fragment
+        type Node = Arc<ConstructNode<Elements>>;
+        let unrecognized = Node::comp(&Node::injl(&Node::unit()), &Node::unit()).unwrap();
+        let program = Node::comp(&Node::unit(), &unrecognized)
+            .unwrap()
+            .finalize_types()
+            .unwrap();
+        match Policy::lift(&program) {
+            Err(Error::UnrecognizedFragment(cmr)) => assert_eq!(cmr, unrecognized.cmr()),
+            _ => panic!("lifted program that is no policy"),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Policy Minimization
+//!
+//! The same spending condition can often be written in several ways, which
+//! compile to programs of different sizes. For example, a threshold where all
+//! sub-policies must be satisfied is equivalent to a chain of conjunctions,
+//! which does not need a counting circuit. The minimizer tries such
+//! reformulations and keeps the one that compiles to the smallest program.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+
+use super::{Policy, ToXOnlyPubkey};
+use crate::BitWriter;
+
+/// Number of random leaf assignments to check if there are too many leaves to
+/// check all of them.
+const N_SAMPLES: usize = 1024;
+
+impl<Pk: ToXOnlyPubkey> Policy<Pk> {
+    /// Return the equivalent reformulation of the policy that compiles to the
+    /// smallest program.
+    ///
+    /// The policy is first normalized. Then, bottom up, thresholds are replaced by
+    /// chains of `and` or `or` fragments and vice versa, wherever this makes the
+    /// compiled program smaller. Ties are resolved in favor of the original form,
+    /// so the result is deterministic.
+    ///
+    /// Policies with assembly fragments cannot be compiled, so they are only normalized.
+    pub fn minimized(&self) -> Policy<Pk> {
+        let normalized = self.clone().normalized();
+        let minimized = normalized.minimize();
+        debug_assert!(minimized.equivalent_by_sampling(self));
+        minimized
+    }
+
+    fn minimize(&self) -> Policy<Pk> {
+        let node = match self {
+            Policy::And { left, right } => Policy::And {
+                left: Arc::new(left.minimize()),
+                right: Arc::new(right.minimize()),
+            },
+            Policy::Or {
+                left,
+                right,
+                weights,
+            } => Policy::Or {
+                left: Arc::new(left.minimize()),
+                right: Arc::new(right.minimize()),
+                weights: *weights,
+            },
+            Policy::Threshold(k, subs) => Policy::Threshold(
+                *k,
+                subs.iter()
+                    .map(|(weight, sub)| (*weight, sub.minimize()))
+                    .collect(),
+            ),
+            _ => return self.clone(),
+        };
+
+        let mut best = node.clone();
+        let mut best_bits = best.program_bits();
+        for candidate in node.reformulations() {
+            let bits = candidate.program_bits();
+            let smaller = match (bits, best_bits) {
+                (Some(bits), Some(best_bits)) => bits < best_bits,
+                _ => false,
+            };
+            if smaller && candidate.equivalent_by_sampling(&node) {
+                best = candidate;
+                best_bits = bits;
+            }
+        }
+        best
+    }
+
+    /// Return the equivalent reformulations of the top-level fragment of the policy.
+    fn reformulations(&self) -> Vec<Policy<Pk>> {
+        let and_chain = |subs: &[Policy<Pk>]| {
+            let mut subs = subs.iter().cloned();
+            let first = subs.next().expect("at least one sub-policy");
+            subs.fold(first, |left, right| Policy::And {
+                left: Arc::new(left),
+                right: Arc::new(right),
+            })
+        };
+        let or_chain = |subs: &[Policy<Pk>]| {
+            let mut subs = subs.iter().cloned();
+            let first = subs.next().expect("at least one sub-policy");
+            subs.fold(first, |left, right| Policy::Or {
+                left: Arc::new(left),
+                right: Arc::new(right),
+                weights: [1, 1],
+            })
+        };
+
+        match self {
+            Policy::Threshold(k, weighted_subs) if !weighted_subs.is_empty() => {
+                let subs: Vec<_> = weighted_subs.iter().map(|(_, sub)| sub.clone()).collect();
+                let mut candidates = vec![];
+                if *k == subs.len() {
+                    candidates.push(and_chain(&subs));
+                }
+                // The weights of the sub-policies would be lost in a chain of `or` fragments
+                let uniform = weighted_subs.windows(2).all(|pair| pair[0].0 == pair[1].0);
+                if *k == 1 && uniform {
+                    candidates.push(or_chain(&subs));
+                }
+                candidates
+            }
+            Policy::And { .. } => {
+                let subs = self.flatten_chain();
+                vec![Policy::threshold(subs.len(), subs)]
+            }
+            // Likewise, a threshold cannot express the weights of `or` fragments
+            Policy::Or {
+                weights: [1, 1], ..
+            } => {
+                let subs = self.flatten_chain();
+                vec![Policy::threshold(1, subs)]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Return the operands of a chain of nested `and` or `or` fragments, from left to right.
+    ///
+    /// Weighted `or` fragments end the chain.
+    fn flatten_chain(&self) -> Vec<Policy<Pk>> {
+        let mut operands = vec![];
+        let mut stack = vec![self];
+        while let Some(top) = stack.pop() {
+            match (self, top) {
+                (Policy::And { .. }, Policy::And { left, right })
+                | (
+                    Policy::Or { .. },
+                    Policy::Or {
+                        left,
+                        right,
+                        weights: [1, 1],
+                    },
+                ) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                _ => operands.push(top.clone()),
+            }
+        }
+        operands
+    }
+
+    /// Return the bit length of the compiled program of the policy.
+    ///
+    /// Returns `None` if the policy contains assembly fragments.
+    fn program_bits(&self) -> Option<usize> {
+        let commit = self.commit()?;
+        let bits = commit
+            .encode(&mut BitWriter::new(io::sink()))
+            .expect("write to sink never fails");
+        Some(bits)
+    }
+
+    /// Check whether two policies agree on whether they are satisfied,
+    /// for a sample of satisfactions of their leaves.
+    ///
+    /// Leaves are all fragments other than `and`, `or` and `thresh`. All assignments
+    /// are checked if there are few leaves, otherwise a fixed pseudorandom sample.
+    fn equivalent_by_sampling(&self, other: &Self) -> bool {
+        let mut leaves = BTreeMap::new();
+        for leaf in self.iter().chain(other.iter()).filter(|p| p.is_leaf()) {
+            let index = leaves.len();
+            leaves.entry(leaf).or_insert(index);
+        }
+
+        let mut assignment = vec![false; leaves.len()];
+        let check = |assignment: &[bool]| {
+            let eval = |leaf: &Policy<Pk>| assignment[leaves[leaf]];
+            self.evaluate(&eval) == other.evaluate(&eval)
+        };
+
+        // Checking all assignments is no more expensive than sampling
+        if leaves.len() <= N_SAMPLES.trailing_zeros() as usize {
+            (0..1usize << leaves.len()).all(|bits| {
+                for (i, value) in assignment.iter_mut().enumerate() {
+                    *value = bits & (1 << i) != 0;
+                }
+                check(&assignment)
+            })
+        } else {
+            // Xorshift, seeded with a constant for determinism
+            let mut state = 0x2545_f491_4f6c_dd1du64;
+            (0..N_SAMPLES).all(|_| {
+                for value in assignment.iter_mut() {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    *value = state & 1 != 0;
+                }
+                check(&assignment)
+            })
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        !matches!(
+            self,
+            Policy::And { .. } | Policy::Or { .. } | Policy::Threshold(..)
+        )
+    }
+
+    /// Evaluate whether the policy is satisfied, given which of its leaves are satisfied.
+    fn evaluate<F: Fn(&Self) -> bool>(&self, leaf: &F) -> bool {
+        match self {
+            Policy::Unsatisfiable(..) => false,
+            Policy::Trivial => true,
+            Policy::And { left, right } => left.evaluate(leaf) && right.evaluate(leaf),
+            Policy::Or { left, right, .. } => left.evaluate(leaf) || right.evaluate(leaf),
+            // Exactly `k` sub-policies are satisfied in the witness, which is possible
+            // if at least `k` sub-policies can be satisfied
+            Policy::Threshold(k, subs) => {
+                subs.iter().filter(|(_, sub)| sub.evaluate(leaf)).count() >= *k
+            }
+            _ => leaf(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::bitcoin::key::XOnlyPublicKey;
+    use elements::secp256k1_zkp;
+
+    fn get_keys(n: u8) -> Vec<XOnlyPublicKey> {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        (1..=n)
+            .map(|i| {
+                let keypair = secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[i; 32])
+                    .expect("valid secret key");
+                keypair.x_only_public_key().0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn minimize_verbose_threshold() {
+        let keys = get_keys(4);
+        let policy = Policy::Or {
+            left: Arc::new(Policy::threshold(
+                3,
+                keys[0..3].iter().copied().map(Policy::Key).collect(),
+            )),
+            right: Arc::new(Policy::threshold(
+                1,
+                vec![Policy::Key(keys[3]), Policy::Older(144)],
+            )),
+            weights: [1, 1],
+        };
+
+        let minimized = policy.minimized();
+        assert!(minimized.program_bits().unwrap() < policy.program_bits().unwrap());
+        assert!(minimized.equivalent_by_sampling(&policy));
+        assert_eq!(minimized.minimized(), minimized);
+
+        // A proper threshold is already as small as it gets
+        let multisig = Policy::threshold(2, keys.iter().copied().map(Policy::Key).collect());
+        assert_eq!(multisig.minimized(), multisig);
+    }
+}
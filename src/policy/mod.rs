@@ -15,12 +15,18 @@
 
 mod ast;
 mod error;
+mod expression;
 mod key;
+mod lift;
+mod minimize;
+mod recompile;
 mod satisfy;
 mod serialize;
 pub mod sighash;
 
-pub use ast::Policy;
+pub use ast::{cheaper_of, Policy};
 pub use error::Error;
-pub use key::{SimplicityKey, ToXOnlyPubkey, Translator};
-pub use satisfy::{Preimage32, Satisfier};
+pub use key::{SimplicityKey, ToXOnlyPubkey, Translator, TranslatorExt};
+pub use recompile::Recompiler;
+pub use satisfy::{Preimage32, Satisfaction, Satisfier};
+pub use serialize::DEFAULT_PREIMAGE_BITS;
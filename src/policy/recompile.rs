@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Incremental Compilation
+//!
+//! Editing a policy typically changes only a small part of it. The recompiler
+//! remembers the fragments of the last compiled policy and reuses them for all
+//! sub-policies that did not change, so only the edited part is compiled again.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::serialize::DEFAULT_PREIMAGE_BITS;
+use super::{Policy, ToXOnlyPubkey};
+use crate::jet::Elements;
+use crate::node::{CommitNode, ConstructNode};
+
+/// Compiler of policies that reuses the fragments of the previously compiled policy.
+///
+/// Sub-policies are matched structurally, so any sub-policy that is equal to a
+/// sub-policy of the previous policy is not compiled again. Weights do not change
+/// the compiled fragments, so they are ignored when matching sub-policies. The resulting program
+/// is the same as that of [`Policy::commit`].
+#[derive(Debug)]
+pub struct Recompiler<Pk: ToXOnlyPubkey> {
+    fragments: HashMap<Policy<Pk>, Arc<ConstructNode<Elements>>>,
+}
+
+impl<Pk: ToXOnlyPubkey> Default for Recompiler<Pk> {
+    fn default() -> Self {
+        Recompiler {
+            fragments: HashMap::new(),
+        }
+    }
+}
+
+impl<Pk: ToXOnlyPubkey> Recompiler<Pk> {
+    /// Create a recompiler without any previously compiled fragments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the program commitment of the policy.
+    ///
+    /// Afterwards, the recompiler remembers the fragments of this policy
+    /// and forgets those of the previous one.
+    pub fn commit(&mut self, policy: &Policy<Pk>) -> Option<Arc<CommitNode<Elements>>> {
+        let mut fragments = HashMap::new();
+        let construct = self.serialize(policy, &mut fragments)?;
+        self.fragments = fragments;
+        Some(construct.finalize_types().expect("policy has sound types"))
+    }
+
+    fn serialize(
+        &self,
+        policy: &Policy<Pk>,
+        fragments: &mut HashMap<Policy<Pk>, Arc<ConstructNode<Elements>>>,
+    ) -> Option<Arc<ConstructNode<Elements>>> {
+        // Every fragment has type 1 → 1, so reusing it does not change its type
+        let key = policy.unweighted();
+        let fragment = match self.fragments.get(&key) {
+            Some(fragment) => {
+                // Keep the fragments of the sub-policies, too,
+                // so they can be reused if the next policy changes this one
+                for sub in key.iter().skip(1) {
+                    if let Some(sub_fragment) = self.fragments.get(sub) {
+                        fragments.insert(sub.clone(), Arc::clone(sub_fragment));
+                    }
+                }
+                Arc::clone(fragment)
+            }
+            None => policy.serialize_no_witness_with(DEFAULT_PREIMAGE_BITS, &mut |sub| {
+                self.serialize(sub, fragments)
+            })?,
+        };
+        fragments.insert(key, Arc::clone(&fragment));
+        Some(fragment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::bitcoin::key::XOnlyPublicKey;
+    use elements::secp256k1_zkp;
+
+    fn get_keys(n: u8) -> Vec<XOnlyPublicKey> {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        (1..=n)
+            .map(|i| {
+                let keypair = secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[i; 32])
+                    .expect("valid secret key");
+                keypair.x_only_public_key().0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recompile_changed_leaf() {
+        let keys = get_keys(8);
+        let multisig = |keys: &[XOnlyPublicKey]| {
+            Policy::threshold(2, keys.iter().copied().map(Policy::Key).collect())
+        };
+        let policy = Policy::Or {
+            left: Arc::new(multisig(&keys[0..4])),
+            right: Arc::new(Policy::And {
+                left: Arc::new(multisig(&keys[4..8])),
+                right: Arc::new(Policy::Older(144)),
+            }),
+            weights: [1, 1],
+        };
+        let edited = Policy::Or {
+            left: Arc::new(multisig(&keys[0..4])),
+            right: Arc::new(Policy::And {
+                left: Arc::new(multisig(&keys[4..8])),
+                right: Arc::new(Policy::Older(1000)),
+            }),
+            weights: [1, 1],
+        };
+
+        let mut recompiler = Recompiler::new();
+        let commit = recompiler.commit(&policy).unwrap();
+        assert_eq!(commit.cmr(), policy.commit().unwrap().cmr());
+
+        let fragments = recompiler.fragments.clone();
+        let recommit = recompiler.commit(&edited).unwrap();
+        assert_eq!(recommit.cmr(), edited.commit().unwrap().cmr());
+        assert_ne!(recommit.cmr(), commit.cmr());
+
+        // The unchanged multisig fragments are shared with the previous compilation
+        for unchanged in [multisig(&keys[0..4]), multisig(&keys[4..8])] {
+            assert!(Arc::ptr_eq(
+                &fragments[&unchanged],
+                &recompiler.fragments[&unchanged]
+            ));
+        }
+        assert!(!recompiler.fragments.contains_key(&Policy::Older(144)));
+    }
+
+    #[test]
+    fn recompile_keeps_reused_sub_fragments() {
+        let keys = get_keys(3);
+        let pk = |i: usize| Policy::Key(keys[i]);
+        let and = |left: &Policy<_>, older| Policy::And {
+            left: Arc::new(left.clone()),
+            right: Arc::new(Policy::Older(older)),
+        };
+        let multisig = Policy::threshold(1, vec![pk(0), pk(1)]);
+        let edited = Policy::threshold(1, vec![pk(0), pk(2)]);
+
+        let mut recompiler = Recompiler::new();
+        recompiler.commit(&and(&multisig, 1)).unwrap();
+        let fragments = recompiler.fragments.clone();
+        // The threshold is reused as a whole, and the fragments of its keys are kept
+        recompiler.commit(&and(&multisig, 2)).unwrap();
+        assert!(Arc::ptr_eq(
+            &fragments[&pk(0)],
+            &recompiler.fragments[&pk(0)]
+        ));
+
+        // So they are reused when the threshold changes afterwards
+        let recommit = recompiler.commit(&and(&edited, 2)).unwrap();
+        assert_eq!(recommit.cmr(), and(&edited, 2).commit().unwrap().cmr());
+        assert!(Arc::ptr_eq(
+            &fragments[&pk(0)],
+            &recompiler.fragments[&pk(0)]
+        ));
+    }
+
+    #[test]
+    fn recompile_changed_weights() {
+        let keys = get_keys(2);
+        let or = |weights| Policy::Or {
+            left: Arc::new(Policy::Key(keys[0])),
+            right: Arc::new(Policy::Key(keys[1])),
+            weights,
+        };
+
+        let mut recompiler = Recompiler::new();
+        let commit = recompiler.commit(&or([1, 1])).unwrap();
+        let fragments = recompiler.fragments.clone();
+        let recommit = recompiler.commit(&or([9, 1])).unwrap();
+        assert_eq!(recommit.cmr(), commit.cmr());
+
+        // The reweighted disjunction is reused as a whole
+        let key = or([1, 1]);
+        assert!(Arc::ptr_eq(&fragments[&key], &recompiler.fragments[&key]));
+    }
+}
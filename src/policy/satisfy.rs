@@ -1,8 +1,9 @@
 // SPDX-License-Identifier: CC0-1.0
 
 use crate::analysis::Cost;
+use crate::dag::{DagLike, InternalSharing};
 use crate::jet::Elements;
-use crate::node::{RedeemNode, WitnessNode};
+use crate::node::{Inner, RedeemNode, WitnessNode};
 use crate::policy::ToXOnlyPubkey;
 use crate::{Cmr, Error, Policy, Value};
 use elements::bitcoin;
@@ -33,6 +34,11 @@ pub trait Satisfier<Pk: ToXOnlyPubkey> {
         None
     }
 
+    /// Given a double SHA256 hash, look up its preimage.
+    fn lookup_hash256(&self, _: &Pk::Hash256) -> Option<Preimage32> {
+        None
+    }
+
     /// Assert that a relative locktime is satisfied.
     fn check_older(&self, _: elements::Sequence) -> bool {
         false
@@ -90,6 +96,85 @@ impl<Pk: ToXOnlyPubkey> Satisfier<Pk> for elements::LockTime {
     }
 }
 
+/// Satisfied program of a policy, along with the timelocks that
+/// the spending transaction must set.
+#[derive(Clone, Debug)]
+pub struct Satisfaction {
+    /// The satisfied program
+    pub program: Arc<RedeemNode<Elements>>,
+    /// Minimum lock time of the spending transaction,
+    /// if the chosen branches contain `after` fragments
+    pub required_locktime: Option<elements::LockTime>,
+    /// Minimum sequence number of the spending input,
+    /// if the chosen branches contain `older` fragments
+    pub required_sequence: Option<elements::Sequence>,
+}
+
+impl Satisfaction {
+    /// Compute the timelocks that the given satisfied program checks.
+    ///
+    /// Pruned branches are hidden inside the program,
+    /// so only the timelocks of the chosen branches are found.
+    fn from_program(program: Arc<RedeemNode<Elements>>) -> Self {
+        let mut max_height = None::<u32>;
+        let mut max_distance = None::<u16>;
+        let mut max_duration = None::<u16>;
+
+        for data in program.as_ref().post_order_iter::<InternalSharing>() {
+            if let Inner::Comp(left, right) = data.node.inner() {
+                let (word, jet) = match (left.inner(), right.inner()) {
+                    (Inner::Word(word), Inner::Jet(jet)) => (word, jet),
+                    _ => continue,
+                };
+                let bytes = word.try_to_bytes().unwrap_or_default();
+                match *jet {
+                    Elements::CheckLockHeight => {
+                        if let Ok(bytes) = <[u8; 4]>::try_from(&bytes[..]) {
+                            let height = u32::from_be_bytes(bytes);
+                            max_height = Some(max_height.map_or(height, |h| h.max(height)));
+                        }
+                    }
+                    Elements::CheckLockDistance => {
+                        if let Ok(bytes) = <[u8; 2]>::try_from(&bytes[..]) {
+                            let distance = u16::from_be_bytes(bytes);
+                            max_distance = Some(max_distance.map_or(distance, |d| d.max(distance)));
+                        }
+                    }
+                    Elements::CheckLockDuration => {
+                        if let Ok(bytes) = <[u8; 2]>::try_from(&bytes[..]) {
+                            let duration = u16::from_be_bytes(bytes);
+                            max_duration = Some(max_duration.map_or(duration, |d| d.max(duration)));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Satisfaction {
+            program,
+            required_locktime: max_height.map(|n| {
+                elements::LockTime::Blocks(Height::from_consensus(n).expect("timelock is valid"))
+            }),
+            // A sequence number encodes either blocks or time,
+            // so a satisfied program never checks both
+            required_sequence: max_distance
+                .map(|n| elements::Sequence(n.into()))
+                .or_else(|| max_duration.map(elements::Sequence::from_512_second_intervals)),
+        }
+    }
+}
+
+/// Satisfier which forwards only the lookup of assembly programs,
+/// which are needed to construct a policy even without its witness data.
+struct AssemblyOnly<'a, Pk>(&'a dyn Satisfier<Pk>);
+
+impl<'a, Pk: ToXOnlyPubkey> Satisfier<Pk> for AssemblyOnly<'a, Pk> {
+    fn lookup_asm_program(&self, cmr: Cmr) -> Option<Arc<WitnessNode<Elements>>> {
+        self.0.lookup_asm_program(cmr)
+    }
+}
+
 impl<Pk: ToXOnlyPubkey> Policy<Pk> {
     fn satisfy_internal<S: Satisfier<Pk>>(
         &self,
@@ -106,11 +191,10 @@ impl<Pk: ToXOnlyPubkey> Policy<Pk> {
             }
             Policy::After(n) => {
                 let node = super::serialize::after::<Arc<_>>(n);
-                let height = Height::from_consensus(n).expect("timelock is valid");
-                if satisfier.check_after(elements::LockTime::Blocks(height)) {
-                    node
-                } else {
-                    node.pruned()
+                // Lock times that are not block heights can never be satisfied
+                match Height::from_consensus(n) {
+                    Ok(height) if satisfier.check_after(elements::LockTime::Blocks(height)) => node,
+                    _ => node.pruned(),
                 }
             }
             Policy::Older(n) => {
@@ -121,23 +205,45 @@ impl<Pk: ToXOnlyPubkey> Policy<Pk> {
                     node.pruned()
                 }
             }
+            Policy::OlderTime(n) => {
+                let node = super::serialize::older_time::<Arc<_>>(n);
+                if satisfier.check_older(elements::Sequence::from_512_second_intervals(n)) {
+                    node
+                } else {
+                    node.pruned()
+                }
+            }
             Policy::Sha256(ref hash) => {
                 let preimage_wit = satisfier
                     .lookup_sha256(hash)
                     .map(|preimage| Value::u256_from_slice(preimage.as_ref()));
                 super::serialize::sha256::<Pk, _, _>(hash, preimage_wit)
             }
+            Policy::Hash256(ref hash) => {
+                let preimage_wit = satisfier
+                    .lookup_hash256(hash)
+                    .map(|preimage| Value::u256_from_slice(preimage.as_ref()));
+                super::serialize::hash256::<Pk, _, _>(hash, preimage_wit)
+            }
             Policy::And {
                 ref left,
                 ref right,
             } => {
                 let left = left.satisfy_internal(satisfier)?;
-                let right = right.satisfy_internal(satisfier)?;
+                // If the left sub-policy is unsatisfiable, then so is the conjunction.
+                // The right sub-policy is still needed to compute the CMR, but there
+                // is no point in looking up its witness data.
+                let right = if left.must_prune() {
+                    right.satisfy_internal(&AssemblyOnly(satisfier))?
+                } else {
+                    right.satisfy_internal(satisfier)?
+                };
                 super::serialize::and(&left, &right)
             }
             Policy::Or {
                 ref left,
                 ref right,
+                ..
             } => {
                 let left = left.satisfy_internal(satisfier)?;
                 let right = right.satisfy_internal(satisfier)?;
@@ -165,7 +271,7 @@ impl<Pk: ToXOnlyPubkey> Policy<Pk> {
             Policy::Threshold(k, ref subs) => {
                 let nodes: Result<Vec<Arc<WitnessNode<_>>>, Error> = subs
                     .iter()
-                    .map(|sub| sub.satisfy_internal(satisfier))
+                    .map(|(_, sub)| sub.satisfy_internal(satisfier))
                     .collect();
                 let mut nodes = nodes?;
                 let mut costs = vec![Cost::CONSENSUS_MAX; subs.len()];
@@ -211,6 +317,13 @@ impl<Pk: ToXOnlyPubkey> Policy<Pk> {
         Ok(node)
     }
 
+    /// Return a satisfied program for the policy, using the witness data of the satisfier.
+    ///
+    /// Every sub-policy is visited, because the program commits to all of its branches.
+    /// The only shortcut is that a conjunction does not look up witness data for its
+    /// right sub-policy if its left sub-policy is unsatisfiable. Disjunctions and
+    /// thresholds satisfy all of their sub-policies, so that the cheapest satisfaction
+    /// can be chosen. Use [`Policy::is_satisfiable`] to stop early instead.
     pub fn satisfy<S: Satisfier<Pk>>(
         &self,
         satisfier: &S,
@@ -222,6 +335,64 @@ impl<Pk: ToXOnlyPubkey> Policy<Pk> {
             WitnessNode::finalize(&witnode.prune_and_retype())
         }
     }
+
+    /// Return a satisfied program for the policy like [`Policy::satisfy`],
+    /// along with the lock time and sequence number that the spending transaction
+    /// must set for the chosen branches to succeed.
+    pub fn satisfy_with_timelocks<S: Satisfier<Pk>>(
+        &self,
+        satisfier: &S,
+    ) -> Result<Satisfaction, Error> {
+        self.satisfy(satisfier).map(Satisfaction::from_program)
+    }
+
+    /// Check whether the satisfier has enough witness data to satisfy the policy.
+    ///
+    /// This is cheaper than [`Policy::satisfy`] because no program is constructed.
+    /// Unlike [`Policy::satisfy`], this method short-circuits. Sub-policies are evaluated
+    /// from left to right: a conjunction stops at the first unsatisfiable sub-policy,
+    /// a disjunction stops at the first satisfiable sub-policy and a threshold stops
+    /// as soon as its outcome is decided.
+    pub fn is_satisfiable<S: Satisfier<Pk>>(&self, satisfier: &S) -> bool {
+        match *self {
+            Policy::Unsatisfiable(..) => false,
+            Policy::Trivial => true,
+            Policy::Key(ref key) => satisfier
+                .lookup_tap_leaf_script_sig(key, &TapLeafHash::all_zeros())
+                .is_some(),
+            Policy::After(n) => Height::from_consensus(n)
+                .map(|height| satisfier.check_after(elements::LockTime::Blocks(height)))
+                .unwrap_or(false),
+            Policy::Older(n) => satisfier.check_older(elements::Sequence((n).into())),
+            Policy::OlderTime(n) => {
+                satisfier.check_older(elements::Sequence::from_512_second_intervals(n))
+            }
+            Policy::Sha256(ref hash) => satisfier.lookup_sha256(hash).is_some(),
+            Policy::Hash256(ref hash) => satisfier.lookup_hash256(hash).is_some(),
+            Policy::And {
+                ref left,
+                ref right,
+            } => left.is_satisfiable(satisfier) && right.is_satisfiable(satisfier),
+            Policy::Or {
+                ref left,
+                ref right,
+                ..
+            } => left.is_satisfiable(satisfier) || right.is_satisfiable(satisfier),
+            Policy::Threshold(k, ref subs) => {
+                let mut satisfied = 0;
+                for (i, (_, sub)) in subs.iter().enumerate() {
+                    if satisfied >= k || satisfied + (subs.len() - i) < k {
+                        break;
+                    }
+                    if sub.is_satisfiable(satisfier) {
+                        satisfied += 1;
+                    }
+                }
+                satisfied >= k
+            }
+            Policy::Assembly(cmr) => satisfier.lookup_asm_program(cmr).is_some(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -232,6 +403,7 @@ mod tests {
     use crate::node::{CoreConstructible, JetConstructible, SimpleFinalizer, WitnessConstructible};
     use crate::policy::serialize;
     use crate::{BitMachine, FailEntropy, SimplicityKey};
+    use bitcoin_miniscript::hash256;
     use elements::bitcoin::key::{Keypair, XOnlyPublicKey};
     use elements::secp256k1_zkp;
     use hashes::{sha256, Hash};
@@ -240,6 +412,7 @@ mod tests {
 
     pub struct PolicySatisfier<'a, Pk: SimplicityKey> {
         pub preimages: HashMap<Pk::Sha256, Preimage32>,
+        pub hash256_preimages: HashMap<Pk::Hash256, Preimage32>,
         pub signatures: HashMap<Pk, elements::SchnorrSig>,
         pub assembly: HashMap<Cmr, Arc<WitnessNode<Elements>>>,
         pub tx: &'a elements::Transaction,
@@ -259,6 +432,10 @@ mod tests {
             self.preimages.get(hash).copied()
         }
 
+        fn lookup_hash256(&self, hash: &Pk::Hash256) -> Option<Preimage32> {
+            self.hash256_preimages.get(hash).copied()
+        }
+
         fn check_older(&self, sequence: elements::Sequence) -> bool {
             let self_sequence = self.tx.input[self.index].sequence;
             <elements::Sequence as Satisfier<Pk>>::check_older(&self_sequence, sequence)
@@ -277,10 +454,12 @@ mod tests {
         env: &ElementsEnv<Arc<elements::Transaction>>,
     ) -> PolicySatisfier<XOnlyPublicKey> {
         let mut preimages = HashMap::new();
+        let mut hash256_preimages = HashMap::new();
 
         for i in 0..3 {
             let preimage = [i; 32];
             preimages.insert(sha256::Hash::hash(&preimage), preimage);
+            hash256_preimages.insert(hash256::Hash::hash(&preimage), preimage);
         }
 
         let secp = secp256k1_zkp::Secp256k1::new();
@@ -303,6 +482,7 @@ mod tests {
 
         PolicySatisfier {
             preimages,
+            hash256_preimages,
             signatures,
             assembly: HashMap::new(),
             tx: env.tx(),
@@ -404,6 +584,30 @@ mod tests {
         execute_successful(program, &env);
     }
 
+    #[test]
+    fn satisfy_hash256() {
+        let env = ElementsEnv::dummy();
+        let satisfier = get_satisfier(&env);
+        let image = *satisfier.hash256_preimages.keys().next().unwrap();
+        let policy = Policy::Hash256(image);
+
+        let program = policy.satisfy(&satisfier).expect("satisfiable");
+        let witness = to_witness(&program);
+        assert_eq!(1, witness.len());
+
+        let witness_bytes = witness[0].try_to_bytes().expect("to bytes");
+        let preimage = *satisfier.hash256_preimages.get(&image).unwrap();
+        assert_eq!(&preimage[..], witness_bytes.as_slice());
+
+        execute_successful(program, &env);
+
+        // The single SHA256 hash of the preimage is not the image
+        let policy = Policy::Hash256(hash256::Hash::from_byte_array(
+            sha256::Hash::hash(&preimage).to_byte_array(),
+        ));
+        assert!(policy.satisfy(&satisfier).is_err(), "unsatisfiable");
+    }
+
     #[test]
     fn satisfy_after() {
         let height = Height::from_consensus(42).unwrap();
@@ -425,6 +629,11 @@ mod tests {
 
         let policy2 = Policy::After(43);
         assert!(policy2.satisfy(&satisfier).is_err(), "unsatisfiable");
+
+        // Not a block height
+        let policy3 = Policy::After(600_000_000);
+        assert!(!policy3.is_satisfiable(&satisfier));
+        assert!(policy3.satisfy(&satisfier).is_err(), "unsatisfiable");
     }
 
     #[test]
@@ -451,6 +660,79 @@ mod tests {
         assert!(policy2.satisfy(&satisfier).is_err(), "unsatisfiable");
     }
 
+    #[test]
+    fn satisfy_older_time() {
+        let env = ElementsEnv::dummy_with(
+            elements::LockTime::ZERO,
+            elements::Sequence::from_512_second_intervals(12),
+        );
+        let satisfier = get_satisfier(&env);
+
+        let policy0 = Policy::OlderTime(10);
+        let satisfaction = policy0.satisfy_with_timelocks(&satisfier).unwrap();
+        assert_eq!(
+            satisfaction.required_sequence,
+            Some(elements::Sequence::from_512_second_intervals(10))
+        );
+        assert_eq!(satisfaction.required_locktime, None);
+        execute_successful(satisfaction.program, &env);
+
+        let policy1 = Policy::OlderTime(13);
+        assert!(policy1.satisfy(&satisfier).is_err(), "unsatisfiable");
+
+        // Block-based locks are not satisfied by a time-based sequence
+        let policy2 = Policy::Older(10);
+        assert!(policy2.satisfy(&satisfier).is_err(), "different units");
+    }
+
+    #[test]
+    fn satisfy_timeout_branch() {
+        let env = ElementsEnv::dummy_with(
+            elements::LockTime::ZERO,
+            elements::Sequence::from_consensus(144),
+        );
+        let satisfier = get_satisfier(&env);
+        let key = *satisfier.signatures.keys().next().unwrap();
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let unknown_key = Keypair::from_seckey_slice(&secp, &[1; 32])
+            .unwrap()
+            .x_only_public_key()
+            .0;
+        let images: Vec<_> = satisfier.preimages.keys().copied().collect();
+
+        // The hash branch is unsatisfiable, so the timeout branch is chosen
+        let policy = Policy::Or {
+            left: Arc::new(Policy::And {
+                left: Arc::new(Policy::Key(key)),
+                right: Arc::new(Policy::Sha256(sha256::Hash::from_byte_array([0; 32]))),
+            }),
+            right: Arc::new(Policy::And {
+                left: Arc::new(Policy::Key(key)),
+                right: Arc::new(Policy::Older(144)),
+            }),
+            weights: [1, 1],
+        };
+        let satisfaction = policy.satisfy_with_timelocks(&satisfier).unwrap();
+        assert_eq!(
+            satisfaction.required_sequence,
+            Some(elements::Sequence::from_consensus(144))
+        );
+        assert_eq!(satisfaction.required_locktime, None);
+        execute_successful(satisfaction.program, &env);
+
+        // Timelocks of the pruned branch are not reported
+        let policy = Policy::Or {
+            left: Arc::new(Policy::Sha256(images[0])),
+            right: Arc::new(Policy::And {
+                left: Arc::new(Policy::Key(unknown_key)),
+                right: Arc::new(Policy::Older(144)),
+            }),
+            weights: [1, 1],
+        };
+        let satisfaction = policy.satisfy_with_timelocks(&satisfier).unwrap();
+        assert_eq!(satisfaction.required_sequence, None);
+    }
+
     #[test]
     fn satisfy_and() {
         let env = ElementsEnv::dummy();
@@ -526,6 +808,7 @@ mod tests {
         let policy0 = Policy::Or {
             left: Arc::new(Policy::Sha256(images[0])),
             right: Arc::new(Policy::Sha256(images[1])),
+            weights: [1, 1],
         };
         assert_branch(&policy0, false);
 
@@ -534,6 +817,7 @@ mod tests {
         let policy1 = Policy::Or {
             left: Arc::new(Policy::Sha256(images[0])),
             right: Arc::new(Policy::Sha256(sha256::Hash::from_byte_array([1; 32]))),
+            weights: [1, 1],
         };
         assert_branch(&policy1, false);
 
@@ -542,6 +826,7 @@ mod tests {
         let policy2 = Policy::Or {
             left: Arc::new(Policy::Sha256(sha256::Hash::from_byte_array([0; 32]))),
             right: Arc::new(Policy::Sha256(images[1])),
+            weights: [1, 1],
         };
         assert_branch(&policy2, true);
 
@@ -550,10 +835,40 @@ mod tests {
         let policy3 = Policy::Or {
             left: Arc::new(Policy::Sha256(sha256::Hash::from_byte_array([0; 32]))),
             right: Arc::new(Policy::Sha256(sha256::Hash::from_byte_array([1; 32]))),
+            weights: [1, 1],
         };
         assert!(policy3.satisfy(&satisfier).is_err());
     }
 
+    #[test]
+    fn satisfy_weighted_or() {
+        let env = ElementsEnv::dummy();
+        let satisfier = get_satisfier(&env);
+        let image = *satisfier.preimages.keys().next().unwrap();
+        let unknown = |byte| Policy::Sha256(sha256::Hash::from_byte_array([byte; 32]));
+        let subs = vec![unknown(0), unknown(1), Policy::Sha256(image)];
+
+        let unweighted = Policy::Or {
+            left: Arc::new(subs[0].clone()),
+            right: Arc::new(Policy::Or {
+                left: Arc::new(subs[1].clone()),
+                right: Arc::new(subs[2].clone()),
+                weights: [1, 1],
+            }),
+            weights: [1, 1],
+        };
+        let weighted = Policy::weighted_or(vec![1, 1, 10].into_iter().zip(subs).collect());
+
+        let unweighted_program = unweighted.satisfy(&satisfier).expect("satisfiable");
+        let weighted_program = weighted.satisfy(&satisfier).expect("satisfiable");
+        // The likely branch is nested less deeply, so it takes one witness bit less
+        assert_eq!(to_witness(&unweighted_program).len(), 3);
+        assert_eq!(to_witness(&weighted_program).len(), 2);
+        assert!(weighted_program.bounds().cost < unweighted_program.bounds().cost);
+
+        execute_successful(weighted_program, &env);
+    }
+
     #[test]
     fn satisfy_thresh() {
         let env = ElementsEnv::dummy();
@@ -605,7 +920,7 @@ mod tests {
                 for &bit2 in &[true, false] {
                     let image2 = image_from_bit(bit2, 2);
 
-                    let policy = Policy::Threshold(
+                    let policy = Policy::threshold(
                         2,
                         vec![
                             Policy::Sha256(image0),
@@ -624,6 +939,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn satisfy_nested_matches_commitment() {
+        let env = ElementsEnv::dummy();
+        let satisfier = get_satisfier(&env);
+        let mut keys: Vec<_> = satisfier.signatures.keys().copied().collect();
+        keys.sort();
+        let image = *satisfier.preimages.keys().next().unwrap();
+        let unknown_image = sha256::Hash::from_byte_array([0; 32]);
+
+        let policy = |right_image| Policy::Or {
+            left: Arc::new(Policy::threshold(
+                2,
+                vec![
+                    Policy::Key(keys[0]),
+                    Policy::Sha256(unknown_image),
+                    Policy::Key(keys[1]),
+                ],
+            )),
+            right: Arc::new(Policy::And {
+                left: Arc::new(Policy::Key(keys[2])),
+                right: Arc::new(Policy::Sha256(right_image)),
+            }),
+            weights: [1, 1],
+        };
+
+        // Either branch can be taken; the program always commits to the whole policy
+        for satisfiable in [policy(image), policy(unknown_image)] {
+            let program = satisfiable.satisfy(&satisfier).expect("satisfiable");
+            assert_eq!(program.cmr(), satisfiable.commit().unwrap().cmr());
+            assert_eq!(program.cmr(), satisfiable.cmr());
+            execute_successful(program, &env);
+        }
+
+        let unsatisfiable = Policy::And {
+            left: Arc::new(policy(image)),
+            right: Arc::new(Policy::Sha256(unknown_image)),
+        };
+        assert!(unsatisfiable.satisfy(&satisfier).is_err());
+    }
+
     #[test]
     fn satisfy_asm() {
         let env = ElementsEnv::dummy();
@@ -662,4 +1017,61 @@ mod tests {
             }
         }
     }
+
+    /// Satisfier which counts how often preimages are looked up.
+    struct CountingSatisfier<'a, 'b> {
+        inner: &'b PolicySatisfier<'a, XOnlyPublicKey>,
+        sha256_lookups: std::cell::Cell<usize>,
+    }
+
+    impl<'a, 'b> Satisfier<XOnlyPublicKey> for CountingSatisfier<'a, 'b> {
+        fn lookup_sha256(&self, hash: &sha256::Hash) -> Option<Preimage32> {
+            self.sha256_lookups.set(self.sha256_lookups.get() + 1);
+            self.inner.lookup_sha256(hash)
+        }
+    }
+
+    #[test]
+    fn short_circuit() {
+        let env = ElementsEnv::dummy();
+        let satisfier = get_satisfier(&env);
+        let images: Vec<_> = satisfier.preimages.keys().copied().collect();
+        let counting = CountingSatisfier {
+            inner: &satisfier,
+            sha256_lookups: std::cell::Cell::new(0),
+        };
+
+        // Expensive to analyze: 64 preimage lookups
+        let expensive = Policy::threshold(
+            64,
+            (0..64)
+                .map(|i| Policy::Sha256(images[i % images.len()]))
+                .collect(),
+        );
+        let unknown = Policy::Sha256(sha256::Hash::from_byte_array([0; 32]));
+
+        let and = Policy::And {
+            left: Arc::new(unknown.clone()),
+            right: Arc::new(expensive.clone()),
+        };
+        assert!(!and.is_satisfiable(&counting));
+        assert_eq!(counting.sha256_lookups.get(), 1);
+        counting.sha256_lookups.set(0);
+        assert!(and.satisfy(&counting).is_err());
+        assert_eq!(counting.sha256_lookups.get(), 1);
+
+        let or = Policy::Or {
+            left: Arc::new(Policy::Sha256(images[0])),
+            right: Arc::new(expensive),
+            weights: [1, 1],
+        };
+        counting.sha256_lookups.set(0);
+        assert!(or.is_satisfiable(&counting));
+        assert_eq!(counting.sha256_lookups.get(), 1);
+
+        let thresh = Policy::threshold(1, vec![Policy::Sha256(images[0]), unknown]);
+        counting.sha256_lookups.set(0);
+        assert!(thresh.is_satisfiable(&counting));
+        assert_eq!(counting.sha256_lookups.get(), 1);
+    }
 }
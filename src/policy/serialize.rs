@@ -4,9 +4,10 @@
 
 use crate::jet::{Elements, Jet};
 use crate::node::{CoreConstructible, JetConstructible, WitnessConstructible};
+use crate::{types, FailEntropy, Value};
 use crate::{Cmr, ConstructNode, ToXOnlyPubkey};
-use crate::{FailEntropy, Value};
 
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::sync::Arc;
 
@@ -30,6 +31,92 @@ impl<J: Jet> AssemblyConstructible for Arc<ConstructNode<J>> {
     }
 }
 
+/// Set of jets used by a Simplicity fragment.
+///
+/// Constructing a fragment as a jet set computes the jets that the fragment uses
+/// without constructing the fragment itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JetSet(pub BTreeSet<Elements>);
+
+impl JetSet {
+    fn union(left: &Self, right: &Self) -> Self {
+        JetSet(left.0.union(&right.0).copied().collect())
+    }
+}
+
+impl CoreConstructible for JetSet {
+    fn iden() -> Self {
+        JetSet::default()
+    }
+
+    fn unit() -> Self {
+        JetSet::default()
+    }
+
+    fn injl(child: &Self) -> Self {
+        child.clone()
+    }
+
+    fn injr(child: &Self) -> Self {
+        child.clone()
+    }
+
+    fn take(child: &Self) -> Self {
+        child.clone()
+    }
+
+    fn drop_(child: &Self) -> Self {
+        child.clone()
+    }
+
+    fn comp(left: &Self, right: &Self) -> Result<Self, types::Error> {
+        Ok(JetSet::union(left, right))
+    }
+
+    fn case(left: &Self, right: &Self) -> Result<Self, types::Error> {
+        Ok(JetSet::union(left, right))
+    }
+
+    fn assertl(left: &Self, _right: Cmr) -> Result<Self, types::Error> {
+        Ok(left.clone())
+    }
+
+    fn assertr(_left: Cmr, right: &Self) -> Result<Self, types::Error> {
+        Ok(right.clone())
+    }
+
+    fn pair(left: &Self, right: &Self) -> Result<Self, types::Error> {
+        Ok(JetSet::union(left, right))
+    }
+
+    fn fail(_entropy: FailEntropy) -> Self {
+        JetSet::default()
+    }
+
+    fn const_word(_word: Arc<Value>) -> Self {
+        JetSet::default()
+    }
+}
+
+impl JetConstructible<Elements> for JetSet {
+    fn jet(jet: Elements) -> Self {
+        JetSet(BTreeSet::from([jet]))
+    }
+}
+
+impl<W> WitnessConstructible<W> for JetSet {
+    fn witness(_witness: W) -> Self {
+        JetSet::default()
+    }
+}
+
+impl AssemblyConstructible for JetSet {
+    /// The jets of an assembly fragment are unknown
+    fn assembly(_cmr: Cmr) -> Option<Self> {
+        None
+    }
+}
+
 pub fn unsatisfiable<N>(entropy: FailEntropy) -> N
 where
     N: CoreConstructible,
@@ -82,14 +169,56 @@ where
     N::comp(&const_n, &check_lock_distance).expect("consistent types")
 }
 
-pub fn compute_sha256<N>(witness256: &N) -> N
+pub fn older_time<N>(n: u16) -> N
 where
     N: CoreConstructible + JetConstructible<Elements>,
 {
+    let n_value = Value::u16(n);
+    let const_n = N::const_word(n_value);
+    let check_lock_duration = N::jet(Elements::CheckLockDuration);
+
+    N::comp(&const_n, &check_lock_duration).expect("consistent types")
+}
+
+/// Bit width of hash preimages in compiled policies, unless configured otherwise.
+pub const DEFAULT_PREIMAGE_BITS: usize = 256;
+
+/// Return the jet that adds a preimage of the given bit width to a SHA256 context.
+///
+/// Returns `None` if there is no such jet.
+fn sha256_add_jet(preimage_bits: usize) -> Option<Elements> {
+    match preimage_bits {
+        8 => Some(Elements::Sha256Ctx8Add1),
+        16 => Some(Elements::Sha256Ctx8Add2),
+        32 => Some(Elements::Sha256Ctx8Add4),
+        64 => Some(Elements::Sha256Ctx8Add8),
+        128 => Some(Elements::Sha256Ctx8Add16),
+        256 => Some(Elements::Sha256Ctx8Add32),
+        512 => Some(Elements::Sha256Ctx8Add64),
+        1024 => Some(Elements::Sha256Ctx8Add128),
+        2048 => Some(Elements::Sha256Ctx8Add256),
+        4096 => Some(Elements::Sha256Ctx8Add512),
+        _ => None,
+    }
+}
+
+/// witness: 1 → 2^preimage_bits
+///
+/// compute_sha256(witness): 1 → 2^256
+///
+/// ## Panics
+///
+/// The preimage width is not a power of two between 8 and 4096.
+pub fn compute_sha256<N>(witness: &N, preimage_bits: usize) -> N
+where
+    N: CoreConstructible + JetConstructible<Elements>,
+{
+    let add_jet = sha256_add_jet(preimage_bits)
+        .unwrap_or_else(|| panic!("unsupported preimage width of {} bits", preimage_bits));
     let ctx = N::jet(Elements::Sha256Ctx8Init);
-    let pair_ctx_witness = N::pair(&ctx, witness256).expect("consistent types");
-    let add256 = N::jet(Elements::Sha256Ctx8Add32);
-    let digest_ctx = N::comp(&pair_ctx_witness, &add256).expect("consistent types");
+    let pair_ctx_witness = N::pair(&ctx, witness).expect("consistent types");
+    let add = N::jet(add_jet);
+    let digest_ctx = N::comp(&pair_ctx_witness, &add).expect("consistent types");
     let finalize = N::jet(Elements::Sha256Ctx8Finalize);
     N::comp(&digest_ctx, &finalize).expect("consistent types")
 }
@@ -104,14 +233,47 @@ where
 }
 
 pub fn sha256<Pk, N, W>(hash: &Pk::Sha256, witness: W) -> N
+where
+    Pk: ToXOnlyPubkey,
+    N: CoreConstructible + JetConstructible<Elements> + WitnessConstructible<W>,
+{
+    sha256_with_width::<Pk, N, W>(hash, witness, DEFAULT_PREIMAGE_BITS)
+}
+
+/// Like [`sha256`], but the preimage is `preimage_bits` wide.
+pub fn sha256_with_width<Pk, N, W>(hash: &Pk::Sha256, witness: W, preimage_bits: usize) -> N
 where
     Pk: ToXOnlyPubkey,
     N: CoreConstructible + JetConstructible<Elements> + WitnessConstructible<W>,
 {
     let hash_value = Value::u256_from_slice(Pk::to_sha256(hash).as_ref());
     let const_hash = N::const_word(hash_value);
-    let witness256 = N::witness(witness);
-    let computed_hash = compute_sha256(&witness256);
+    let preimage = N::witness(witness);
+    let computed_hash = compute_sha256(&preimage, preimage_bits);
+    let pair_hash_computed_hash = N::pair(&const_hash, &computed_hash).expect("consistent types");
+    let eq256 = N::jet(Elements::Eq256);
+
+    verify_bexp(&pair_hash_computed_hash, &eq256)
+}
+
+pub fn hash256<Pk, N, W>(hash: &Pk::Hash256, witness: W) -> N
+where
+    Pk: ToXOnlyPubkey,
+    N: CoreConstructible + JetConstructible<Elements> + WitnessConstructible<W>,
+{
+    hash256_with_width::<Pk, N, W>(hash, witness, DEFAULT_PREIMAGE_BITS)
+}
+
+/// Like [`hash256`], but the preimage is `preimage_bits` wide.
+pub fn hash256_with_width<Pk, N, W>(hash: &Pk::Hash256, witness: W, preimage_bits: usize) -> N
+where
+    Pk: ToXOnlyPubkey,
+    N: CoreConstructible + JetConstructible<Elements> + WitnessConstructible<W>,
+{
+    let hash_value = Value::u256_from_slice(Pk::to_hash256(hash).as_ref());
+    let const_hash = N::const_word(hash_value);
+    let preimage = N::witness(witness);
+    let computed_hash = compute_sha256(&compute_sha256(&preimage, preimage_bits), 256);
     let pair_hash_computed_hash = N::pair(&const_hash, &computed_hash).expect("consistent types");
     let eq256 = N::jet(Elements::Eq256);
 
@@ -237,7 +399,7 @@ mod tests {
     use super::*;
     use crate::jet::elements::ElementsEnv;
     use crate::node::SimpleFinalizer;
-    use crate::policy::Policy;
+    use crate::policy::{Policy, Preimage32, Satisfier};
     use crate::{BitMachine, CommitNode, FailEntropy, Value};
     use elements::bitcoin::key::XOnlyPublicKey;
     use elements::locktime::Height;
@@ -421,6 +583,7 @@ mod tests {
         let (commit, env) = compile(Policy::Or {
             left: Arc::new(Policy::Sha256(image0)),
             right: Arc::new(Policy::Sha256(image1)),
+            weights: [1, 1],
         });
 
         let valid_witness = vec![
@@ -450,6 +613,96 @@ mod tests {
         assert!(!execute_successful(&commit, invalid_witness, &env));
     }
 
+    #[test]
+    fn pruning_savings_or() {
+        struct Preimage(sha256::Hash, Preimage32);
+
+        impl Satisfier<XOnlyPublicKey> for Preimage {
+            fn lookup_sha256(&self, hash: &sha256::Hash) -> Option<Preimage32> {
+                (*hash == self.0).then(|| self.1)
+            }
+        }
+
+        let preimage0 = [1; 32];
+        let image0 = sha256::Hash::hash(&preimage0);
+        let image1 = sha256::Hash::hash(&[2; 32]);
+
+        let policy = Policy::Or {
+            left: Arc::new(Policy::Sha256(image0)),
+            right: Arc::new(Policy::Sha256(image1)),
+            weights: [1, 1],
+        };
+        let (commit, env) = compile(policy.clone());
+        // Only the left branch is taken
+        let witness = vec![
+            Value::u1(0),
+            Value::u256_from_slice(&preimage0),
+            Value::u256_from_slice(&[0; 32]),
+        ];
+        let finalized = commit
+            .finalize(&mut SimpleFinalizer::new(witness.into_iter()))
+            .expect("finalize");
+
+        let savings = finalized.pruning_savings(&env).expect("execution succeeds");
+        assert!(savings > 0);
+
+        // The satisfier hides the right branch, for which it has no preimage
+        let satisfied = policy
+            .satisfy(&Preimage(image0, preimage0))
+            .expect("satisfiable");
+        assert_eq!(satisfied.cmr(), finalized.cmr());
+        let mut sink = crate::BitWriter::new(std::io::sink());
+        let finalized_len = finalized.encode(&mut sink).unwrap();
+        let satisfied_len = satisfied.encode(&mut sink).unwrap();
+        assert_eq!(finalized_len - satisfied_len, savings);
+
+        let pruned = finalized.prune(&env).expect("execution succeeds");
+        assert_eq!(pruned.cmr(), finalized.cmr());
+        let pruned_len = pruned.encode(&mut sink).unwrap();
+        assert_eq!(finalized_len - pruned_len, savings);
+
+        let mut mac = BitMachine::for_program(&pruned);
+        assert!(mac.exec(&pruned, &env).is_ok());
+    }
+
+    #[test]
+    fn weighted_or_cost() {
+        struct Preimage(sha256::Hash, Preimage32);
+
+        impl Satisfier<XOnlyPublicKey> for Preimage {
+            fn lookup_sha256(&self, hash: &sha256::Hash) -> Option<Preimage32> {
+                (*hash == self.0).then(|| self.1)
+            }
+        }
+
+        let preimage = [1; 32];
+        let images: Vec<_> = (1..=3u8).map(|i| sha256::Hash::hash(&[i; 32])).collect();
+        let satisfier = Preimage(images[0], preimage);
+        // The first branch is very likely and the only one that is satisfied
+        let satisfy = |weights: &[u32]| {
+            let weighted_subs = weights
+                .iter()
+                .copied()
+                .zip(images.iter().copied().map(Policy::Sha256))
+                .collect();
+            let policy = Policy::<XOnlyPublicKey>::weighted_or(weighted_subs);
+            let program = policy.satisfy(&satisfier).expect("satisfiable");
+            let mut mac = BitMachine::for_program(&program);
+            assert!(mac.exec(&program, &ElementsEnv::dummy()).is_ok());
+            let mut sink = crate::BitWriter::new(std::io::sink());
+            let len = program.encode(&mut sink).unwrap();
+            (program.bounds().cost, len)
+        };
+
+        // The branches of a single `or` fragment cost the same
+        assert_eq!(satisfy(&[99, 1]), satisfy(&[1, 1]));
+        // Many branches are nested so that the likely one is cheaper
+        let (weighted_cost, weighted_len) = satisfy(&[9, 1, 1]);
+        let (uniform_cost, uniform_len) = satisfy(&[1, 1, 1]);
+        assert!(weighted_cost < uniform_cost);
+        assert!(weighted_len < uniform_len);
+    }
+
     #[test]
     fn execute_threshold() {
         let preimage0 = [1; 32];
@@ -459,7 +712,7 @@ mod tests {
         let preimage2 = [3; 32];
         let image2 = sha256::Hash::hash(&preimage2);
 
-        let (commit, env) = compile(Policy::Threshold(
+        let (commit, env) = compile(Policy::threshold(
             2,
             vec![
                 Policy::Sha256(image0),
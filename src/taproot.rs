@@ -0,0 +1,401 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Taproot Support
+//!
+//! Helpers for committing to Simplicity programs inside Bitcoin Taproot trees.
+//!
+//! A Simplicity leaf is a tap leaf whose "script" consists of the 32 bytes of
+//! the program CMR, tagged with the Simplicity leaf version.
+
+#[cfg(feature = "elements")]
+use std::cmp::Reverse;
+#[cfg(feature = "elements")]
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1;
+use bitcoin::taproot::{LeafVersion, TaprootBuilder};
+#[cfg(feature = "elements")]
+use bitcoin::taproot::{TaprootMerkleBranch, TaprootSpendInfo};
+use bitcoin::{ScriptBuf, Weight, Witness};
+
+use crate::dag::{DagLike, MaxSharing};
+use crate::jet::Jet;
+use crate::node::Redeem;
+#[cfg(feature = "elements")]
+use crate::policy::{Policy, ToXOnlyPubkey};
+use crate::{encode, write_to_vec, Cmr, RedeemNode, LEAF_VERSION};
+
+/// Number of bytes of a transaction input outside its witness:
+/// the outpoint, the length of the empty script sig and the sequence number.
+const NON_WITNESS_INPUT_BYTES: u64 = 32 + 4 + 1 + 4;
+
+/// Return the version of Simplicity leaves inside a tap tree.
+pub fn leaf_version() -> LeafVersion {
+    LeafVersion::from_consensus(LEAF_VERSION).expect("constant leaf version")
+}
+
+/// Return the tap leaf script of the Simplicity program with the given CMR.
+pub fn leaf_script(cmr: Cmr) -> ScriptBuf {
+    ScriptBuf::from(cmr.as_ref().to_vec())
+}
+
+/// Return the serialized BIP-341 control block for spending the Simplicity leaf
+/// with the given CMR.
+///
+/// The tree must already contain the leaf, as added by
+/// `tree.add_leaf_with_ver(depth, leaf_script(cmr), leaf_version())`.
+///
+/// Returns `None` if the tree is incomplete or does not contain the leaf.
+pub fn control_block(
+    internal_key: XOnlyPublicKey,
+    cmr: Cmr,
+    tree: TaprootBuilder,
+) -> Option<Vec<u8>> {
+    let secp = secp256k1::Secp256k1::verification_only();
+    let spend_info = tree.finalize(&secp, internal_key).ok()?;
+    let control_block = spend_info.control_block(&(leaf_script(cmr), leaf_version()))?;
+    Some(control_block.serialize())
+}
+
+/// Weight of a transaction input that spends a Simplicity leaf.
+///
+/// Fees are paid per weight unit. Bytes of the witness are discounted
+/// and cost one weight unit each, all other bytes cost four weight units each.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InputWeight {
+    /// Weight of the outpoint, the empty script sig and the sequence number
+    pub non_witness: Weight,
+    /// Weight of the witness stack: the encoded witness data, the encoded program,
+    /// the leaf script and the control block
+    pub witness: Weight,
+}
+
+impl InputWeight {
+    /// Return the total weight of the input.
+    pub fn total(&self) -> Weight {
+        self.non_witness + self.witness
+    }
+}
+
+/// Return the weight of a transaction input that spends the given program
+/// using the given serialized control block.
+///
+/// The witness stack of the input is that of [`witness_stack`], without an annex.
+pub fn input_weight<J: Jet>(program: &RedeemNode<J>, control_block: &[u8]) -> InputWeight {
+    let witness = witness_from_control_block(program, control_block);
+    InputWeight {
+        non_witness: Weight::from_non_witness_data_size(NON_WITNESS_INPUT_BYTES),
+        witness: Weight::from_witness_data_size(witness.size() as u64),
+    }
+}
+
+/// Return the witness stack for spending the Simplicity leaf of the given program.
+///
+/// The stack consists of four elements: the encoded witness data, the encoded program
+/// without witness data, the leaf script, which is the CMR of the program, and the
+/// control block. The witness data and the program are each padded to full bytes.
+/// The tree must already contain the leaf, like for [`control_block`].
+///
+/// Returns `None` if the tree is incomplete or does not contain the leaf.
+pub fn witness_stack<J: Jet>(
+    program: &RedeemNode<J>,
+    internal_key: XOnlyPublicKey,
+    tree: TaprootBuilder,
+) -> Option<Witness> {
+    let control_block = control_block(internal_key, program.cmr(), tree)?;
+    Some(witness_from_control_block(program, &control_block))
+}
+
+fn witness_from_control_block<J: Jet>(program: &RedeemNode<J>, control_block: &[u8]) -> Witness {
+    let witness_bytes = write_to_vec(|w| {
+        let witnesses = program
+            .post_order_iter::<MaxSharing<Redeem<J>>>()
+            .into_witnesses()
+            .map(Arc::as_ref);
+        encode::encode_witness(witnesses, w)
+    });
+    let program_bytes = write_to_vec(|w| encode::encode_program(program, w));
+    let script = leaf_script(program.cmr());
+    Witness::from_slice(&[
+        &witness_bytes[..],
+        &program_bytes[..],
+        script.as_bytes(),
+        control_block,
+    ])
+}
+
+/// Simplicity leaf of a tap tree, as built by [`policy_tree`].
+#[cfg(feature = "elements")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyLeaf {
+    /// CMR of the compiled policy
+    pub cmr: Cmr,
+    /// Merkle path from the leaf to the root of the tree
+    pub merkle_branch: TaprootMerkleBranch,
+}
+
+/// Compile alternative spending policies into Simplicity leaves and arrange them
+/// into a tap tree.
+///
+/// The tree minimizes the expected size of the control block, where the policies
+/// are spent with probabilities proportional to the given weights. Without weights,
+/// all policies are equally likely. The leaves are returned in the order of the policies.
+///
+/// Returns `None` if there are no policies or if the number of weights differs
+/// from the number of policies.
+#[cfg(feature = "elements")]
+pub fn policy_tree<Pk: ToXOnlyPubkey>(
+    internal_key: XOnlyPublicKey,
+    policies: &[Policy<Pk>],
+    weights: Option<&[u32]>,
+) -> Option<(TaprootSpendInfo, Vec<PolicyLeaf>)> {
+    let weights = match weights {
+        Some(weights) if weights.len() == policies.len() => weights.to_vec(),
+        Some(_) => return None,
+        None => vec![1; policies.len()],
+    };
+    let cmrs: Vec<Cmr> = policies.iter().map(Policy::cmr).collect();
+    let depths = huffman_depths(&weights)?;
+
+    // Adding the leaves from deepest to shallowest yields a valid tree
+    let mut order: Vec<usize> = (0..cmrs.len()).collect();
+    order.sort_by_key(|&i| Reverse(depths[i]));
+    let mut tree = TaprootBuilder::new();
+    for i in order {
+        tree = tree
+            .add_leaf_with_ver(depths[i], leaf_script(cmrs[i]), leaf_version())
+            .expect("Huffman depths form a complete tree");
+    }
+
+    let secp = secp256k1::Secp256k1::verification_only();
+    let spend_info = tree.finalize(&secp, internal_key).ok()?;
+    let leaves = cmrs
+        .into_iter()
+        .map(|cmr| {
+            let control_block = spend_info
+                .control_block(&(leaf_script(cmr), leaf_version()))
+                .expect("leaf in tree");
+            PolicyLeaf {
+                cmr,
+                merkle_branch: control_block.merkle_branch,
+            }
+        })
+        .collect();
+    Some((spend_info, leaves))
+}
+
+/// Return the depths of the leaves of a Huffman tree with the given weights.
+///
+/// Returns `None` if there are no weights.
+#[cfg(feature = "elements")]
+fn huffman_depths(weights: &[u32]) -> Option<Vec<u8>> {
+    // Children of each inner node, which are numbered after the leaves
+    let mut children = vec![];
+    let mut heap: BinaryHeap<_> = weights
+        .iter()
+        .enumerate()
+        .map(|(i, &weight)| Reverse((u64::from(weight), i)))
+        .collect();
+    while heap.len() > 1 {
+        let Reverse((left_weight, left)) = heap.pop().unwrap();
+        let Reverse((right_weight, right)) = heap.pop().unwrap();
+        heap.push(Reverse((
+            left_weight + right_weight,
+            weights.len() + children.len(),
+        )));
+        children.push((left, right));
+    }
+    let Reverse((_, root)) = heap.pop()?;
+
+    let mut depths = vec![0; weights.len()];
+    let mut stack = vec![(root, 0)];
+    while let Some((node, depth)) = stack.pop() {
+        match node.checked_sub(weights.len()) {
+            Some(inner) => {
+                let (left, right) = children[inner];
+                stack.push((left, depth + 1));
+                stack.push((right, depth + 1));
+            }
+            None => depths[node] = depth,
+        }
+    }
+    Some(depths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jet::Core;
+    use crate::node::CoreConstructible;
+    use crate::{BitIter, CommitNode, ConstructNode};
+
+    fn internal_key() -> XOnlyPublicKey {
+        let secp = secp256k1::Secp256k1::new();
+        let keypair = secp256k1::Keypair::from_seckey_slice(&secp, &[1; 32]).expect("valid key");
+        keypair.x_only_public_key().0
+    }
+
+    #[test]
+    fn single_leaf_control_block() {
+        let cmr = Arc::<ConstructNode<Core>>::unit().cmr();
+        let internal_key = internal_key();
+        let tree = TaprootBuilder::new()
+            .add_leaf_with_ver(0, leaf_script(cmr), leaf_version())
+            .unwrap();
+
+        let secp = secp256k1::Secp256k1::verification_only();
+        let parity = tree
+            .clone()
+            .finalize(&secp, internal_key)
+            .unwrap()
+            .output_key_parity();
+
+        let control_block = control_block(internal_key, cmr, tree).expect("leaf in tree");
+        // Leaf version and parity byte, internal key, empty Merkle path
+        assert_eq!(control_block.len(), 33);
+        assert_eq!(control_block[0] & 0xfe, LEAF_VERSION);
+        assert_eq!(control_block[0] & 0x01, parity.to_u8());
+        assert_eq!(&control_block[1..], &internal_key.serialize());
+    }
+
+    #[test]
+    fn leaf_hash_commits_to_leaf_version() {
+        use bitcoin::taproot::TapLeafHash;
+        use hashes::{sha256, Hash, HashEngine};
+
+        let cmr = Arc::<ConstructNode<Core>>::unit().cmr();
+        let leaf_hash = TapLeafHash::from_script(&leaf_script(cmr), leaf_version());
+
+        // BIP-341: tagged hash of leaf version and length-prefixed script
+        let tag = sha256::Hash::hash(b"TapLeaf");
+        let mut engine = sha256::Hash::engine();
+        engine.input(tag.as_ref());
+        engine.input(tag.as_ref());
+        engine.input(&[LEAF_VERSION, 32]);
+        engine.input(cmr.as_ref());
+        let expected = sha256::Hash::from_engine(engine);
+
+        assert_eq!(leaf_hash.to_byte_array(), expected.to_byte_array());
+    }
+
+    #[test]
+    #[cfg(feature = "elements")]
+    fn policy_tree_control_blocks() {
+        let secp = secp256k1::Secp256k1::new();
+        let key = |i| {
+            secp256k1::Keypair::from_seckey_slice(&secp, &[i; 32])
+                .unwrap()
+                .x_only_public_key()
+                .0
+        };
+        let policies = [
+            Policy::Key(key(2)),
+            Policy::And {
+                left: Arc::new(Policy::Key(key(3))),
+                right: Arc::new(Policy::Older(144)),
+            },
+        ];
+
+        let (spend_info, leaves) = policy_tree(internal_key(), &policies, None).unwrap();
+        assert_eq!(leaves.len(), 2);
+        for (policy, leaf) in policies.iter().zip(&leaves) {
+            assert_eq!(leaf.cmr, policy.cmr());
+            assert_eq!(leaf.merkle_branch.len(), 1);
+
+            let control_block = spend_info
+                .control_block(&(leaf_script(leaf.cmr), leaf_version()))
+                .unwrap();
+            assert_eq!(control_block.merkle_branch, leaf.merkle_branch);
+            assert!(control_block.verify_taproot_commitment(
+                &secp,
+                spend_info.output_key().to_inner(),
+                &leaf_script(leaf.cmr),
+            ));
+        }
+
+        // A likely policy gets a shorter path
+        let mut three = policies.to_vec();
+        three.push(Policy::Older(1000));
+        let (_, leaves) = policy_tree(internal_key(), &three, Some(&[1, 1, 10])).unwrap();
+        let depths: Vec<_> = leaves.iter().map(|leaf| leaf.merkle_branch.len()).collect();
+        assert_eq!(depths, [2, 2, 1]);
+        assert!(policy_tree(internal_key(), &three, Some(&[1])).is_none());
+    }
+
+    #[test]
+    fn input_weight_of_unit() {
+        use crate::node::SimpleFinalizer;
+
+        let program = Arc::<ConstructNode<Core>>::unit()
+            .finalize_types()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+            .unwrap();
+        assert_eq!(program.encode_to_vec().len(), 1);
+
+        let cmr = program.cmr();
+        let tree = TaprootBuilder::new()
+            .add_leaf_with_ver(0, leaf_script(cmr), leaf_version())
+            .unwrap();
+        let control_block = control_block(internal_key(), cmr, tree).unwrap();
+
+        let weight = input_weight(&program, &control_block);
+        // 41 bytes outside the witness, at 4 weight units each
+        assert_eq!(weight.non_witness, Weight::from_wu(164));
+        // Stack length, then length-prefixed witness data (1 byte), program (1 byte),
+        // leaf script (32 bytes) and control block (33 bytes)
+        assert_eq!(weight.witness, Weight::from_wu(1 + 2 + 2 + 33 + 34));
+        assert_eq!(weight.total(), Weight::from_wu(236));
+    }
+
+    #[test]
+    fn single_leaf_witness_stack() {
+        use crate::node::SimpleFinalizer;
+
+        let program = Arc::<ConstructNode<Core>>::unit()
+            .finalize_types()
+            .unwrap()
+            .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+            .unwrap();
+        let cmr = program.cmr();
+        let tree = TaprootBuilder::new()
+            .add_leaf_with_ver(0, leaf_script(cmr), leaf_version())
+            .unwrap();
+
+        let witness = witness_stack(&program, internal_key(), tree.clone()).unwrap();
+        let stack: Vec<&[u8]> = witness.iter().collect();
+        assert_eq!(stack.len(), 4);
+        // Empty witness data
+        assert_eq!(stack[0], &[0x00]);
+        let decoded = CommitNode::<Core>::decode(&mut BitIter::from(stack[1])).unwrap();
+        assert_eq!(decoded.cmr(), cmr);
+        assert_eq!(stack[2], cmr.as_ref());
+        assert_eq!(
+            stack[3],
+            &control_block(internal_key(), cmr, tree).unwrap()[..]
+        );
+        assert_eq!(
+            Weight::from_witness_data_size(witness.size() as u64),
+            input_weight(&program, stack[3]).witness
+        );
+
+        let iden = Arc::<ConstructNode<Core>>::iden().cmr();
+        let other_tree = TaprootBuilder::new()
+            .add_leaf_with_ver(0, leaf_script(iden), leaf_version())
+            .unwrap();
+        assert!(witness_stack(&program, internal_key(), other_tree).is_none());
+    }
+
+    #[test]
+    fn control_block_missing_leaf() {
+        let unit = Arc::<ConstructNode<Core>>::unit().cmr();
+        let iden = Arc::<ConstructNode<Core>>::iden().cmr();
+        let tree = TaprootBuilder::new()
+            .add_leaf_with_ver(0, leaf_script(unit), leaf_version())
+            .unwrap();
+
+        assert!(control_block(internal_key(), iden, tree).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Test Vectors
+//!
+//! Programs from the test suite of the C implementation of Simplicity,
+//! along with their expected Merkle roots, costs and execution results.
+//!
+//! Downstream crates can use these vectors for their own conformance tests.
+
+use crate::ffi::tests::{self as ffi_tests, ffi::SimplicityErr, TestData};
+use crate::{Amr, Cmr, Cost, Imr};
+
+/// A bundled test program with its expected properties.
+#[derive(Clone, Debug)]
+pub struct TestVector {
+    /// Name of the program in the C test suite
+    pub name: &'static str,
+    /// Bit encoding of the program, including its witness data
+    pub program: Vec<u8>,
+    /// Expected CMR of the program
+    pub cmr: Cmr,
+    /// Expected AMR of the program
+    pub amr: Amr,
+    /// Expected IMR of the program
+    pub imr: Imr,
+    /// Expected worst-case cost of the program
+    pub cost: Cost,
+    /// Expected result of decoding, type-checking and executing the program
+    /// with the C implementation
+    pub result: SimplicityErr,
+}
+
+impl TestVector {
+    fn new(name: &'static str, data: TestData, result: SimplicityErr) -> Self {
+        TestVector {
+            name,
+            program: data.prog,
+            cmr: Cmr::from_byte_array(data.cmr),
+            amr: Amr::from_byte_array(data.amr),
+            imr: Imr::from_byte_array(data.imr),
+            cost: Cost::from_milliweight(data.cost),
+            result,
+        }
+    }
+}
+
+/// Return all bundled test vectors.
+///
+/// `hashBlock` is an expression of type `2^512 → 2^256`, not a program,
+/// so it fails type-checking as a program.
+pub fn all() -> Vec<TestVector> {
+    vec![
+        TestVector::new(
+            "hashBlock",
+            ffi_tests::hash_block_test_data(),
+            SimplicityErr::TypeInferenceNotProgram,
+        ),
+        TestVector::new(
+            "ctx8Pruned",
+            ffi_tests::ctx8_pruned_test_data(),
+            SimplicityErr::NoError,
+        ),
+        TestVector::new(
+            "ctx8Unpruned",
+            ffi_tests::ctx8_unpruned_test_data(),
+            SimplicityErr::AntiDoS,
+        ),
+        TestVector::new(
+            "schnorr0",
+            ffi_tests::schnorr0_test_data(),
+            SimplicityErr::NoError,
+        ),
+        TestVector::new(
+            "schnorr6",
+            ffi_tests::schnorr6_test_data(),
+            SimplicityErr::ExecJet,
+        ),
+    ]
+}
+
+/// Return the bundled test vectors with the given CMR.
+///
+/// Pruned and unpruned versions of a program share their CMR,
+/// so there may be more than one.
+pub fn by_cmr(cmr: Cmr) -> Vec<TestVector> {
+    all()
+        .into_iter()
+        .filter(|vector| vector.cmr == cmr)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::tests::{run_program, TestUpTo};
+    use crate::jet::Elements;
+    use crate::{BitIter, RedeemNode};
+
+    #[test]
+    fn vector_cmrs() {
+        for vector in all() {
+            let mut bits = BitIter::from(&vector.program[..]);
+            let cmr = match RedeemNode::<Elements>::decode(&mut bits) {
+                Ok(program) => program.cmr(),
+                // Expressions that are not programs can only be decoded as such
+                Err(_) => {
+                    let mut bits = BitIter::from(&vector.program[..]);
+                    crate::decode::decode_expression::<_, Elements>(&mut bits)
+                        .expect(vector.name)
+                        .cmr()
+                }
+            };
+            assert_eq!(cmr, vector.cmr, "{}", vector.name);
+            assert!(by_cmr(cmr).iter().any(|v| v.name == vector.name));
+
+            let result = match run_program(&vector.program, TestUpTo::Everything) {
+                Ok(output) => output.eval_result,
+                Err(e) => e,
+            };
+            assert_eq!(result, vector.result, "{}", vector.name);
+        }
+    }
+}
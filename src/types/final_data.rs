@@ -166,6 +166,17 @@ impl Final {
         super::precomputed::nth_power_of_2(n).final_data().unwrap()
     }
 
+    /// Create the type of words with the given bit width.
+    ///
+    /// Returns `None` if the bit width is not a power of two.
+    pub fn word(bit_width: usize) -> Option<Arc<Self>> {
+        if bit_width.is_power_of_two() {
+            Some(Self::two_two_n(bit_width.trailing_zeros() as usize))
+        } else {
+            None
+        }
+    }
+
     /// Create the sum of the given `left` and `right` types.
     pub fn sum(left: Arc<Self>, right: Arc<Self>) -> Arc<Self> {
         Arc::new(Final {
@@ -254,4 +265,22 @@ mod tests {
         let ty1 = Final::sum(Final::unit(), Final::two_two_n(2));
         assert_eq!(ty1.to_string(), "2^4?");
     }
+
+    #[test]
+    fn final_bit_width() {
+        assert_eq!(Final::unit().bit_width(), 0);
+        assert_eq!(Final::sum(Final::unit(), Final::unit()).bit_width(), 1);
+        assert_eq!(Final::word(1).unwrap(), Final::two_two_n(0));
+        assert_eq!(Final::word(256).unwrap(), Final::two_two_n(8));
+        assert_eq!(Final::word(256).unwrap().bit_width(), 256);
+        assert!(Final::word(0).is_none());
+        assert!(Final::word(24).is_none());
+
+        // 1 + 2^8: tag bit plus padding to the wider summand
+        let option_byte = Final::sum(Final::unit(), Final::word(8).unwrap());
+        assert_eq!(option_byte.bit_width(), 9);
+        // (1 + 2^8) × 2^32
+        let prod = Final::product(option_byte, Final::word(32).unwrap());
+        assert_eq!(prod.bit_width(), 41);
+    }
 }
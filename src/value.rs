@@ -6,7 +6,7 @@
 //! i.e., inputs, intermediate results and outputs.
 
 use crate::dag::{Dag, DagLike, NoSharing};
-use crate::types::Final;
+use crate::types::{Bound, Final, Type};
 
 use std::collections::VecDeque;
 use std::convert::TryInto;
@@ -126,6 +126,20 @@ impl Value {
         }
     }
 
+    /// Create the zero value of the given type.
+    ///
+    /// This is the value whose encoding consists of zero bits only,
+    /// i.e. every sum value is a left value.
+    pub fn zero(ty: &Final) -> Arc<Self> {
+        if let Some((left, _)) = ty.as_sum() {
+            Value::sum_l(Value::zero(left))
+        } else if let Some((left, right)) = ty.as_product() {
+            Value::prod(Value::zero(left), Value::zero(right))
+        } else {
+            Value::unit()
+        }
+    }
+
     /// Encode a single bit as a value. Will panic if the input is out of range
     pub fn u1(n: u8) -> Arc<Self> {
         match n {
@@ -172,6 +186,36 @@ impl Value {
         Value::prod(Value::u16(w0), Value::u16(w1))
     }
 
+    /// Encode a 32-bit number as a value, in big-endian byte order.
+    ///
+    /// Big endian is the native byte order of Simplicity words,
+    /// so this is the same as [`Value::u32`].
+    pub fn u32_be(n: u32) -> Arc<Self> {
+        Value::u32(n)
+    }
+
+    /// Encode a 32-bit number as a value, in little-endian byte order.
+    ///
+    /// The least significant byte of `n` becomes the first byte of the word.
+    /// This is useful for data that is little endian on the wire,
+    /// such as Bitcoin amounts.
+    pub fn u32_le(n: u32) -> Arc<Self> {
+        Value::u32(n.swap_bytes())
+    }
+
+    /// Decode a 32-bit word value as a number, in big-endian byte order.
+    ///
+    /// Returns `None` if the value is not a 32-bit word.
+    /// Values of [`Value::u32_le`] decode to the byte-swapped number.
+    pub fn to_u32(&self) -> Option<u32> {
+        if !self.is_of_type(&Final::two_two_n(5)) {
+            return None;
+        }
+        let mut n = 0u32;
+        self.do_each_bit(|bit| n = 2 * n + u32::from(bit));
+        Some(n)
+    }
+
     /// Encode a 64-bit number as a value
     pub fn u64(n: u64) -> Arc<Self> {
         let w0 = (n >> 32) as u32;
@@ -329,6 +373,45 @@ impl Value {
 
         true
     }
+
+    /// Check if the value conforms to the given type, which may be incomplete.
+    ///
+    /// Free type variables accept any value, so the check only covers the parts
+    /// of the type that are already known. For complete types, this is the same
+    /// as [`Value::is_of_type`].
+    pub fn has_type(&self, ty: &Type) -> bool {
+        let mut stack = vec![(self, ty.bound())];
+
+        while let Some((value, bound)) = stack.pop() {
+            match *bound {
+                Bound::Free(_) => {}
+                Bound::Complete(ref data) => {
+                    if !value.is_of_type(data) {
+                        return false;
+                    }
+                }
+                Bound::Sum(ref ty_l, ref ty_r) => {
+                    if let Some(value_l) = value.as_left() {
+                        stack.push((value_l, ty_l.bound()));
+                    } else if let Some(value_r) = value.as_right() {
+                        stack.push((value_r, ty_r.bound()));
+                    } else {
+                        return false;
+                    }
+                }
+                Bound::Product(ref ty_l, ref ty_r) => {
+                    if let Some((value_l, value_r)) = value.as_product() {
+                        stack.push((value_r, ty_r.bound()));
+                        stack.push((value_l, ty_l.bound()));
+                    } else {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
 }
 
 impl fmt::Debug for Value {
@@ -402,4 +485,56 @@ mod tests {
             assert!(value.is_of_type(ty.as_ref()));
         }
     }
+
+    #[test]
+    fn has_type() {
+        // 2^8 × (1 + A) for a free type A
+        let ty = Type::product(
+            Type::two_two_n(3),
+            Type::sum(Type::unit(), Type::free("A".to_owned())),
+        );
+
+        let matching = Value::prod(Value::u8(0xab), Value::sum_r(Value::u16(7)));
+        assert!(matching.has_type(&ty));
+        let matching = Value::prod(Value::u8(0xab), Value::sum_l(Value::unit()));
+        assert!(matching.has_type(&ty));
+
+        let wrong_width = Value::prod(Value::u16(0xab), Value::sum_l(Value::unit()));
+        assert!(!wrong_width.has_type(&ty));
+
+        // The tag of the sum is nested one level too deep
+        let wrong_depth = Value::prod(Value::u8(0xab), Value::sum_l(Value::sum_l(Value::unit())));
+        assert!(!wrong_depth.has_type(&ty));
+    }
+
+    #[test]
+    fn u32_byte_order() {
+        let n = 0x1234_5678;
+        let be = Value::u32_be(n);
+        let le = Value::u32_le(n);
+
+        assert_eq!(be, Value::u32(n));
+        assert_ne!(be, le);
+        assert_eq!(be.try_to_bytes().unwrap(), n.to_be_bytes());
+        assert_eq!(le.try_to_bytes().unwrap(), n.to_le_bytes());
+
+        assert_eq!(be.to_u32(), Some(n));
+        assert_eq!(le.to_u32(), Some(n.swap_bytes()));
+        assert_eq!(le.to_u32().map(u32::swap_bytes), Some(n));
+
+        assert_eq!(Value::u16(0x1234).to_u32(), None);
+        assert_eq!(Value::u64(0x1234).to_u32(), None);
+    }
+
+    #[test]
+    fn zero() {
+        assert_eq!(Value::zero(&TypeName(b"l").to_final()), Value::u64(0));
+
+        for typename in [&b"1"[..], b"+11", b"+1h", b"*1+11", b"+*22c"] {
+            let ty = TypeName(typename).to_final();
+            let value = Value::zero(&ty);
+            assert!(value.is_of_type(&ty));
+            value.do_each_bit(|bit| assert!(!bit));
+        }
+    }
 }